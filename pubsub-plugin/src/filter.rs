@@ -0,0 +1,94 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use pubsub_wire::Pubkey;
+
+/// Public key of Vote program, whose accounts are not published by default
+pub(crate) const VOTE_PROGRAM: &str = "Vote111111111111111111111111111111111111111";
+
+/// Filter of accounts, which should be published. Account lists take
+/// precedence over owner lists, and exclusions take precedence over
+/// inclusions, so that e.g. a single vote account can still be
+/// published, while the rest of Vote program accounts are excluded.
+/// If nothing is explicitly included, everything, that isn't
+/// excluded, is published
+#[derive(Default)]
+pub(crate) struct AccountFilter {
+    include_accounts: HashSet<Pubkey>,
+    exclude_accounts: HashSet<Pubkey>,
+    include_owners: HashSet<Pubkey>,
+    exclude_owners: HashSet<Pubkey>,
+    stats: Arc<FilterStats>,
+}
+
+/// Number of accounts, which have passed filter or have been
+/// filtered out, shared with task, which reports them to log
+#[derive(Default)]
+pub(crate) struct FilterStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl AccountFilter {
+    pub(crate) fn new(
+        include_accounts: HashSet<Pubkey>,
+        exclude_accounts: HashSet<Pubkey>,
+        include_owners: HashSet<Pubkey>,
+        exclude_owners: HashSet<Pubkey>,
+    ) -> Self {
+        Self {
+            include_accounts,
+            exclude_accounts,
+            include_owners,
+            exclude_owners,
+            stats: Arc::default(),
+        }
+    }
+
+    /// Check whether account should be published, and count the outcome
+    pub(crate) fn matches(&self, pubkey: &[u8], owner: &[u8]) -> bool {
+        let matches = self.check(pubkey, owner);
+        let counter = if matches {
+            &self.stats.hits
+        } else {
+            &self.stats.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        matches
+    }
+
+    pub(crate) fn stats(&self) -> Arc<FilterStats> {
+        Arc::clone(&self.stats)
+    }
+
+    fn check(&self, pubkey: &[u8], owner: &[u8]) -> bool {
+        let (pubkey, owner) = match (<&Pubkey>::try_from(pubkey), <&Pubkey>::try_from(owner)) {
+            (Ok(pubkey), Ok(owner)) => (pubkey, owner),
+            _ => return false,
+        };
+        if self.exclude_accounts.contains(pubkey) {
+            return false;
+        }
+        if self.include_accounts.contains(pubkey) {
+            return true;
+        }
+        if self.exclude_owners.contains(owner) {
+            return false;
+        }
+        if self.include_owners.contains(owner) {
+            return true;
+        }
+        self.include_accounts.is_empty() && self.include_owners.is_empty()
+    }
+}
+
+impl FilterStats {
+    /// Take number of hits and misses, counted since the last call
+    pub(crate) fn take(&self) -> (u64, u64) {
+        let hits = self.hits.swap(0, Ordering::Relaxed);
+        let misses = self.misses.swap(0, Ordering::Relaxed);
+        (hits, misses)
+    }
+}
@@ -1,116 +1,145 @@
-use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use bytes::Bytes;
-use rmp_serde as rmps;
-use serde::Serialize;
+use pubsub_wire::{AccountData, Commitment, Kind, SlotData, SnapshotData, Stamp};
 use solana_accountsdb_plugin_interface::accountsdb_plugin_interface::ReplicaAccountInfoVersions;
 use solana_accountsdb_plugin_interface::accountsdb_plugin_interface::SlotStatus as AccDBSlotStatus;
-use tokio_nsq::NSQTopic;
 
 enum Payload {
     Account(AccountData),
     Slot(SlotData),
+    Snapshot(SnapshotData),
 }
 
 struct Message {
     payload: Payload,
-    topic: Arc<NSQTopic>,
+    /// Stamp, which is put on message, right before it's published
+    stamp: Option<Stamp>,
+}
+
+/// Source of stamps for published messages, with sequence
+/// numbers counted separately for every kind of messages
+struct Sequencer {
+    session: u64,
+    next: [u64; 3],
 }
 
 impl Message {
+    /// Kind of message, which determines where it's published to
+    fn kind(&self) -> Kind {
+        match self.payload {
+            Payload::Account(_) => Kind::Account,
+            Payload::Slot(_) => Kind::Slot,
+            Payload::Snapshot(_) => Kind::Snapshot,
+        }
+    }
+
     fn serialize(&self) -> Option<Vec<u8>> {
-        match &self.payload {
-            Payload::Slot(v) => rmps::to_vec(v).ok(),
-            Payload::Account(v) => rmps::to_vec(v).ok(),
+        let encoded = match &self.payload {
+            Payload::Slot(v) => pubsub_wire::encode(v).ok(),
+            Payload::Account(v) => pubsub_wire::encode(v).ok(),
+            Payload::Snapshot(v) => pubsub_wire::encode(v).ok(),
+        }?;
+        match self.stamp {
+            Some(stamp) => Some(pubsub_wire::stamp(stamp, &encoded)),
+            None => Some(encoded),
         }
     }
 
-    fn from_account(
-        account: ReplicaAccountInfoVersions<'_>,
-        slot: Slot,
-        topic: Arc<NSQTopic>,
-    ) -> Self {
-        let mut account = AccountData::from(account);
-        account.slot = slot;
-        let payload = Payload::Account(account);
-        Self { payload, topic }
+    fn new(payload: Payload) -> Self {
+        Self {
+            payload,
+            stamp: None,
+        }
+    }
+
+    fn from_account(account: ReplicaAccountInfoVersions<'_>, slot: Slot) -> Self {
+        let account = account_data(account, slot);
+        Self::new(Payload::Account(account))
+    }
+
+    fn from_snapshot(snapshot: SnapshotData) -> Self {
+        Self::new(Payload::Snapshot(snapshot))
     }
 
-    fn from_slot(slot: Slot, parent: Slot, status: AccDBSlotStatus, topic: Arc<NSQTopic>) -> Self {
+    fn from_slot(slot: Slot, parent: Slot, status: AccDBSlotStatus) -> Self {
         let slot = SlotData {
             slot,
             parent,
-            status: status.into(),
+            status: commitment(status),
         };
-        let payload = Payload::Slot(slot);
-        Self { payload, topic }
+        Self::new(Payload::Slot(slot))
     }
 }
 
-type Pubkey = [u8; 32];
-
-type Slot = u64;
+impl Sequencer {
+    /// Start new session, identified by the time it has started at,
+    /// so that session ids grow with every restart of plugin
+    fn new() -> Self {
+        let session = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or_default();
+        Self {
+            session,
+            next: [0; 3],
+        }
+    }
 
-#[derive(Serialize)]
-struct AccountData {
-    /// Public key of given account
-    pubkey: Pubkey,
-    /// Public key owner program (if any) of given account
-    owner: Pubkey,
-    lamports: u64,
-    data: Bytes,
-    rent_epoch: u64,
-    executable: bool,
-    /// Slot number at which the update was generated
-    slot: Slot,
+    /// Stamp for the next message of given kind
+    fn next(&mut self, kind: Kind) -> Stamp {
+        let next = &mut self.next[kind as usize];
+        let stamp = Stamp {
+            session: self.session,
+            seq: *next,
+        };
+        *next += 1;
+        stamp
+    }
 }
 
-#[derive(Serialize)]
-struct SlotData {
-    slot: Slot,
-    parent: Slot,
-    status: Commitment,
-}
+type Slot = u64;
 
-#[derive(Serialize)]
-enum Commitment {
-    Processed = 1,
-    Confirmed = 2,
-    Finalized = 3,
+fn commitment(status: AccDBSlotStatus) -> Commitment {
+    match status {
+        AccDBSlotStatus::Processed => Commitment::Processed,
+        AccDBSlotStatus::Confirmed => Commitment::Confirmed,
+        AccDBSlotStatus::Rooted => Commitment::Finalized,
+    }
 }
 
-impl From<AccDBSlotStatus> for Commitment {
-    fn from(status: AccDBSlotStatus) -> Self {
-        match status {
-            AccDBSlotStatus::Processed => Self::Processed,
-            AccDBSlotStatus::Confirmed => Self::Confirmed,
-            AccDBSlotStatus::Rooted => Self::Finalized,
-        }
+/// Public key of account and its owner, without copying the rest of account
+fn account_keys<'a>(src: &ReplicaAccountInfoVersions<'a>) -> (&'a [u8], &'a [u8]) {
+    match src {
+        ReplicaAccountInfoVersions::V0_0_1(acc) => (acc.pubkey, acc.owner),
     }
 }
 
-impl From<ReplicaAccountInfoVersions<'_>> for AccountData {
-    fn from(src: ReplicaAccountInfoVersions<'_>) -> Self {
-        match src {
-            ReplicaAccountInfoVersions::V0_0_1(acc) => {
-                let mut pubkey = [0; 32];
-                pubkey.copy_from_slice(acc.pubkey);
-                let mut owner = [0; 32];
-                owner.copy_from_slice(acc.owner);
-                let data = Bytes::copy_from_slice(acc.data);
-                Self {
-                    pubkey,
-                    owner,
-                    lamports: acc.lamports,
-                    data,
-                    rent_epoch: acc.rent_epoch,
-                    executable: acc.executable,
-                    slot: 0,
-                }
+fn account_data(src: ReplicaAccountInfoVersions<'_>, slot: Slot) -> AccountData {
+    match src {
+        ReplicaAccountInfoVersions::V0_0_1(acc) => {
+            let mut pubkey = [0; 32];
+            pubkey.copy_from_slice(acc.pubkey);
+            let mut owner = [0; 32];
+            owner.copy_from_slice(acc.owner);
+            let data = Bytes::copy_from_slice(acc.data);
+            AccountData {
+                pubkey,
+                owner,
+                lamports: acc.lamports,
+                data,
+                rent_epoch: acc.rent_epoch,
+                executable: acc.executable,
+                slot,
             }
         }
     }
 }
 
+mod filter;
 mod plugin;
 mod publisher;
+mod spool;
+/// Tests of account filtering, spooling and transports
+mod tests;
+mod transport;
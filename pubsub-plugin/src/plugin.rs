@@ -1,4 +1,7 @@
-use std::{convert::TryFrom, fmt::Debug, fs::File, sync::Arc};
+use std::{
+    collections::HashSet, convert::TryFrom, fmt::Debug, fs::File, path::PathBuf, sync::Arc,
+    time::Duration,
+};
 
 use serde::Deserialize;
 use solana_accountsdb_plugin_interface::accountsdb_plugin_interface::{
@@ -7,20 +10,20 @@ use solana_accountsdb_plugin_interface::accountsdb_plugin_interface::{
 };
 use tokio::{
     runtime::{Builder, Runtime},
-    sync::mpsc::Sender,
+    sync::mpsc::{error::TrySendError, Sender},
 };
-use tokio_nsq::{NSQEvent, NSQProducerConfig, NSQTopic};
-
-use crate::{publisher::Publisher, Message};
+use tokio_nsq::NSQTopic;
 
-impl Default for Topics {
-    fn default() -> Self {
-        let accounts = NSQTopic::new("mopics").unwrap();
-        let slots = NSQTopic::new("topics").unwrap();
+use pubsub_wire::{Pubkey, SnapshotData, SnapshotEnd};
 
-        Self { accounts, slots }
-    }
-}
+use crate::{
+    account_data, account_keys,
+    filter::{AccountFilter, FilterStats, VOTE_PROGRAM},
+    publisher::{Batching, Publisher},
+    spool::{self, FullPolicy, Spool},
+    transport::{NSQTransport, StreamTransport, Topics, Transport, TransportConfig},
+    Message, Sequencer, Slot,
+};
 
 impl TryFrom<&str> for NSQPluginConfig {
     type Error = AccountsDbPluginError;
@@ -40,21 +43,87 @@ impl Debug for NSQPubSubPlugin {
 /// Configuration that the plugin was loaded with
 #[derive(Deserialize, Default, Debug)]
 struct NSQPluginConfig {
+    /// Transport to publish messages over, NSQ if not set
+    #[serde(default)]
+    transport: TransportConfig,
+    #[serde(default = "default_account_topic")]
     account_topic: String,
+    #[serde(default = "default_slot_topic")]
     slot_topic: String,
+    /// Address of NSQ daemon, only used by NSQ transport
+    #[serde(default)]
     host: String,
+    /// Topic to publish accounts, restored from snapshot at startup, to.
+    /// Startup accounts are not published at all, if it's not set. Other
+    /// transports than NSQ ignore the name, and only check if it's set
+    #[serde(default)]
+    snapshot_topic: Option<String>,
+    /// Owners (base58 encoded) of startup accounts to publish,
+    /// accounts of any owner are published if empty
+    #[serde(default)]
+    snapshot_owners: Vec<String>,
+    /// Accounts to publish, regardless of their owners
+    #[serde(default)]
+    include_accounts: Vec<String>,
+    /// Accounts to never publish
+    #[serde(default)]
+    exclude_accounts: Vec<String>,
+    /// Owners, whose accounts should be published
+    #[serde(default)]
+    include_owners: Vec<String>,
+    /// Owners, whose accounts should not be published, unless included explicitly
+    #[serde(default = "default_exclude_owners")]
+    exclude_owners: Vec<String>,
+    /// Interval in seconds, to log number of published and filtered out accounts
+    #[serde(default = "default_filter_stats_interval")]
+    filter_stats_interval: u64,
+    /// Max number of concurrent publishers, once all of them are
+    /// busy, messages are spooled, until they catch up
+    #[serde(default = "default_max_publishers")]
+    max_publishers: usize,
+    /// Max number of messages, which are published together
+    #[serde(default = "default_batch_max_messages")]
+    batch_max_messages: usize,
+    /// Max total size of messages in bytes, which are published together
+    #[serde(default = "default_batch_max_bytes")]
+    batch_max_bytes: usize,
+    /// Time in milliseconds, to wait for more messages to publish together
+    #[serde(default = "default_batch_linger")]
+    batch_linger: u64,
+    /// Whether to compress every batch with zstd, into single message
+    #[serde(default)]
+    batch_compression: bool,
+    /// Directory, where messages are spooled, while transport is unavailable
+    #[serde(default = "default_spool_dir")]
+    spool_dir: PathBuf,
+    /// Max size of spool on disk in bytes
+    #[serde(default = "default_spool_max_bytes")]
+    spool_max_bytes: u64,
+    /// What to do with new messages, once spool is full
+    #[serde(default)]
+    spool_full_policy: FullPolicy,
 }
 
-struct Topics {
-    accounts: Arc<NSQTopic>,
-    slots: Arc<NSQTopic>,
+/// Publishing of accounts, which are restored from snapshot at startup
+struct Snapshot {
+    /// Owners of accounts to publish, any accounts are published if empty
+    owners: HashSet<Pubkey>,
+    /// Number of accounts, published so far
+    accounts: u64,
+    /// The most recent slot among published accounts
+    slot: Slot,
 }
 
 pub struct NSQPubSubPlugin {
     runtime: Runtime,
     publishers: Vec<Sender<Message>>,
-    host: String,
-    topics: Topics,
+    transport: Option<Arc<dyn Transport>>,
+    snapshot: Option<Snapshot>,
+    filter: AccountFilter,
+    max_publishers: usize,
+    batching: Batching,
+    sequencer: Sequencer,
+    spool: Option<Arc<Spool>>,
 }
 
 impl NSQPubSubPlugin {
@@ -69,47 +138,107 @@ impl NSQPubSubPlugin {
             Self {
                 runtime,
                 publishers: Vec::new(),
-                host: String::new(),
-                topics: Topics::default(), // placholder
+                transport: None,
+                snapshot: None,
+                filter: AccountFilter::default(),
+                max_publishers: default_max_publishers(),
+                batching: Batching {
+                    max_messages: default_batch_max_messages(),
+                    max_bytes: default_batch_max_bytes(),
+                    linger: Duration::from_millis(default_batch_linger()),
+                    compress: false,
+                },
+                sequencer: Sequencer::new(),
+                spool: None,
             }
         } else {
             std::process::exit(1);
         }
     }
 
-    fn get_publisher(&mut self) -> &Sender<Message> {
-        let mut index = 0;
-        for p in &self.publishers {
-            if p.is_closed() {
-                // the only reason why it might have shut down,
-                // is that connection to nsq server has been terminated
-                std::process::exit(1);
-            }
-            if p.capacity() > 0 {
-                break;
-            }
-            index += 1;
+    fn get_publisher(
+        &mut self,
+        transport: &Arc<dyn Transport>,
+        spool: &Arc<Spool>,
+    ) -> Option<&Sender<Message>> {
+        // the only reason why publisher might have shut down, is that its
+        // connection has been terminated, its messages have been spooled already
+        self.publishers.retain(|p| !p.is_closed());
+        if let Some(index) = self.publishers.iter().position(|p| p.capacity() > 0) {
+            return self.publishers.get(index);
         }
-        if index < self.publishers.len() {
-            return &self.publishers[index];
+        if self.publishers.len() >= self.max_publishers {
+            return None;
         }
         // if no free workers are available we have to
         // create another one to handle the load
-        let mut producer = NSQProducerConfig::new(&self.host).build();
-        if let Some(NSQEvent::Unhealthy()) = self.runtime.block_on(producer.consume()) {
-            std::process::exit(1);
-        }
-        let publisher = Publisher::new(producer);
+        let connection = match self.runtime.block_on(transport.connect()) {
+            Ok(connection) => connection,
+            Err(e) => {
+                log::warn!("{}: couldn't connect to publish: {}", self.name(), e);
+                return None;
+            }
+        };
+        let publisher = Publisher::new(connection, Arc::clone(spool), self.batching);
         self.publishers.push(publisher.0);
         self.runtime.spawn(publisher.1.run());
-        self.publishers.last().unwrap()
+        self.publishers.last()
     }
 
-    fn publish(&mut self, msg: Message) {
-        let publisher = self.get_publisher();
-        if let Err(_) = publisher.try_send(msg) {
-            std::process::exit(1);
+    /// Set up transport, chosen in configuration, stream
+    /// servers start accepting consumers right away
+    fn open_transport(&self, config: &NSQPluginConfig) -> AccDBResult<Arc<dyn Transport>> {
+        let transport: Arc<dyn Transport> = match config.transport {
+            TransportConfig::Nsq => {
+                let topic = |name: &str| {
+                    NSQTopic::new(name)
+                        .ok_or_else(|| config_error(&format!("invalid topic: {}", name)))
+                };
+                let topics = Topics {
+                    accounts: topic(&config.account_topic)?,
+                    slots: topic(&config.slot_topic)?,
+                    snapshot: config.snapshot_topic.as_deref().map(topic).transpose()?,
+                };
+                Arc::new(NSQTransport::new(config.host.clone(), topics))
+            }
+            TransportConfig::Tcp { ref address } => {
+                let transport = self.runtime.block_on(StreamTransport::tcp(address));
+                Arc::new(transport.map_err(|e| AccountsDbPluginError::Custom(Box::new(e)))?)
+            }
+            TransportConfig::Unix { ref path } => {
+                let transport = self.runtime.block_on(StreamTransport::unix(path));
+                Arc::new(transport.map_err(|e| AccountsDbPluginError::Custom(Box::new(e)))?)
+            }
+        };
+        Ok(transport)
+    }
+
+    fn publish(&mut self, mut msg: Message) {
+        let (transport, spool) = match (&self.transport, &self.spool) {
+            (Some(transport), Some(spool)) => (Arc::clone(transport), Arc::clone(spool)),
+            _ => {
+                return log::error!(
+                    "{}: message published before plugin was loaded",
+                    self.name()
+                )
+            }
+        };
+        // messages are stamped in the order, they're produced by validator,
+        // regardless of the order, in which they're published afterwards
+        msg.stamp = Some(self.sequencer.next(msg.kind()));
+        // while there are spooled messages, new ones are queued after
+        // them, so that they're all published in the original order
+        if !spool.is_empty() {
+            return spool.spill(&msg);
         }
+        let msg = match self.get_publisher(&transport, &spool) {
+            Some(publisher) => match publisher.try_send(msg) {
+                Ok(()) => return,
+                Err(TrySendError::Full(msg)) | Err(TrySendError::Closed(msg)) => msg,
+            },
+            None => msg,
+        };
+        spool.spill(&msg);
     }
 }
 
@@ -121,9 +250,42 @@ impl AccountsDbPlugin for NSQPubSubPlugin {
     fn on_load(&mut self, config_file: &str) -> AccDBResult<()> {
         log::warn!("{}, LOADED", self.name());
         let config = NSQPluginConfig::try_from(config_file)?;
-        let accounts = NSQTopic::new(&config.account_topic).unwrap();
-        let slots = NSQTopic::new(&config.slot_topic).unwrap();
-        self.topics = Topics { accounts, slots };
+        if config.snapshot_topic.is_some() {
+            let owners = decode_pubkeys(&config.snapshot_owners)?;
+            self.snapshot = Some(Snapshot::new(owners));
+        }
+        self.filter = AccountFilter::new(
+            decode_pubkeys(&config.include_accounts)?,
+            decode_pubkeys(&config.exclude_accounts)?,
+            decode_pubkeys(&config.include_owners)?,
+            decode_pubkeys(&config.exclude_owners)?,
+        );
+        let period = Duration::from_secs(config.filter_stats_interval.max(1));
+        self.runtime
+            .spawn(report_filter_stats(self.filter.stats(), period));
+
+        self.max_publishers = config.max_publishers.max(1);
+        self.batching = Batching {
+            max_messages: config.batch_max_messages.max(1),
+            max_bytes: config.batch_max_bytes,
+            linger: Duration::from_millis(config.batch_linger),
+            compress: config.batch_compression,
+        };
+        let spool = Spool::open(
+            &config.spool_dir,
+            config.spool_max_bytes,
+            config.spool_full_policy,
+        )
+        .map_err(|e| AccountsDbPluginError::Custom(Box::new(e)))?;
+        let spool = Arc::new(spool);
+        let transport = self.open_transport(&config)?;
+        self.runtime.spawn(spool::drain(
+            Arc::clone(&spool),
+            Arc::clone(&transport),
+            self.batching,
+        ));
+        self.spool = Some(spool);
+        self.transport = Some(transport);
 
         Ok(())
     }
@@ -135,16 +297,44 @@ impl AccountsDbPlugin for NSQPubSubPlugin {
         is_startup: bool,
     ) -> AccDBResult<()> {
         log::warn!("ACCOUNT UPDATE FOR {}", self.name());
-        // for now we are not interested in accounts, which were restored from last snapshot
+        let (pubkey, owner) = account_keys(&account);
+        if !self.filter.matches(pubkey, owner) {
+            return Ok(());
+        }
+        // accounts, which were restored from last snapshot, are only published if requested
         if is_startup {
+            let snapshot = match self.snapshot {
+                Some(ref mut snapshot) if snapshot.includes(owner) => snapshot,
+                _ => return Ok(()),
+            };
+            let account = account_data(account, slot);
+            snapshot.accounts += 1;
+            snapshot.slot = snapshot.slot.max(slot);
+            let message = Message::from_snapshot(SnapshotData::Account(account));
+            self.publish(message);
             return Ok(());
         }
-        let message = Message::from_account(account, slot, Arc::clone(&self.topics.accounts));
+        let message = Message::from_account(account, slot);
         self.publish(message);
         Ok(())
     }
 
     fn notify_end_of_startup(&mut self) -> AccDBResult<()> {
+        // snapshot is published only once, as the validator starts up
+        if let Some(snapshot) = self.snapshot.take() {
+            log::info!(
+                "{}: published {} startup accounts, up to slot {}",
+                self.name(),
+                snapshot.accounts,
+                snapshot.slot
+            );
+            let end = SnapshotEnd {
+                accounts: snapshot.accounts,
+                slot: snapshot.slot,
+            };
+            let message = Message::from_snapshot(SnapshotData::End(end));
+            self.publish(message);
+        }
         Ok(())
     }
 
@@ -155,17 +345,106 @@ impl AccountsDbPlugin for NSQPubSubPlugin {
         status: AccDBSlotStatus,
     ) -> AccDBResult<()> {
         log::warn!("SLOT UPDATE FOR {}", self.name());
-        let message = Message::from_slot(
-            slot,
-            parent.unwrap_or_default(),
-            status,
-            Arc::clone(&self.topics.slots),
-        );
+        let message = Message::from_slot(slot, parent.unwrap_or_default(), status);
         self.publish(message);
         Ok(())
     }
 }
 
+impl Snapshot {
+    fn new(owners: HashSet<Pubkey>) -> Self {
+        Self {
+            owners,
+            accounts: 0,
+            slot: 0,
+        }
+    }
+
+    /// Whether accounts of given owner should be published
+    fn includes(&self, owner: &[u8]) -> bool {
+        match <&Pubkey>::try_from(owner) {
+            Ok(owner) => self.owners.is_empty() || self.owners.contains(owner),
+            Err(_) => false,
+        }
+    }
+}
+
+fn decode_pubkeys(pubkeys: &[String]) -> AccDBResult<HashSet<Pubkey>> {
+    pubkeys.iter().map(|pubkey| decode_pubkey(pubkey)).collect()
+}
+
+fn decode_pubkey(pubkey: &str) -> AccDBResult<Pubkey> {
+    let mut buf = Pubkey::default();
+    match bs58::decode(pubkey).into(&mut buf) {
+        Ok(len) if len == buf.len() => Ok(buf),
+        _ => Err(config_error(&format!("invalid public key: {}", pubkey))),
+    }
+}
+
+fn default_account_topic() -> String {
+    "accounts".into()
+}
+
+fn default_slot_topic() -> String {
+    "slots".into()
+}
+
+fn default_exclude_owners() -> Vec<String> {
+    vec![VOTE_PROGRAM.to_string()]
+}
+
+fn default_filter_stats_interval() -> u64 {
+    60
+}
+
+fn default_max_publishers() -> usize {
+    4
+}
+
+fn default_batch_max_messages() -> usize {
+    256
+}
+
+fn default_batch_max_bytes() -> usize {
+    // NSQ daemon rejects messages larger than 1 MiB by default
+    512 * 1024
+}
+
+fn default_batch_linger() -> u64 {
+    5
+}
+
+fn default_spool_dir() -> PathBuf {
+    std::env::temp_dir().join("nsq-pubsub-spool")
+}
+
+fn default_spool_max_bytes() -> u64 {
+    1 << 30
+}
+
+/// Periodically log number of accounts, which have passed filter, and which haven't
+async fn report_filter_stats(stats: Arc<FilterStats>, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    // the first tick completes immediately
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let (hits, misses) = stats.take();
+        log::info!(
+            "account filter: {} published, {} filtered out, in the last {} seconds",
+            hits,
+            misses,
+            period.as_secs()
+        );
+    }
+}
+
+fn config_error(msg: &str) -> AccountsDbPluginError {
+    AccountsDbPluginError::ConfigFileReadError {
+        msg: msg.to_string(),
+    }
+}
+
 /// Every AccountsDbPlugin is required to expose this method, in order for the validator
 /// to be able to load them during runtime
 #[no_mangle]
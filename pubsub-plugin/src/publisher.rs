@@ -1,56 +1,174 @@
+use std::sync::Arc;
 use std::time::Duration;
 
+use pubsub_wire::Kind;
 use tokio::sync::mpsc::{Receiver, Sender};
-use tokio_nsq::{NSQEvent, NSQProducer};
+use tokio::time::Instant;
 
-use crate::Message;
+use crate::{spool::Spool, transport::Connection, Message};
 
 pub(super) struct Publisher {
-    producer: NSQProducer,
+    connection: Box<dyn Connection>,
     receiver: Receiver<Message>,
+    spool: Arc<Spool>,
+    batching: Batching,
+    /// Batches, which messages are being collected into, one per kind
+    batches: Vec<Batch>,
 }
 
 pub(super) enum Error {
-    SerializeError,
     PublishTimeout,
 }
 
+/// Limits of batches, which messages are collected into before publishing
+#[derive(Clone, Copy)]
+pub(super) struct Batching {
+    /// Max number of messages in batch
+    pub(super) max_messages: usize,
+    /// Max total size of encoded messages in batch
+    pub(super) max_bytes: usize,
+    /// How long to wait for more messages, after the first one has arrived
+    pub(super) linger: Duration,
+    /// Whether to pack batch into single zstd compressed message
+    pub(super) compress: bool,
+}
+
+/// Encoded messages of the same kind, which are published together
+struct Batch {
+    kind: Kind,
+    messages: Vec<Vec<u8>>,
+    bytes: usize,
+}
+
 impl Publisher {
-    pub(super) fn new(producer: NSQProducer) -> (Sender<Message>, Self) {
+    pub(super) fn new(
+        connection: Box<dyn Connection>,
+        spool: Arc<Spool>,
+        batching: Batching,
+    ) -> (Sender<Message>, Self) {
         let (sender, receiver) = tokio::sync::mpsc::channel(4096);
-        let publisher = Self { producer, receiver };
+        let publisher = Self {
+            connection,
+            receiver,
+            spool,
+            batching,
+            batches: Vec::new(),
+        };
         (sender, publisher)
     }
 
     pub(super) async fn run(mut self) -> Result<(), Error> {
         while let Some(msg) = self.receiver.recv().await {
-            self.send(msg).await?;
+            self.collect(msg);
+            let deadline = Instant::now() + self.batching.linger;
+            while !self.is_full() {
+                match tokio::time::timeout_at(deadline, self.receiver.recv()).await {
+                    Ok(Some(msg)) => self.collect(msg),
+                    // either linger time is over, or plugin is shutting down
+                    Ok(None) | Err(_) => break,
+                }
+            }
+            if let Err(e) = self.flush().await {
+                // transport is unavailable, so publisher shuts down, and
+                // the queued messages are spooled after the collected ones
+                self.receiver.close();
+                while let Some(msg) = self.receiver.recv().await {
+                    tokio::task::block_in_place(|| self.spool.spill(&msg));
+                }
+                return Err(e);
+            }
         }
         Ok(())
     }
 
-    async fn send(&mut self, msg: Message) -> Result<(), Error> {
-        let mut value = match msg.serialize() {
-            Some(v) => v,
-            None => return Err(Error::SerializeError),
+    /// Add message to the batch of its kind
+    fn collect(&mut self, msg: Message) {
+        let message = match msg.serialize() {
+            Some(message) => message,
+            None => return log::error!("couldn't serialize message"),
         };
-        let mut retries = 0;
-        loop {
-            while let Err(e) = self.producer.publish(&msg.topic, value) {
-                eprintln!("couldn't publish to nsq: {}", e);
-                retries += 1;
-                if retries >= 10 {
-                    eprintln!("retries exceeded to publish nsq, shutting down worker");
-                    return Err(Error::PublishTimeout);
+        let kind = msg.kind();
+        let batch = match self.batches.iter_mut().position(|b| b.kind == kind) {
+            Some(index) => &mut self.batches[index],
+            None => {
+                self.batches.push(Batch::new(kind));
+                self.batches.last_mut().unwrap()
+            }
+        };
+        batch.bytes += message.len();
+        batch.messages.push(message);
+    }
+
+    fn is_full(&self) -> bool {
+        self.batches.iter().any(|b| {
+            b.messages.len() >= self.batching.max_messages || b.bytes >= self.batching.max_bytes
+        })
+    }
+
+    /// Publish all the collected batches, batches, which
+    /// couldn't be published, are spooled instead
+    async fn flush(&mut self) -> Result<(), Error> {
+        let mut batches = std::mem::take(&mut self.batches).into_iter();
+        while let Some(batch) = batches.next() {
+            let messages = self.batching.pack(batch.messages);
+            if let Err(e) = self.send(batch.kind, &messages).await {
+                self.spill(batch.kind, &messages);
+                for batch in batches {
+                    let messages = self.batching.pack(batch.messages);
+                    self.spill(batch.kind, &messages);
                 }
-                tokio::time::sleep(Duration::from_millis(100)).await;
-                value = msg.serialize().unwrap();
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn spill(&self, kind: Kind, messages: &[Vec<u8>]) {
+        // spool might block, until there's room for messages
+        tokio::task::block_in_place(|| {
+            for message in messages {
+                self.spool.spill_encoded(kind, message);
             }
-            if let Some(NSQEvent::Ok()) = self.producer.consume().await {
-                break;
+        });
+    }
+
+    async fn send(&mut self, kind: Kind, messages: &[Vec<u8>]) -> Result<(), Error> {
+        let mut retries = 0;
+        while let Err(e) = self.connection.publish(kind, messages).await {
+            log::warn!("couldn't publish messages: {}", e);
+            retries += 1;
+            if retries >= 10 {
+                log::error!("retries exceeded to publish messages, shutting down worker");
+                return Err(Error::PublishTimeout);
             }
-            value = msg.serialize().unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
         }
         Ok(())
     }
 }
+
+impl Batching {
+    /// Compress batch into single message, if requested
+    pub(super) fn pack(&self, messages: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        if !self.compress {
+            return messages;
+        }
+        match pubsub_wire::pack(&messages, true) {
+            Ok(packed) => vec![packed],
+            Err(e) => {
+                log::warn!("couldn't compress batch, publishing it as is: {}", e);
+                messages
+            }
+        }
+    }
+}
+
+impl Batch {
+    fn new(kind: Kind) -> Self {
+        Self {
+            kind,
+            messages: Vec::new(),
+            bytes: 0,
+        }
+    }
+}
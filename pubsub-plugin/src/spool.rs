@@ -0,0 +1,471 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use pubsub_wire::Kind;
+use serde::Deserialize;
+use tokio::sync::Notify;
+
+use crate::publisher::Batching;
+use crate::transport::{Connection, Transport};
+use crate::Message;
+
+/// Extension of spool segment files
+const SEGMENT_EXT: &str = "spool";
+/// Number of segments, which spool is split into, when it's full
+const SEGMENTS: u64 = 16;
+/// Length of record header: length of the rest of record
+const HEADER_LEN: u64 = 4;
+/// Delay before retrying to publish spooled message, after failure
+#[cfg(not(test))]
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+#[cfg(test)]
+const RETRY_DELAY: Duration = Duration::from_millis(10);
+/// Number of times broker may fail to accept the same batch over an established connection,
+/// before batch is considered rejected and discarded
+const MAX_PUBLISH_ATTEMPTS: u32 = 5;
+
+/// What to do with new messages, once spool has reached its size limit
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum FullPolicy {
+    /// Remove the oldest segment of spool, to make room for new messages
+    #[default]
+    DropOldest,
+    /// Discard new messages, until there's room for them
+    DropNewest,
+    /// Wait until spool is drained, blocking the validator
+    Block,
+}
+
+/// Bounded append-only queue of messages on disk, which keeps messages,
+/// that couldn't be handed over to publishers, until NSQ is available
+/// again. Spool is split into segment files, which are removed once
+/// drained (or dropped as a whole, if spool is full). Messages are
+/// delivered at least once: if the validator restarts, segments, left
+/// from the previous run, are drained again from their very beginning
+pub(crate) struct Spool {
+    inner: Mutex<Inner>,
+    /// Signals blocked writers, that some room has been freed
+    space: Condvar,
+    /// Signals drain task, that new messages have been spooled
+    ready: Notify,
+}
+
+struct Inner {
+    dir: PathBuf,
+    max_bytes: u64,
+    segment_bytes: u64,
+    policy: FullPolicy,
+    /// Segments on disk, the oldest first
+    segments: VecDeque<Segment>,
+    /// Total size of segments on disk
+    size: u64,
+    /// Segment, which new messages are appended to
+    writer: Option<(u64, File)>,
+    /// Segment, which messages are being drained from, and offset of the next one
+    reader: Option<(u64, File, u64)>,
+    /// Number of messages or bytes, which have been dropped, since the last report
+    dropped: u64,
+}
+
+struct Segment {
+    seq: u64,
+    size: u64,
+}
+
+/// Spooled message, along with its position in spool
+pub(crate) struct Record {
+    seq: u64,
+    end: u64,
+    /// Kind of message, which determines where it's published to
+    pub(crate) tag: u8,
+    /// Message, serialized the same way it's published
+    pub(crate) payload: Vec<u8>,
+}
+
+impl Spool {
+    /// Open spool in given directory, picking up segments, which were left over
+    pub(crate) fn open<P: Into<PathBuf>>(
+        dir: P,
+        max_bytes: u64,
+        policy: FullPolicy,
+    ) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(SEGMENT_EXT) {
+                continue;
+            }
+            let seq = path.file_stem().and_then(|s| s.to_str()?.parse().ok());
+            if let Some(seq) = seq {
+                let size = fs::metadata(&path)?.len();
+                segments.push(Segment { seq, size });
+            }
+        }
+        segments.sort_by_key(|s| s.seq);
+        let size = segments.iter().map(|s| s.size).sum();
+        let inner = Inner {
+            dir,
+            max_bytes,
+            segment_bytes: (max_bytes / SEGMENTS).max(1),
+            policy,
+            segments: segments.into(),
+            size,
+            writer: None,
+            reader: None,
+            dropped: 0,
+        };
+        Ok(Self {
+            inner: Mutex::new(inner),
+            space: Condvar::new(),
+            ready: Notify::new(),
+        })
+    }
+
+    /// Whether there are any messages in spool, waiting to be published
+    pub(crate) fn is_empty(&self) -> bool {
+        self.lock().segments.is_empty()
+    }
+
+    /// Keep message in spool, errors are only logged, as there's
+    /// nothing else, which can be done with message at this point
+    pub(crate) fn spill(&self, msg: &Message) {
+        match msg.serialize() {
+            Some(payload) => self.spill_encoded(msg.kind(), &payload),
+            None => log::error!("couldn't serialize message to spool"),
+        }
+    }
+
+    /// Keep already encoded message (or batch of messages) in spool
+    pub(crate) fn spill_encoded(&self, kind: Kind, payload: &[u8]) {
+        if let Err(e) = self.push(kind as u8, payload) {
+            log::error!("couldn't write message to spool: {}", e);
+        }
+    }
+
+    /// Append message to spool, returns false if message has been dropped
+    pub(crate) fn push(&self, tag: u8, payload: &[u8]) -> io::Result<bool> {
+        let len = HEADER_LEN + 1 + payload.len() as u64;
+        let mut inner = self.lock();
+        while inner.size + len > inner.max_bytes {
+            match inner.policy {
+                FullPolicy::DropOldest if !inner.segments.is_empty() => inner.drop_oldest()?,
+                FullPolicy::Block if !inner.segments.is_empty() => {
+                    inner = self.space.wait(inner).unwrap_or_else(|e| e.into_inner());
+                }
+                // either policy says so, or message won't fit into spool anyway
+                _ => {
+                    inner.dropped += 1;
+                    return Ok(false);
+                }
+            }
+        }
+        inner.append(tag, payload, len)?;
+        drop(inner);
+        self.ready.notify_one();
+        Ok(true)
+    }
+
+    /// Read the oldest message in spool, without removing it
+    #[cfg(test)]
+    pub(crate) fn peek(&self) -> io::Result<Option<Record>> {
+        self.lock().read()
+    }
+
+    /// Read the oldest messages of the same kind in spool, within given
+    /// limits, without removing them. Committing the last one of them
+    /// removes all of them at once
+    pub(crate) fn peek_batch(
+        &self,
+        max_messages: usize,
+        max_bytes: usize,
+    ) -> io::Result<Vec<Record>> {
+        self.lock().read_batch(max_messages, max_bytes)
+    }
+
+    /// Remove message from spool, once it has been published
+    pub(crate) fn commit(&self, record: &Record) -> io::Result<()> {
+        if self.lock().commit(record)? {
+            self.space.notify_all();
+        }
+        Ok(())
+    }
+
+    /// Take number of messages (or bytes, for dropped segments),
+    /// which have been dropped since the last call
+    pub(crate) fn take_dropped(&self) -> u64 {
+        std::mem::take(&mut self.lock().dropped)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Inner {
+    fn path(&self, seq: u64) -> PathBuf {
+        self.dir.join(format!("{:020}.{}", seq, SEGMENT_EXT))
+    }
+
+    fn append(&mut self, tag: u8, payload: &[u8], len: u64) -> io::Result<()> {
+        let full = match self.segments.back() {
+            Some(segment) => segment.size > 0 && segment.size + len > self.segment_bytes,
+            None => true,
+        };
+        // never append to segments, which were left from the previous run
+        let own =
+            matches!((&self.writer, self.segments.back()), (Some((w, _)), Some(s)) if *w == s.seq);
+        if full || !own {
+            let seq = self.segments.back().map(|s| s.seq + 1).unwrap_or_default();
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.path(seq))?;
+            self.writer = Some((seq, file));
+            self.segments.push_back(Segment { seq, size: 0 });
+        }
+        let mut record = Vec::with_capacity(len as usize);
+        record.extend_from_slice(&((len - HEADER_LEN) as u32).to_le_bytes());
+        record.push(tag);
+        record.extend_from_slice(payload);
+
+        let (_, file) = self.writer.as_mut().expect("spool writer is open");
+        let result = file.write_all(&record);
+        let segment = self.segments.back_mut().expect("spool segment is open");
+        if let Err(e) = result {
+            // record might have been written partially, so segment is closed
+            // for writing, and the rest of it is discarded by reader
+            segment.size = file.metadata().map(|m| m.len()).unwrap_or(segment.size);
+            self.size = self.segments.iter().map(|s| s.size).sum();
+            self.writer = None;
+            return Err(e);
+        }
+        segment.size += len;
+        self.size += len;
+        Ok(())
+    }
+
+    fn read(&mut self) -> io::Result<Option<Record>> {
+        loop {
+            let seq = match self.segments.front() {
+                Some(segment) => segment.seq,
+                None => return Ok(None),
+            };
+            if !matches!(self.reader, Some((s, _, _)) if s == seq) {
+                self.reader = Some((seq, File::open(self.path(seq))?, 0));
+            }
+            let writing = matches!(self.writer, Some((s, _)) if s == seq);
+            let (_, file, offset) = self.reader.as_mut().expect("spool reader is open");
+            match read_record(file, *offset, self.max_bytes) {
+                Ok((tag, payload)) => {
+                    let end = *offset + HEADER_LEN + 1 + payload.len() as u64;
+                    return Ok(Some(Record {
+                        seq,
+                        end,
+                        tag,
+                        payload,
+                    }));
+                }
+                // segment, which is being written to, has no more messages yet
+                Err(e) if writing && e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                // the rest of segment is either missing or corrupted
+                Err(e)
+                    if e.kind() == io::ErrorKind::UnexpectedEof
+                        || e.kind() == io::ErrorKind::InvalidData =>
+                {
+                    log::warn!("discarding the rest of spool segment {}: {}", seq, e);
+                    self.remove_front()?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Read consecutive records of the same kind, starting from the oldest one.
+    /// Batch never spans segments, and records, which are packed batches
+    /// themselves (spooled by publishers), are always read on their own
+    fn read_batch(&mut self, max_messages: usize, max_bytes: usize) -> io::Result<Vec<Record>> {
+        let first = match self.read()? {
+            Some(record) => record,
+            None => return Ok(Vec::new()),
+        };
+        let mut bytes = first.payload.len();
+        let mut records = vec![first];
+        let (_, file, _) = self.reader.as_mut().expect("spool reader is open");
+        while records.len() < max_messages && bytes < max_bytes {
+            let last = records.last().expect("batch has at least one record");
+            if last.packed() {
+                break;
+            }
+            let (seq, offset, kind) = (last.seq, last.end, last.tag);
+            // whatever is wrong with the rest of segment, it's up to the next read
+            let (tag, payload) = match read_record(file, offset, self.max_bytes) {
+                Ok(record) => record,
+                Err(_) => break,
+            };
+            let end = offset + HEADER_LEN + 1 + payload.len() as u64;
+            let record = Record {
+                seq,
+                end,
+                tag,
+                payload,
+            };
+            if tag != kind || record.packed() {
+                break;
+            }
+            bytes += record.payload.len();
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    /// Advance reader past the record, returns whether any room has been freed
+    fn commit(&mut self, record: &Record) -> io::Result<bool> {
+        match self.reader {
+            Some((seq, _, ref mut offset)) if seq == record.seq => *offset = record.end,
+            // segment has been dropped, since record was read
+            _ => return Ok(false),
+        }
+        let drained =
+            matches!(self.segments.front(), Some(s) if s.seq == record.seq && record.end >= s.size);
+        if drained {
+            self.remove_front()?;
+        }
+        Ok(drained)
+    }
+
+    fn drop_oldest(&mut self) -> io::Result<()> {
+        let size = self.segments.front().map(|s| s.size).unwrap_or_default();
+        self.dropped += size;
+        log::warn!(
+            "spool is full, dropping {} bytes of the oldest messages",
+            size
+        );
+        self.remove_front()
+    }
+
+    fn remove_front(&mut self) -> io::Result<()> {
+        let segment = match self.segments.pop_front() {
+            Some(segment) => segment,
+            None => return Ok(()),
+        };
+        self.size -= segment.size;
+        if matches!(self.reader, Some((s, _, _)) if s == segment.seq) {
+            self.reader = None;
+        }
+        if matches!(self.writer, Some((s, _)) if s == segment.seq) {
+            self.writer = None;
+        }
+        fs::remove_file(self.path(segment.seq))
+    }
+}
+
+impl Record {
+    /// Whether record is a batch of messages, which has been packed already
+    fn packed(&self) -> bool {
+        matches!(
+            self.payload.first(),
+            Some(&pubsub_wire::BATCH) | Some(&pubsub_wire::ZSTD_BATCH)
+        )
+    }
+}
+
+/// Read single record at given offset of segment file
+fn read_record(file: &mut File, offset: u64, max_len: u64) -> io::Result<(u8, Vec<u8>)> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut header = [0; HEADER_LEN as usize];
+    file.read_exact(&mut header)?;
+    let len = u32::from_le_bytes(header) as u64;
+    if len == 0 || len > max_len {
+        let msg = format!("invalid length of spooled message: {}", len);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+    }
+    let mut record = vec![0; len as usize];
+    file.read_exact(&mut record)?;
+    let payload = record.split_off(1);
+    Ok((record[0], payload))
+}
+
+/// Publish spooled messages in order, as soon as transport is available.
+/// Consecutive messages of the same kind are published in batches, within
+/// the same limits as used by publishers. Messages of unknown kinds are discarded,
+/// as are batches, which broker keeps failing to accept over established connection
+pub(crate) async fn drain(spool: Arc<Spool>, transport: Arc<dyn Transport>, batching: Batching) {
+    let mut connection: Option<Box<dyn Connection>> = None;
+    // failed attempts to publish the batch at the head of spool
+    let mut attempts = 0;
+    loop {
+        let dropped = spool.take_dropped();
+        if dropped > 0 {
+            log::warn!(
+                "spool is full, {} messages (or bytes) have been dropped",
+                dropped
+            );
+        }
+        let mut records = match spool.peek_batch(batching.max_messages, batching.max_bytes) {
+            Ok(records) if records.is_empty() => {
+                spool.ready.notified().await;
+                continue;
+            }
+            Ok(records) => records,
+            Err(e) => {
+                log::error!("couldn't read message from spool: {}", e);
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
+            }
+        };
+        let last = records.len() - 1;
+        if let Ok(kind) = Kind::try_from(records[last].tag) {
+            let open = match connection {
+                Some(ref mut open) => open,
+                None => match transport.connect().await {
+                    Ok(c) => connection.insert(c),
+                    Err(e) => {
+                        log::warn!("couldn't connect to publish spooled messages: {}", e);
+                        tokio::time::sleep(RETRY_DELAY).await;
+                        continue;
+                    }
+                },
+            };
+            let packed = records[last].packed();
+            let messages = records.iter_mut().map(|r| std::mem::take(&mut r.payload));
+            let messages = messages.collect();
+            let messages = if packed {
+                messages
+            } else {
+                batching.pack(messages)
+            };
+            if let Err(e) = open.publish(kind, &messages).await {
+                connection = None;
+                attempts += 1;
+                if attempts < MAX_PUBLISH_ATTEMPTS {
+                    log::warn!("couldn't publish spooled messages: {}", e);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                    continue;
+                }
+                log::error!(
+                    "discarding {} spooled messages, failed to publish them {} times: {}",
+                    records.len(),
+                    attempts,
+                    e
+                );
+            }
+        } else {
+            log::warn!(
+                "discarding spooled messages of unknown kind: {}",
+                records[last].tag
+            );
+        }
+        attempts = 0;
+        if let Err(e) = spool.commit(&records[last]) {
+            log::error!("couldn't remove published messages from spool: {}", e);
+        }
+    }
+}
@@ -0,0 +1,423 @@
+#![cfg(test)]
+use std::collections::HashSet;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use pubsub_wire::{
+    Kind, Pubkey, SlotData, SnapshotData, SnapshotEnd, Stamp, Stamped, FRAME_HEADER_LEN,
+};
+use solana_accountsdb_plugin_interface::accountsdb_plugin_interface::SlotStatus;
+use tokio::io::AsyncReadExt;
+use tokio::net::UnixStream;
+
+use crate::filter::AccountFilter;
+use crate::publisher::{Batching, Publisher};
+use crate::spool::{drain as drain_spool, FullPolicy, Spool};
+use crate::transport::{BoxFuture, Connection, StreamTransport, Transport};
+use crate::{Message, Sequencer};
+
+const ACCOUNT: Pubkey = [1; 32];
+const VOTE_ACCOUNT: Pubkey = [2; 32];
+const PROGRAM: Pubkey = [3; 32];
+const VOTE: Pubkey = [4; 32];
+
+fn set(keys: &[Pubkey]) -> HashSet<Pubkey> {
+    keys.iter().copied().collect()
+}
+
+#[test]
+fn excluded_owners() {
+    let filter = AccountFilter::new(set(&[]), set(&[]), set(&[]), set(&[VOTE]));
+    assert!(filter.matches(&ACCOUNT, &PROGRAM));
+    assert!(!filter.matches(&VOTE_ACCOUNT, &VOTE));
+    assert_eq!(filter.stats().take(), (1, 1));
+    assert_eq!(filter.stats().take(), (0, 0));
+}
+
+#[test]
+fn included_owners() {
+    let filter = AccountFilter::new(set(&[]), set(&[]), set(&[PROGRAM]), set(&[VOTE]));
+    assert!(filter.matches(&ACCOUNT, &PROGRAM));
+    assert!(!filter.matches(&ACCOUNT, &[5; 32]));
+    assert!(!filter.matches(&VOTE_ACCOUNT, &VOTE));
+}
+
+#[test]
+fn accounts_take_precedence_over_owners() {
+    let filter = AccountFilter::new(
+        set(&[VOTE_ACCOUNT]),
+        set(&[ACCOUNT]),
+        set(&[PROGRAM]),
+        set(&[VOTE]),
+    );
+    assert!(filter.matches(&VOTE_ACCOUNT, &VOTE));
+    assert!(!filter.matches(&ACCOUNT, &PROGRAM));
+    assert!(filter.matches(&[6; 32], &PROGRAM));
+}
+
+#[test]
+fn malformed_keys() {
+    let filter = AccountFilter::default();
+    assert!(filter.matches(&ACCOUNT, &PROGRAM));
+    assert!(!filter.matches(&ACCOUNT[..31], &PROGRAM));
+}
+
+/// Empty directory for spool, unique to the test
+fn spool_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("spool-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Publish all the messages in spool, returning their payloads
+fn drain(spool: &Spool) -> Vec<Vec<u8>> {
+    let mut payloads = Vec::new();
+    while let Some(record) = spool.peek().unwrap() {
+        spool.commit(&record).unwrap();
+        payloads.push(record.payload);
+    }
+    payloads
+}
+
+#[test]
+fn spool_keeps_order() {
+    let dir = spool_dir("order");
+    let spool = Spool::open(&dir, 1024, FullPolicy::DropOldest).unwrap();
+    assert!(spool.is_empty());
+    for i in 0..20u8 {
+        assert!(spool.push(i % 3, &[i; 10]).unwrap());
+    }
+    let record = spool.peek().unwrap().unwrap();
+    assert_eq!((record.tag, record.payload), (0, vec![0; 10]));
+    let payloads = drain(&spool);
+    assert_eq!(payloads, (0..20u8).map(|i| vec![i; 10]).collect::<Vec<_>>());
+    assert!(spool.is_empty());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn spool_drops_oldest() {
+    let dir = spool_dir("oldest");
+    // every segment holds 4 records of 15 bytes
+    let spool = Spool::open(&dir, 16 * 64, FullPolicy::DropOldest).unwrap();
+    for i in 0..100u8 {
+        assert!(spool.push(0, &[i; 10]).unwrap());
+    }
+    assert!(spool.take_dropped() > 0);
+    let payloads = drain(&spool);
+    assert_eq!(payloads.last(), Some(&vec![99; 10]));
+    assert!(payloads.len() < 100);
+    assert!(payloads.windows(2).all(|w| w[0][0] + 1 == w[1][0]));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn spool_drops_newest() {
+    let dir = spool_dir("newest");
+    let spool = Spool::open(&dir, 16 * 64, FullPolicy::DropNewest).unwrap();
+    let pushed = (0..100u8)
+        .filter(|&i| spool.push(0, &[i; 10]).unwrap())
+        .count();
+    assert_eq!(spool.take_dropped() as usize, 100 - pushed);
+    let payloads = drain(&spool);
+    assert_eq!(payloads.len(), pushed);
+    assert_eq!(payloads.first(), Some(&vec![0; 10]));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn spool_survives_restart() {
+    let dir = spool_dir("restart");
+    let spool = Spool::open(&dir, 1024, FullPolicy::Block).unwrap();
+    for i in 0..5u8 {
+        spool.push(1, &[i; 10]).unwrap();
+    }
+    let record = spool.peek().unwrap().unwrap();
+    spool.commit(&record).unwrap();
+    drop(spool);
+
+    // messages are delivered at least once, so the whole
+    // segment, left from previous run, is drained again
+    let spool = Spool::open(&dir, 1024, FullPolicy::Block).unwrap();
+    assert!(!spool.is_empty());
+    spool.push(1, &[5; 10]).unwrap();
+    let payloads = drain(&spool);
+    assert_eq!(payloads, (0..6u8).map(|i| vec![i; 10]).collect::<Vec<_>>());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn stream_transport() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let dir = spool_dir("stream");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("pubsub.sock");
+    runtime.block_on(async {
+        let transport = StreamTransport::unix(&path).await.unwrap();
+        let mut connection = transport.connect().await.unwrap();
+        // nobody would receive the message yet
+        let messages = [vec![1, 2, 3], vec![4]];
+        assert!(connection.publish(Kind::Slot, &messages).await.is_err());
+
+        let mut consumer = UnixStream::connect(&path).await.unwrap();
+        // consumer is accepted in background
+        while connection.publish(Kind::Slot, &messages).await.is_err() {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        let mut frames = [0; FRAME_HEADER_LEN * 2 + 6];
+        consumer.read_exact(&mut frames).await.unwrap();
+        let slot = Kind::Slot as u8;
+        assert_eq!(frames, [0, 0, 0, 4, slot, 1, 2, 3, 0, 0, 0, 2, slot, 4]);
+    });
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// Batches of messages, along with their kinds
+type Batches = Vec<(Kind, Vec<Vec<u8>>)>;
+
+/// Connection, which records published batches, or fails to publish them
+struct Recorder {
+    batches: Arc<Mutex<Batches>>,
+    fail: bool,
+}
+
+impl Connection for Recorder {
+    fn publish<'a>(
+        &'a mut self,
+        kind: Kind,
+        messages: &'a [Vec<u8>],
+    ) -> BoxFuture<'a, io::Result<()>> {
+        let result = if self.fail {
+            Err(io::Error::new(io::ErrorKind::NotConnected, "unavailable"))
+        } else {
+            self.batches.lock().unwrap().push((kind, messages.to_vec()));
+            Ok(())
+        };
+        Box::pin(async move { result })
+    }
+}
+
+/// Publish given messages with recording connection, returning published batches
+fn publish(messages: Vec<Message>, compress: bool, fail: bool, spool: Arc<Spool>) -> Batches {
+    let batches = Arc::default();
+    let connection = Recorder {
+        batches: Arc::clone(&batches),
+        fail,
+    };
+    let batching = Batching {
+        max_messages: 3,
+        max_bytes: 1024,
+        linger: Duration::from_millis(10),
+        compress,
+    };
+    let (sender, publisher) = Publisher::new(Box::new(connection), spool, batching);
+    for message in messages {
+        assert!(sender.try_send(message).is_ok());
+    }
+    drop(sender);
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let _ = runtime.block_on(publisher.run());
+    let batches = batches.lock().unwrap().clone();
+    batches
+}
+
+fn messages() -> Vec<Message> {
+    let mut messages: Vec<_> = (1..=5)
+        .map(|slot| Message::from_slot(slot, slot - 1, SlotStatus::Processed))
+        .collect();
+    let end = SnapshotEnd {
+        accounts: 0,
+        slot: 5,
+    };
+    messages.insert(1, Message::from_snapshot(SnapshotData::End(end)));
+    messages
+}
+
+#[test]
+fn publisher_batches_by_kind() {
+    let dir = spool_dir("batches");
+    let spool = Arc::new(Spool::open(&dir, 1024, FullPolicy::DropNewest).unwrap());
+    let batches = publish(messages(), false, false, Arc::clone(&spool));
+    let sizes: Vec<_> = batches.iter().map(|(k, m)| (*k, m.len())).collect();
+    assert_eq!(
+        sizes,
+        [(Kind::Slot, 3), (Kind::Snapshot, 1), (Kind::Slot, 2)]
+    );
+    let slots = batches[0].1.iter().chain(&batches[2].1);
+    let slots: Vec<SlotData> = slots.map(|m| pubsub_wire::decode(m).unwrap()).collect();
+    assert_eq!(
+        slots.iter().map(|s| s.slot).collect::<Vec<_>>(),
+        [1, 2, 3, 4, 5]
+    );
+    assert!(spool.is_empty());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn publisher_compresses_batches() {
+    let dir = spool_dir("compressed");
+    let spool = Arc::new(Spool::open(&dir, 1024, FullPolicy::DropNewest).unwrap());
+    let batches = publish(messages(), true, false, spool);
+    assert!(batches.iter().all(|(_, m)| m.len() == 1));
+    let slots: Vec<SlotData> = pubsub_wire::unpack(&batches[0].1[0]).unwrap();
+    assert_eq!(slots.iter().map(|s| s.slot).collect::<Vec<_>>(), [1, 2, 3]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn publisher_spools_failed_batches() {
+    let dir = spool_dir("failed");
+    let spool = Arc::new(Spool::open(&dir, 4096, FullPolicy::DropNewest).unwrap());
+    let batches = publish(messages(), false, true, Arc::clone(&spool));
+    assert!(batches.is_empty());
+    // the first batch is spooled, along with all the queued messages
+    let kinds: Vec<_> = std::iter::from_fn(|| {
+        let record = spool.peek().unwrap()?;
+        spool.commit(&record).unwrap();
+        Some(record.tag)
+    })
+    .collect();
+    let (slot, snapshot) = (Kind::Slot as u8, Kind::Snapshot as u8);
+    assert_eq!(kinds, [slot, slot, slot, snapshot, slot, slot]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// Transport, whose connections record published batches, or reject all of them
+struct Recording(Arc<Mutex<Batches>>, bool);
+
+impl Transport for Recording {
+    fn connect(&self) -> BoxFuture<'_, io::Result<Box<dyn Connection>>> {
+        let connection = Recorder {
+            batches: Arc::clone(&self.0),
+            fail: self.1,
+        };
+        Box::pin(async move { Ok(Box::new(connection) as Box<dyn Connection>) })
+    }
+}
+
+#[test]
+fn spool_drains_in_batches() {
+    let dir = spool_dir("drain");
+    let spool = Arc::new(Spool::open(&dir, 4096, FullPolicy::Block).unwrap());
+    let slot = |slot| Message::from_slot(slot, slot - 1, SlotStatus::Processed);
+    for n in 1..=4 {
+        spool.spill(&slot(n));
+    }
+    // batch, spooled by publisher, is published as it is
+    let packed = [slot(5).serialize().unwrap(), slot(6).serialize().unwrap()];
+    spool.spill_encoded(Kind::Slot, &pubsub_wire::pack(&packed, true).unwrap());
+    let end = SnapshotEnd {
+        accounts: 0,
+        slot: 6,
+    };
+    spool.spill(&Message::from_snapshot(SnapshotData::End(end)));
+
+    let batches = Arc::default();
+    let transport = Arc::new(Recording(Arc::clone(&batches), false));
+    let batching = Batching {
+        max_messages: 3,
+        max_bytes: 1024,
+        linger: Duration::from_millis(10),
+        compress: true,
+    };
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.spawn(drain_spool(Arc::clone(&spool), transport, batching));
+    let published = |check: &dyn Fn() -> bool| {
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !check() {
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    };
+    published(&|| !batches.lock().unwrap().is_empty());
+    // messages keep arriving, while spool is being drained
+    for n in 7..=11 {
+        spool.spill(&slot(n));
+    }
+    published(&|| spool.is_empty());
+
+    let batches = batches.lock().unwrap().clone();
+    // every batch has been packed into single message
+    assert!(batches.iter().all(|(_, m)| m.len() == 1));
+    assert_eq!(batches[3].0, Kind::Snapshot);
+    let slots: Vec<_> = batches
+        .iter()
+        .filter(|(k, _)| *k == Kind::Slot)
+        .map(|(_, m)| {
+            let slots: Vec<SlotData> = pubsub_wire::unpack(&m[0]).unwrap();
+            slots.iter().map(|s| s.slot).collect::<Vec<_>>()
+        })
+        .collect();
+    // packed batches are never merged with other messages
+    assert_eq!(slots[..3], [vec![1, 2, 3], vec![4], vec![5, 6]]);
+    // while the late ones are batched, as they come
+    assert!(slots[3..].iter().all(|s| s.len() <= 3));
+    let late: Vec<_> = slots[3..].concat();
+    assert_eq!(late, [7, 8, 9, 10, 11]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn spool_discards_rejected_batches() {
+    let dir = spool_dir("rejected");
+    let spool = Arc::new(Spool::open(&dir, 4096, FullPolicy::Block).unwrap());
+    for n in 1..=4 {
+        spool.spill(&Message::from_slot(n, n - 1, SlotStatus::Processed));
+    }
+
+    let batches = Arc::default();
+    let transport = Arc::new(Recording(Arc::clone(&batches), true));
+    let batching = Batching {
+        max_messages: 3,
+        max_bytes: 1024,
+        linger: Duration::from_millis(10),
+        compress: false,
+    };
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.spawn(drain_spool(Arc::clone(&spool), transport, batching));
+    // rejected batches don't block the spool forever
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    while !spool.is_empty() {
+        assert!(std::time::Instant::now() < deadline);
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(batches.lock().unwrap().is_empty());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn messages_are_stamped_by_kind() {
+    let mut sequencer = Sequencer::new();
+    let session = sequencer.session;
+    let kinds = [
+        Kind::Slot,
+        Kind::Account,
+        Kind::Slot,
+        Kind::Snapshot,
+        Kind::Slot,
+    ];
+    let seqs: Vec<_> = kinds.iter().map(|&k| sequencer.next(k).seq).collect();
+    assert_eq!(seqs, [0, 0, 1, 0, 2]);
+    assert!(Sequencer::new().session >= session);
+
+    let mut message = Message::from_slot(7, 6, SlotStatus::Confirmed);
+    let stamp = Stamp { session, seq: 3 };
+    message.stamp = Some(stamp);
+    let decoded: Stamped<SlotData> = pubsub_wire::unstamp(&message.serialize().unwrap()).unwrap();
+    assert_eq!(decoded.stamp, Some(stamp));
+    assert_eq!(decoded.payload.slot, 7);
+}
@@ -0,0 +1,248 @@
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use pubsub_wire::Kind;
+use serde::Deserialize;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, UnixListener};
+use tokio::sync::broadcast::{self, error::RecvError, Receiver, Sender};
+use tokio_nsq::{NSQEvent, NSQProducer, NSQProducerConfig, NSQTopic};
+
+/// Max number of batches of frames, which stream consumers can lag behind,
+/// consumers, which lag behind any further, are disconnected
+const STREAM_CAPACITY: usize = 65536;
+
+/// Future, returned by transports and connections
+pub(crate) type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Way of delivering published messages to consumers
+pub(crate) trait Transport: Send + Sync {
+    /// Open new connection, to publish messages over
+    fn connect(&self) -> BoxFuture<'_, io::Result<Box<dyn Connection>>>;
+}
+
+/// Connection, which messages are published over one batch at a time
+pub(crate) trait Connection: Send {
+    /// Publish encoded messages of given kind at once, resolves
+    /// once all of them have been handed over to consumers
+    fn publish<'a>(
+        &'a mut self,
+        kind: Kind,
+        messages: &'a [Vec<u8>],
+    ) -> BoxFuture<'a, io::Result<()>>;
+}
+
+/// Transport, which messages are published over
+#[derive(Deserialize, Debug, Default)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub(crate) enum TransportConfig {
+    /// NSQ daemon at configured host, messages are published to configured topics
+    #[default]
+    Nsq,
+    /// TCP server at given address, e.g. 127.0.0.1:9100, which consumers connect to
+    Tcp { address: String },
+    /// Unix domain socket server at given path, which consumers connect to
+    Unix { path: PathBuf },
+}
+
+/// Publishing to NSQ daemon, with topic chosen by kind of message
+pub(crate) struct NSQTransport {
+    host: String,
+    topics: Arc<Topics>,
+}
+
+pub(crate) struct Topics {
+    pub(crate) accounts: Arc<NSQTopic>,
+    pub(crate) slots: Arc<NSQTopic>,
+    pub(crate) snapshot: Option<Arc<NSQTopic>>,
+}
+
+struct NSQConnection {
+    producer: NSQProducer,
+    topics: Arc<Topics>,
+}
+
+/// Server, which sends every message to all of the consumers, connected
+/// to it at the moment, messages are framed as described in `pubsub_wire`
+pub(crate) struct StreamTransport {
+    sender: Sender<Bytes>,
+}
+
+struct StreamConnection {
+    sender: Sender<Bytes>,
+}
+
+impl NSQTransport {
+    pub(crate) fn new(host: String, topics: Topics) -> Self {
+        let topics = Arc::new(topics);
+        Self { host, topics }
+    }
+}
+
+impl Transport for NSQTransport {
+    fn connect(&self) -> BoxFuture<'_, io::Result<Box<dyn Connection>>> {
+        Box::pin(async move {
+            let mut producer = NSQProducerConfig::new(&self.host).build();
+            if let Some(NSQEvent::Unhealthy()) | None = producer.consume().await {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "nsq daemon is unavailable",
+                ));
+            }
+            let topics = Arc::clone(&self.topics);
+            let connection: Box<dyn Connection> = Box::new(NSQConnection { producer, topics });
+            Ok(connection)
+        })
+    }
+}
+
+impl Connection for NSQConnection {
+    fn publish<'a>(
+        &'a mut self,
+        kind: Kind,
+        messages: &'a [Vec<u8>],
+    ) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let topic = match self.topics.get(kind) {
+                Some(topic) => topic,
+                None => {
+                    log::warn!("discarding {:?} message, as no topic is configured", kind);
+                    return Ok(());
+                }
+            };
+            // several messages are published with single MPUB command,
+            // which is acknowledged as a whole
+            let result = match messages {
+                [message] => self.producer.publish(topic, message.clone()),
+                _ => self.producer.publish_multiple(topic, messages.to_vec()),
+            };
+            result.map_err(|e| io::Error::other(e.to_string()))?;
+            loop {
+                match self.producer.consume().await {
+                    Some(NSQEvent::Ok()) => return Ok(()),
+                    // connection has been (re)established, acknowledgement is yet to come
+                    Some(NSQEvent::Healthy()) => continue,
+                    _ => return Err(io::Error::other("message hasn't been acknowledged by nsq")),
+                }
+            }
+        })
+    }
+}
+
+impl Topics {
+    fn get(&self, kind: Kind) -> Option<&Arc<NSQTopic>> {
+        match kind {
+            Kind::Account => Some(&self.accounts),
+            Kind::Slot => Some(&self.slots),
+            Kind::Snapshot => self.snapshot.as_ref(),
+        }
+    }
+}
+
+impl StreamTransport {
+    /// Start accepting consumers on TCP socket with given address
+    pub(crate) async fn tcp(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address).await?;
+        let transport = Self::new();
+        let sender = transport.sender.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        log::info!("stream consumer connected from {}", peer);
+                        let _ = stream.set_nodelay(true);
+                        tokio::spawn(serve(stream, sender.subscribe()));
+                    }
+                    Err(e) => log::warn!("couldn't accept stream consumer: {}", e),
+                }
+            }
+        });
+        Ok(transport)
+    }
+
+    /// Start accepting consumers on Unix domain socket at given path
+    pub(crate) async fn unix(path: &Path) -> io::Result<Self> {
+        // socket might have been left over from previous run
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let transport = Self::new();
+        let sender = transport.sender.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        log::info!("stream consumer connected");
+                        tokio::spawn(serve(stream, sender.subscribe()));
+                    }
+                    Err(e) => log::warn!("couldn't accept stream consumer: {}", e),
+                }
+            }
+        });
+        Ok(transport)
+    }
+
+    fn new() -> Self {
+        let (sender, _) = broadcast::channel(STREAM_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl Transport for StreamTransport {
+    fn connect(&self) -> BoxFuture<'_, io::Result<Box<dyn Connection>>> {
+        let sender = self.sender.clone();
+        let connection: Box<dyn Connection> = Box::new(StreamConnection { sender });
+        Box::pin(async move { Ok(connection) })
+    }
+}
+
+impl Connection for StreamConnection {
+    fn publish<'a>(
+        &'a mut self,
+        kind: Kind,
+        messages: &'a [Vec<u8>],
+    ) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            // without consumers, message would be lost, so it's treated
+            // the same way, as if the broker was unavailable
+            if self.sender.receiver_count() == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "no stream consumers are connected",
+                ));
+            }
+            let frames = messages
+                .iter()
+                .flat_map(|message| pubsub_wire::frame(kind, message))
+                .collect::<Vec<_>>();
+            self.sender
+                .send(Bytes::from(frames))
+                .map(drop)
+                .map_err(|_| io::Error::new(io::ErrorKind::NotConnected, "consumers have left"))
+        })
+    }
+}
+
+/// Write frames to connected consumer, until it disconnects or lags behind
+async fn serve<S: AsyncWrite + Unpin>(mut stream: S, mut frames: Receiver<Bytes>) {
+    loop {
+        let frame = match frames.recv().await {
+            Ok(frame) => frame,
+            Err(RecvError::Lagged(count)) => {
+                return log::warn!(
+                    "disconnecting stream consumer, which has missed {} messages",
+                    count
+                );
+            }
+            Err(RecvError::Closed) => return,
+        };
+        if let Err(e) = stream.write_all(&frame).await {
+            return log::info!("stream consumer disconnected: {}", e);
+        }
+    }
+}
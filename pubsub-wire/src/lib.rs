@@ -0,0 +1,337 @@
+#![deny(missing_docs)]
+//! Wire protocol, which is used by validator plugin to publish
+//! account and slot updates to pubsub, and by all the consumers
+//! of those updates to read them back.
+//!
+//! Every message on the wire is a single version byte, followed by
+//! MessagePack encoded payload. Structs are encoded as arrays, so
+//! the order of fields is a part of the schema, and any change to
+//! it (or to the set of fields) requires a bump of [`VERSION`]
+//!
+//! Several messages of the same kind can be packed into batch, which
+//! starts with [`BATCH`] or [`ZSTD_BATCH`] marker in place of version
+//! byte, followed by (possibly zstd compressed) sequence of messages,
+//! each of which is prefixed with its little endian `u32` length.
+//!
+//! Every message, published by validator plugin, is stamped: it
+//! starts with [`STAMPED`] marker, followed by [`Stamp`] (as little
+//! endian session and sequence number), followed by message itself.
+//!
+//! Messages, which are sent over plain byte streams, rather than
+//! published to pubsub topics, are wrapped into frames: big endian
+//! length of the rest of frame, followed by [`Kind`] of message,
+//! followed by message itself
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::io;
+
+use bytes::Bytes;
+use rmp_serde as rmps;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Current version of wire protocol schema
+pub const VERSION: u8 = 1;
+/// Marker of batch of messages, which is used in place of version byte
+pub const BATCH: u8 = 0xB0;
+/// Marker of zstd compressed batch of messages
+pub const ZSTD_BATCH: u8 = 0xB1;
+/// Marker of stamped message, which is used in place of version byte
+pub const STAMPED: u8 = 0xB2;
+/// Length of stamp, which follows the marker of stamped message
+pub const STAMP_LEN: usize = 16;
+/// Length of public key in bytes
+pub const KEY_LEN: usize = 32;
+/// Length of frame header, which holds length of the rest of frame
+pub const FRAME_HEADER_LEN: usize = 4;
+/// Max length of frame (without header), consumers reject larger frames
+pub const MAX_FRAME_LEN: usize = 32 << 20;
+
+/// Public key of account or program
+pub type Pubkey = [u8; KEY_LEN];
+/// Slot number
+pub type Slot = u64;
+
+/// Account update, produced by validator
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AccountData {
+    /// Public key of given account
+    pub pubkey: Pubkey,
+    /// Public key owner program (if any) of given account
+    pub owner: Pubkey,
+    /// Number of lamports assigned to this account
+    pub lamports: u64,
+    /// Data associated with the account
+    pub data: Bytes,
+    /// The epoch at which this account will next owe a rent
+    pub rent_epoch: u64,
+    /// Boolean indicating if the account contains a program
+    pub executable: bool,
+    /// Slot number at which the update was generated
+    pub slot: Slot,
+}
+
+/// Slot status update, produced by validator
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlotData {
+    /// Slot number
+    pub slot: Slot,
+    /// Slot number which is considered to be parent of current slot
+    pub parent: Slot,
+    /// Level of finalization of given slot
+    pub status: Commitment,
+}
+
+/// Message of startup snapshot, published by validator on a separate
+/// topic, while it restores accounts from the last snapshot at startup
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum SnapshotData {
+    /// Account, restored from snapshot
+    Account(AccountData),
+    /// Marker of the end of snapshot, live updates follow it
+    End(SnapshotEnd),
+}
+
+/// Summary of startup snapshot, sent once all of its accounts have been
+/// published. Messages are not guaranteed to be delivered in order, so
+/// consumers should wait for the given number of accounts to arrive
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SnapshotEnd {
+    /// Number of accounts, published as part of snapshot
+    pub accounts: u64,
+    /// The most recent slot among published accounts
+    pub slot: Slot,
+}
+
+/// Commitment level of slot, encoded on the wire as a plain integer
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(into = "u8", try_from = "u8")]
+pub enum Commitment {
+    /// Slot has been processed by validator
+    Processed = 1,
+    /// Supermajority of the cluster has voted on slot
+    Confirmed = 2,
+    /// Slot has been rooted
+    Finalized = 3,
+}
+
+/// Stamp, which plugin puts on every published message, so that consumers
+/// can detect messages, which have been lost, duplicated or reordered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Stamp {
+    /// Identifier of plugin session, which grows with every restart of plugin
+    pub session: u64,
+    /// Sequence number of message within session, which is
+    /// counted separately for every kind of messages
+    pub seq: u64,
+}
+
+/// Decoded message, along with its stamp, if it has one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stamped<T> {
+    /// Stamp of message, which is missing if message was published unstamped
+    pub stamp: Option<Stamp>,
+    /// Decoded message itself
+    pub payload: T,
+}
+
+/// Kind of message, which is sent along with it, where there
+/// are no pubsub topics to tell messages of different kinds apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// Message contains [`AccountData`]
+    Account = 0,
+    /// Message contains [`SlotData`]
+    Slot = 1,
+    /// Message contains [`SnapshotData`]
+    Snapshot = 2,
+}
+
+/// Errors, which might occur during encoding or decoding of messages
+#[derive(Debug)]
+pub enum Error {
+    /// Message was empty, not even the version byte was present
+    Empty,
+    /// Message was encoded with unsupported version of schema
+    Version(u8),
+    /// Payload couldn't be encoded
+    Encode(rmps::encode::Error),
+    /// Payload couldn't be decoded
+    Decode(rmps::decode::Error),
+    /// Commitment level on the wire has unknown value
+    Commitment(u8),
+    /// Kind of message in frame has unknown value
+    Kind(u8),
+    /// Batch is truncated, or its messages have invalid lengths
+    Batch,
+    /// Stamp of message is truncated
+    Stamp,
+    /// Batch couldn't be compressed or decompressed
+    Compression(io::Error),
+}
+
+impl From<Commitment> for u8 {
+    fn from(commitment: Commitment) -> Self {
+        commitment as u8
+    }
+}
+
+impl TryFrom<u8> for Commitment {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::Processed),
+            2 => Ok(Self::Confirmed),
+            3 => Ok(Self::Finalized),
+            v => Err(Error::Commitment(v)),
+        }
+    }
+}
+
+impl TryFrom<u8> for Kind {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Account),
+            1 => Ok(Self::Slot),
+            2 => Ok(Self::Snapshot),
+            v => Err(Error::Kind(v)),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty message"),
+            Self::Version(v) => write!(
+                f,
+                "unsupported wire protocol version: {}, expected: {}",
+                v, VERSION
+            ),
+            Self::Encode(e) => write!(f, "encoding error: {}", e),
+            Self::Decode(e) => write!(f, "decoding error: {}", e),
+            Self::Commitment(v) => write!(f, "unknown commitment level: {}", v),
+            Self::Kind(v) => write!(f, "unknown kind of message: {}", v),
+            Self::Batch => write!(f, "malformed batch of messages"),
+            Self::Stamp => write!(f, "truncated stamp of message"),
+            Self::Compression(e) => write!(f, "compression error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Encode payload into versioned message, ready to be published
+pub fn encode<T: Serialize>(payload: &T) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![VERSION];
+    rmps::encode::write(&mut buf, payload).map_err(Error::Encode)?;
+    Ok(buf)
+}
+
+/// Decode payload from versioned message, messages produced
+/// with any other version of schema are rejected
+pub fn decode<T: DeserializeOwned>(buf: &[u8]) -> Result<T, Error> {
+    let (&version, payload) = buf.split_first().ok_or(Error::Empty)?;
+    if version != VERSION {
+        return Err(Error::Version(version));
+    }
+    rmps::from_read_ref(payload).map_err(Error::Decode)
+}
+
+/// Pack encoded messages of the same kind into single batch,
+/// which is optionally compressed with zstd
+pub fn pack(messages: &[Vec<u8>], compress: bool) -> Result<Vec<u8>, Error> {
+    let len = messages.iter().map(|m| m.len() + 4).sum::<usize>();
+    let mut buf = Vec::with_capacity(len + 1);
+    buf.push(BATCH);
+    for message in messages {
+        buf.extend_from_slice(&(message.len() as u32).to_le_bytes());
+        buf.extend_from_slice(message);
+    }
+    if !compress {
+        return Ok(buf);
+    }
+    let mut compressed = vec![ZSTD_BATCH];
+    zstd::stream::copy_encode(&buf[1..], &mut compressed, 0).map_err(Error::Compression)?;
+    Ok(compressed)
+}
+
+/// Put stamp on encoded message
+pub fn stamp(stamp: Stamp, message: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(1 + STAMP_LEN + message.len());
+    buf.push(STAMPED);
+    buf.extend_from_slice(&stamp.session.to_le_bytes());
+    buf.extend_from_slice(&stamp.seq.to_le_bytes());
+    buf.extend_from_slice(message);
+    buf
+}
+
+/// Decode message, which might be stamped
+pub fn unstamp<T: DeserializeOwned>(buf: &[u8]) -> Result<Stamped<T>, Error> {
+    let (stamp, message) = match buf.split_first() {
+        Some((&STAMPED, rest)) if rest.len() >= STAMP_LEN => {
+            let (stamp, message) = rest.split_at(STAMP_LEN);
+            let (session, seq) = stamp.split_at(STAMP_LEN / 2);
+            let stamp = Stamp {
+                session: u64::from_le_bytes(session.try_into().map_err(|_| Error::Stamp)?),
+                seq: u64::from_le_bytes(seq.try_into().map_err(|_| Error::Stamp)?),
+            };
+            (Some(stamp), message)
+        }
+        Some((&STAMPED, _)) => return Err(Error::Stamp),
+        _ => (None, buf),
+    };
+    let payload = decode(message)?;
+    Ok(Stamped { stamp, payload })
+}
+
+/// Decode payloads of all the messages in batch, single
+/// messages are treated as batches of one message
+pub fn unpack<T: DeserializeOwned>(buf: &[u8]) -> Result<Vec<T>, Error> {
+    let messages = unpack_stamped(buf)?;
+    Ok(messages.into_iter().map(|m| m.payload).collect())
+}
+
+/// Decode all the messages in batch, along with their stamps
+pub fn unpack_stamped<T: DeserializeOwned>(buf: &[u8]) -> Result<Vec<Stamped<T>>, Error> {
+    let (&marker, batch) = buf.split_first().ok_or(Error::Empty)?;
+    let decompressed;
+    let mut batch = match marker {
+        BATCH => batch,
+        ZSTD_BATCH => {
+            decompressed = zstd::stream::decode_all(batch).map_err(Error::Compression)?;
+            &decompressed[..]
+        }
+        _ => return unstamp(buf).map(|message| vec![message]),
+    };
+    let mut payloads = Vec::new();
+    while !batch.is_empty() {
+        if batch.len() < 4 {
+            return Err(Error::Batch);
+        }
+        let (len, rest) = batch.split_at(4);
+        let len = u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        if rest.len() < len {
+            return Err(Error::Batch);
+        }
+        let (message, rest) = rest.split_at(len);
+        payloads.push(unstamp(message)?);
+        batch = rest;
+    }
+    Ok(payloads)
+}
+
+/// Wrap encoded message of given kind into frame, to be sent over byte stream
+pub fn frame(kind: Kind, message: &[u8]) -> Vec<u8> {
+    let len = (message.len() + 1) as u32;
+    let mut buf = Vec::with_capacity(FRAME_HEADER_LEN + 1 + message.len());
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.push(kind as u8);
+    buf.extend_from_slice(message);
+    buf
+}
+
+/// Compatibility tests for wire format
+mod tests;
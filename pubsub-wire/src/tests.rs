@@ -0,0 +1,170 @@
+#![cfg(test)]
+use bytes::Bytes;
+
+use crate::*;
+
+fn account() -> AccountData {
+    AccountData {
+        pubkey: [1; KEY_LEN],
+        owner: [2; KEY_LEN],
+        lamports: 42,
+        data: Bytes::from_static(&[1, 2, 3]),
+        rent_epoch: 7,
+        executable: false,
+        slot: 100,
+    }
+}
+
+#[test]
+fn account_roundtrip() {
+    let account = account();
+    let encoded = encode(&account).unwrap();
+    let decoded: AccountData = decode(&encoded).unwrap();
+    assert_eq!(decoded, account);
+}
+
+#[test]
+fn slot_roundtrip() {
+    for status in [
+        Commitment::Processed,
+        Commitment::Confirmed,
+        Commitment::Finalized,
+    ] {
+        let slot = SlotData {
+            slot: 101,
+            parent: 100,
+            status,
+        };
+        let encoded = encode(&slot).unwrap();
+        let decoded: SlotData = decode(&encoded).unwrap();
+        assert_eq!(decoded, slot);
+    }
+}
+
+#[test]
+fn slot_layout() {
+    let slot = SlotData {
+        slot: 10,
+        parent: 9,
+        status: Commitment::Confirmed,
+    };
+    // version, array of 3 elements, slot, parent, commitment as integer
+    assert_eq!(encode(&slot).unwrap(), [VERSION, 0x93, 10, 9, 2]);
+}
+
+#[test]
+fn account_layout() {
+    let encoded = encode(&account()).unwrap();
+    let mut expected = vec![VERSION, 0x97];
+    // public keys are encoded as arrays of 32 integers
+    expected.extend([0xdc, 0, 32]);
+    expected.extend([1; KEY_LEN]);
+    expected.extend([0xdc, 0, 32]);
+    expected.extend([2; KEY_LEN]);
+    // lamports
+    expected.push(42);
+    // data is encoded as binary blob
+    expected.extend([0xc4, 3, 1, 2, 3]);
+    // rent epoch, executable, slot
+    expected.extend([7, 0xc2, 100]);
+    assert_eq!(encoded, expected);
+}
+
+#[test]
+fn snapshot_roundtrip() {
+    let messages = [
+        SnapshotData::Account(account()),
+        SnapshotData::End(SnapshotEnd {
+            accounts: 1,
+            slot: 100,
+        }),
+    ];
+    for message in messages {
+        let encoded = encode(&message).unwrap();
+        let decoded: SnapshotData = decode(&encoded).unwrap();
+        assert_eq!(decoded, message);
+    }
+}
+
+#[test]
+fn reject_unknown_version() {
+    let mut encoded = encode(&account()).unwrap();
+    encoded[0] = VERSION + 1;
+    let result: Result<AccountData, _> = decode(&encoded);
+    assert!(matches!(result, Err(Error::Version(v)) if v == VERSION + 1));
+
+    let result: Result<AccountData, _> = decode(&[]);
+    assert!(matches!(result, Err(Error::Empty)));
+}
+
+#[test]
+fn reject_unknown_commitment() {
+    let result: Result<SlotData, _> = decode(&[VERSION, 0x93, 10, 9, 4]);
+    assert!(matches!(result, Err(Error::Decode(_))));
+}
+
+#[test]
+fn frame_layout() {
+    let encoded = encode(&account()).unwrap();
+    let framed = frame(Kind::Account, &encoded);
+    let (header, rest) = framed.split_at(FRAME_HEADER_LEN);
+    assert_eq!(header, (encoded.len() as u32 + 1).to_be_bytes());
+    assert_eq!(Kind::try_from(rest[0]).unwrap(), Kind::Account);
+    let decoded: AccountData = decode(&rest[1..]).unwrap();
+    assert_eq!(decoded, account());
+
+    assert!(matches!(Kind::try_from(3), Err(Error::Kind(3))));
+}
+
+#[test]
+fn batch_roundtrip() {
+    let accounts: Vec<_> = (0..3)
+        .map(|slot| AccountData { slot, ..account() })
+        .collect();
+    let encoded: Vec<_> = accounts.iter().map(|a| encode(a).unwrap()).collect();
+    for compress in [false, true] {
+        let packed = pack(&encoded, compress).unwrap();
+        assert_eq!(packed[0], if compress { ZSTD_BATCH } else { BATCH });
+        let unpacked: Vec<AccountData> = unpack(&packed).unwrap();
+        assert_eq!(unpacked, accounts);
+    }
+    // single messages are unpacked as they are
+    let unpacked: Vec<AccountData> = unpack(&encoded[0]).unwrap();
+    assert_eq!(unpacked, accounts[..1]);
+}
+
+#[test]
+fn reject_malformed_batch() {
+    let mut packed = pack(&[encode(&account()).unwrap()], false).unwrap();
+    packed.pop();
+    let result: Result<Vec<AccountData>, _> = unpack(&packed);
+    assert!(matches!(result, Err(Error::Batch)));
+
+    let result: Result<Vec<AccountData>, _> = unpack(&[ZSTD_BATCH, 1, 2, 3]);
+    assert!(matches!(result, Err(Error::Compression(_))));
+}
+
+#[test]
+fn stamp_roundtrip() {
+    let stamp = Stamp {
+        session: 1_600_000_000,
+        seq: 42,
+    };
+    let encoded = encode(&account()).unwrap();
+    let stamped = super::stamp(stamp, &encoded);
+    assert_eq!(stamped[0], STAMPED);
+    let decoded: Stamped<AccountData> = unstamp(&stamped).unwrap();
+    assert_eq!(decoded.stamp, Some(stamp));
+    assert_eq!(decoded.payload, account());
+
+    // stamps are kept in batches, and unstamped messages are still accepted
+    let packed = pack(&[stamped, encoded], true).unwrap();
+    let unpacked: Vec<Stamped<AccountData>> = unpack_stamped(&packed).unwrap();
+    assert_eq!(unpacked[0].stamp, Some(stamp));
+    assert_eq!(unpacked[1].stamp, None);
+    let unpacked: Vec<AccountData> = unpack(&packed).unwrap();
+    assert_eq!(unpacked, [account(), account()]);
+
+    let result: Result<Stamped<AccountData>, _> = unstamp(&[STAMPED, 1, 2]);
+    assert!(matches!(result, Err(Error::Stamp)));
+}
@@ -0,0 +1,77 @@
+use structopt::StructOpt;
+
+use crate::consumer::NSQSources;
+
+/// Command line options, which can be supplied during application start
+#[derive(StructOpt)]
+#[structopt(about = "Solana account state storage")]
+pub struct CliOptions {
+    /// Path to directory, where database is kept
+    #[structopt(
+        long,
+        about = "path to directory, where database is kept",
+        default_value = "accounts.db"
+    )]
+    pub db: String,
+    /// Number of slots before the latest root, to keep history of account states for
+    #[structopt(
+        long,
+        about = "number of slots before the latest root, to keep history of account states for",
+        default_value = "216000"
+    )]
+    pub retention: u64,
+    /// List of addresses, where nsq lookup daemons can be queried, e.g. http://127.0.0.1:4161
+    #[structopt(
+        short,
+        long,
+        multiple = true,
+        about = "list of addresses, where nsq lookup daemons can be queried, e.g. http://127.0.0.1:4161"
+    )]
+    pub nsqlookup: Vec<String>,
+    /// List of nsqd TCP addresses to consume from directly, bypassing lookup daemons, e.g. 127.0.0.1:4150
+    #[structopt(
+        long,
+        multiple = true,
+        about = "list of nsqd TCP addresses to consume from directly, bypassing lookup daemons, e.g. 127.0.0.1:4150"
+    )]
+    pub nsqd: Vec<String>,
+    /// NSQ topic, to which account updates are published
+    #[structopt(
+        long = "account-topic",
+        about = "NSQ topic, to which account updates are published",
+        default_value = "accounts"
+    )]
+    pub account_topic: String,
+    /// NSQ channel, to consume account updates from
+    #[structopt(
+        long = "account-channel",
+        about = "NSQ channel, to consume account updates from",
+        default_value = "storage"
+    )]
+    pub account_channel: String,
+    /// NSQ topic, to which slot updates are published
+    #[structopt(
+        long = "slot-topic",
+        about = "NSQ topic, to which slot updates are published",
+        default_value = "slots"
+    )]
+    pub slot_topic: String,
+    /// NSQ channel, to consume slot updates from
+    #[structopt(
+        long = "slot-channel",
+        about = "NSQ channel, to consume slot updates from",
+        default_value = "storage"
+    )]
+    pub slot_channel: String,
+}
+
+impl CliOptions {
+    /// Where to look for NSQ daemons, publishing to configured topics
+    pub fn sources(&self) -> NSQSources {
+        if self.nsqd.is_empty() {
+            NSQSources::Lookup(self.nsqlookup.iter().cloned().collect())
+        } else {
+            NSQSources::Daemons(self.nsqd.clone())
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::HashSet;
+
+use serde::de::DeserializeOwned;
+use tokio_nsq::*;
+
+/// Locations of NSQ daemons, to consume messages from
+#[derive(Clone)]
+pub enum NSQSources {
+    /// List of web addresses of NSQ lookup daemons, like http://127.0.0.1:4161
+    Lookup(HashSet<String>),
+    /// List of TCP addresses of NSQ daemons, like 127.0.0.1:4150
+    Daemons(Vec<String>),
+}
+
+/// Consumer of messages from single NSQ topic, which decodes
+/// them from wire format
+pub struct Consumer(NSQConsumer);
+
+impl Consumer {
+    /// Create new instance of NSQ Consumer.
+    /// Arguments:
+    /// * `topic`: NSQ topic to subscribe to
+    /// * `channel`: NSQ channel to join, after topic subscription
+    /// * `sources`: NSQ lookup daemons or NSQ daemons to consume from
+    pub fn new<T: Into<String>>(topic: T, channel: T, sources: NSQSources) -> Self {
+        let topic = NSQTopic::new(topic).expect("invalid NSQ topic name");
+        let channel = NSQChannel::new(channel).expect("invalid NSQ channel name");
+
+        let sources = match sources {
+            NSQSources::Lookup(lookup) => NSQConsumerConfigSources::Lookup(
+                NSQConsumerLookupConfig::new().set_addresses(lookup),
+            ),
+            NSQSources::Daemons(daemons) => NSQConsumerConfigSources::Daemons(daemons),
+        };
+        let consumer = NSQConsumerConfig::new(topic, channel)
+            .set_sources(sources)
+            .build();
+
+        Self(consumer)
+    }
+
+    /// Consume next message, which can be decoded, messages which
+    /// can't be decoded are logged and removed from NSQ. Batches
+    /// are unpacked, so that several values might be returned at once
+    pub async fn next<T: DeserializeOwned>(&mut self) -> Option<Vec<T>> {
+        loop {
+            let message = self.0.consume_filtered().await?;
+            let result = pubsub_wire::unpack(&message.body);
+            // notify nsq to remove message anyway, so it doesn't get requeued
+            message.finish();
+            match result {
+                Ok(v) => break Some(v),
+                Err(e) => println!("failed to decode message from pubsub: {}", e),
+            }
+        }
+    }
+}
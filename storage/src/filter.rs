@@ -0,0 +1,81 @@
+use serde::Deserialize;
+
+/// Max length of bytes to compare in memcmp filter
+const MAX_MEMCMP_BYTES: usize = 128;
+
+/// Filter of program accounts, all filters of subscription
+/// should match account data, before it's sent to client
+#[derive(Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+#[serde(try_from = "RawFilter")]
+pub enum Filter {
+    /// Account data should be of exact length
+    DataSize(u64),
+    /// Account data should contain given bytes at given offset
+    Memcmp {
+        /// Offset from the start of account data
+        offset: usize,
+        /// Decoded bytes to compare against
+        bytes: Vec<u8>,
+    },
+}
+
+/// Filter in the format, it's sent by client
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum RawFilter {
+    DataSize(u64),
+    Memcmp(RawMemcmp),
+}
+
+#[derive(Deserialize)]
+struct RawMemcmp {
+    offset: usize,
+    bytes: String,
+    #[serde(default)]
+    encoding: MemcmpEncoding,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+enum MemcmpEncoding {
+    #[default]
+    Base58,
+    Base64,
+}
+
+impl TryFrom<RawFilter> for Filter {
+    type Error = String;
+
+    fn try_from(raw: RawFilter) -> Result<Self, Self::Error> {
+        let memcmp = match raw {
+            RawFilter::DataSize(size) => return Ok(Self::DataSize(size)),
+            RawFilter::Memcmp(memcmp) => memcmp,
+        };
+        let bytes = match memcmp.encoding {
+            MemcmpEncoding::Base58 => bs58::decode(&memcmp.bytes)
+                .into_vec()
+                .map_err(|e| e.to_string())?,
+            MemcmpEncoding::Base64 => base64::decode(&memcmp.bytes).map_err(|e| e.to_string())?,
+        };
+        if bytes.len() > MAX_MEMCMP_BYTES {
+            return Err("memcmp bytes are too large".into());
+        }
+        Ok(Self::Memcmp {
+            offset: memcmp.offset,
+            bytes,
+        })
+    }
+}
+
+impl Filter {
+    /// Check whether account data passes the filter
+    pub fn matches(&self, data: &[u8]) -> bool {
+        match self {
+            Self::DataSize(size) => data.len() as u64 == *size,
+            Self::Memcmp { offset, bytes } => data
+                .get(*offset..)
+                .map(|data| data.starts_with(bytes))
+                .unwrap_or_default(),
+        }
+    }
+}
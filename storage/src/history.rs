@@ -0,0 +1,199 @@
+use std::ops::RangeInclusive;
+
+use pubsub_wire::AccountData;
+use sled::transaction::{TransactionError, Transactional};
+
+use crate::{Error, Pubkey, Slot, KEY_LEN};
+
+/// Name of tree, which holds account versions
+const VERSIONS_TREE: &str = "history";
+/// Name of tree, which holds schedule of version removals
+const EXPIRATIONS_TREE: &str = "history/expirations";
+/// Key of the oldest slot, for which history is retained, in meta tree
+const RETAINED_KEY: &[u8] = b"history/retained";
+
+/// Length of slot number in keys
+const SLOT_LEN: usize = std::mem::size_of::<Slot>();
+/// Length of both history keys: public key with slot number
+const HISTORY_KEY_LEN: usize = KEY_LEN + SLOT_LEN;
+
+/// Versioned history of finalized account states. Every version is kept
+/// until its successor gets older than retention window, so the state of
+/// account as of any slot within that window can always be restored
+#[derive(Clone)]
+pub struct History {
+    /// Account versions, by public key and slot of version
+    versions: sled::Tree,
+    /// Schedule of version removals: slot, after which version is no longer
+    /// needed along with its public key, mapped to slot of version itself
+    expirations: sled::Tree,
+    /// Tree, where the oldest retained slot is kept
+    meta: sled::Tree,
+    /// Number of slots to keep history for, everything is kept if not set
+    retention: Option<Slot>,
+}
+
+impl History {
+    pub(crate) fn new(db: &sled::Db, meta: sled::Tree) -> Result<Self, Error> {
+        Ok(Self {
+            versions: db.open_tree(VERSIONS_TREE)?,
+            expirations: db.open_tree(EXPIRATIONS_TREE)?,
+            meta,
+            retention: None,
+        })
+    }
+
+    pub(crate) fn set_retention(&mut self, slots: Slot) {
+        self.retention = Some(slots);
+    }
+
+    /// Save the version of account at its slot, which has been finalized
+    pub fn record(&self, account: &AccountData) -> Result<(), Error> {
+        let pubkey = &account.pubkey;
+        let slot = account.slot;
+        let value = pubsub_wire::encode(account)?;
+        // closest versions, which were recorded before and after this one
+        let prev = self
+            .versions
+            .range(key(pubkey, 0)..key(pubkey, slot))
+            .next_back();
+        let next = match slot.checked_add(1) {
+            Some(after) => self
+                .versions
+                .range(key(pubkey, after)..=key(pubkey, Slot::MAX))
+                .next(),
+            None => None,
+        };
+        let prev = prev.transpose()?.map(|(k, _)| version_slot(&k));
+        let next = next.transpose()?.map(|(k, _)| version_slot(&k));
+
+        let result = (&self.versions, &self.expirations).transaction(|(versions, expirations)| {
+            versions.insert(&key(pubkey, slot)[..], value.as_slice())?;
+            // previous version is no longer needed, once this one gets out of retention window
+            if let Some(prev) = prev {
+                expirations.insert(&key_at(slot, pubkey)[..], &prev.to_be_bytes())?;
+            }
+            // and this one, once the next one does
+            if let Some(next) = next {
+                expirations.insert(&key_at(next, pubkey)[..], &slot.to_be_bytes())?;
+            }
+            Ok(())
+        });
+        result.map_err(|e: TransactionError<()>| match e {
+            TransactionError::Storage(e) => Error::Db(e),
+            TransactionError::Abort(()) => unreachable!("history transaction is never aborted"),
+        })
+    }
+
+    /// Remove versions, which are no longer needed to restore
+    /// account states within retention window from given root
+    pub fn prune(&self, root: Slot) -> Result<(), Error> {
+        let retained = match self.retention {
+            Some(retention) => root.saturating_sub(retention),
+            None => return Ok(()),
+        };
+        if retained <= self.retained()? {
+            return Ok(());
+        }
+        let end = retained.saturating_add(1).to_be_bytes();
+        for entry in self.expirations.range(..&end[..]) {
+            let (k, v) = entry?;
+            let pubkey = &k[SLOT_LEN..];
+            let mut slot = [0; SLOT_LEN];
+            slot.copy_from_slice(&v);
+            // remove version first, so that it's never left without removal scheduled
+            self.versions
+                .remove(&key(pubkey, Slot::from_be_bytes(slot))[..])?;
+            self.expirations.remove(k)?;
+        }
+        self.meta.insert(RETAINED_KEY, &retained.to_be_bytes())?;
+        Ok(())
+    }
+
+    /// The oldest slot, which account states can be restored for
+    pub fn retained(&self) -> Result<Slot, Error> {
+        let slot = self.meta.get(RETAINED_KEY)?.map(|v| {
+            let mut buf = [0; SLOT_LEN];
+            buf.copy_from_slice(&v);
+            Slot::from_be_bytes(buf)
+        });
+        Ok(slot.unwrap_or_default())
+    }
+
+    /// Get the state of account as of given slot, i.e. the most recent
+    /// version, which has been finalized at or before that slot. Closed
+    /// accounts, or those which didn't exist at the time, are reported as missing
+    pub fn as_of(&self, pubkey: &Pubkey, slot: Slot) -> Result<Option<AccountData>, Error> {
+        self.check(slot)?;
+        let version = self
+            .versions
+            .range(key(pubkey, 0)..=key(pubkey, slot))
+            .next_back()
+            .transpose()?;
+        let account = version
+            .map(|(_, v)| pubsub_wire::decode::<AccountData>(&v))
+            .transpose()?;
+        Ok(account.filter(|a| a.lamports > 0))
+    }
+
+    /// List all the versions of account, which have been finalized within
+    /// given range of slots, ordered by slot. Closing of account shows up
+    /// as version with zero lamports
+    pub fn between(
+        &self,
+        pubkey: &Pubkey,
+        slots: RangeInclusive<Slot>,
+    ) -> Result<Vec<AccountData>, Error> {
+        let (&start, &end) = (slots.start(), slots.end());
+        self.check(start)?;
+        let mut versions = Vec::new();
+        for version in self.versions.range(key(pubkey, start)..=key(pubkey, end)) {
+            let (_, v) = version?;
+            versions.push(pubsub_wire::decode(&v)?);
+        }
+        Ok(versions)
+    }
+
+    /// Make sure, that history is retained for given slot
+    fn check(&self, slot: Slot) -> Result<(), Error> {
+        let retained = self.retained()?;
+        if slot < retained {
+            return Err(Error::Retention(retained));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl History {
+    /// Number of account versions, which are currently kept
+    pub fn versions_count(&self) -> usize {
+        self.versions.len()
+    }
+}
+
+/// Key of account version
+#[inline]
+fn key(pubkey: &[u8], slot: Slot) -> [u8; HISTORY_KEY_LEN] {
+    let mut key = [0; HISTORY_KEY_LEN];
+    key[..KEY_LEN].copy_from_slice(pubkey);
+    key[KEY_LEN..].copy_from_slice(&slot.to_be_bytes());
+    key
+}
+
+/// Key of removal schedule entry
+#[inline]
+fn key_at(slot: Slot, pubkey: &Pubkey) -> [u8; HISTORY_KEY_LEN] {
+    let mut key = [0; HISTORY_KEY_LEN];
+    key[..SLOT_LEN].copy_from_slice(&slot.to_be_bytes());
+    key[SLOT_LEN..].copy_from_slice(pubkey);
+    key
+}
+
+/// Slot of account version from its key
+#[inline]
+fn version_slot(key: &[u8]) -> Slot {
+    let mut slot = [0; SLOT_LEN];
+    slot.copy_from_slice(&key[KEY_LEN..]);
+    Slot::from_be_bytes(slot)
+}
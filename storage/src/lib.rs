@@ -0,0 +1,60 @@
+#![deny(missing_docs)]
+//! Persistent storage of account states, which are consumed
+//! from the same pubsub topics, that websocket server listens to
+use std::fmt::{self, Display};
+
+pub use pubsub_wire::{Commitment, Pubkey, Slot, KEY_LEN};
+
+/// Command line options, provided at application startup
+pub mod cli;
+/// Consumption of account and slot updates from NSQ
+pub mod consumer;
+/// Filters of program accounts by their data
+pub mod filter;
+/// Versioned history of finalized account states
+pub mod history;
+/// Data structure to keep track of slot updates
+pub mod slotree;
+/// Embedded key value store of account states
+pub mod store;
+/// Application of account and slot updates to store
+pub mod writer;
+
+mod tests;
+
+/// Errors, which might occur during storage operations
+#[derive(Debug)]
+pub enum Error {
+    /// Error of underlying key value store
+    Db(sled::Error),
+    /// Stored record couldn't be encoded or decoded
+    Wire(pubsub_wire::Error),
+    /// History is not retained for requested slots, only starting from given slot
+    Retention(Slot),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Db(e) => write!(f, "database error: {}", e),
+            Self::Wire(e) => write!(f, "record error: {}", e),
+            Self::Retention(slot) => {
+                write!(f, "history is only retained starting from slot {}", slot)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<sled::Error> for Error {
+    fn from(e: sled::Error) -> Self {
+        Self::Db(e)
+    }
+}
+
+impl From<pubsub_wire::Error> for Error {
+    fn from(e: pubsub_wire::Error) -> Self {
+        Self::Wire(e)
+    }
+}
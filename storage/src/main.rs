@@ -1,3 +1,36 @@
-fn main() {
-    println!("Hello, world!");
+use pubsub_wire::{AccountData, SlotData};
+use structopt::StructOpt;
+
+use storage::cli::CliOptions;
+use storage::consumer::Consumer;
+use storage::store::Store;
+use storage::writer::Writer;
+
+#[tokio::main]
+async fn main() -> Result<(), storage::Error> {
+    let opts = CliOptions::from_args();
+    let store = Store::open(&opts.db)?.retention(opts.retention);
+    let mut writer = Writer::new(store);
+
+    let sources = opts.sources();
+    let mut accounts = Consumer::new(opts.account_topic, opts.account_channel, sources.clone());
+    let mut slots = Consumer::new(opts.slot_topic, opts.slot_channel, sources);
+    println!("Subscribed to NSQ pubsub topics");
+
+    loop {
+        tokio::select! {
+            Some(batch) = accounts.next::<AccountData>() => {
+                for account in batch {
+                    writer.account(account)?;
+                }
+            }
+            Some(batch) = slots.next::<SlotData>() => {
+                for slot in batch {
+                    writer.slot(slot)?;
+                }
+            }
+            else => break,
+        }
+    }
+    writer.store().flush()
 }
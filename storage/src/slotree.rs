@@ -0,0 +1,273 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+
+use pubsub_wire::{Commitment, SlotData};
+
+use crate::Slot;
+
+// TODO add support for tracking the length
+/// Tree of slots, which haven't been rooted yet, along with their
+/// forks. Once slot is rooted, all the rival forks get pruned. Nodes
+/// are owned by tree itself, and refer to each other by slot numbers
+pub struct SlotTree {
+    /// Slot number of the most recently rooted slot
+    root: Slot,
+    /// Nodes of all the tracked slots, including the root
+    nodes: HashMap<Slot, SlotNode>,
+    bootstrapping: bool,
+}
+
+/// Level of finalization of slot in tree
+#[derive(Clone, Copy, Default)]
+pub enum SlotStatus {
+    /// Slot has been processed by validator
+    Processed = 1,
+    /// Supermajority of the cluster has voted on slot
+    Confirmed = 2,
+    /// Slot has been rooted, and can no longer be rolled back
+    #[default]
+    Rooted = 3,
+}
+
+struct SlotNode {
+    parent: Slot,
+    children: HashSet<Slot>,
+    status: SlotStatus,
+}
+
+/// Slot update, which can be pushed into the tree
+pub trait RawSlot {
+    /// Slot number
+    fn slot(&self) -> Slot;
+    /// Slot number of parent slot
+    fn parent(&self) -> Slot;
+    /// Level of finalization of slot
+    fn status(&self) -> SlotStatus;
+}
+
+impl SlotNode {
+    fn new(parent: Slot, status: SlotStatus) -> Self {
+        Self {
+            parent,
+            children: HashSet::default(),
+            status,
+        }
+    }
+
+    #[inline]
+    fn rooted(&self) -> bool {
+        matches!(self.status, SlotStatus::Rooted)
+    }
+}
+
+/// Slot, which has been removed from tree, either because it has
+/// been rooted, or because it belonged to the pruned fork
+pub struct RootedOrPrunedSlot {
+    slot: Slot,
+    rooted: bool,
+}
+
+impl Deref for RootedOrPrunedSlot {
+    type Target = Slot;
+
+    fn deref(&self) -> &Self::Target {
+        &self.slot
+    }
+}
+
+impl RootedOrPrunedSlot {
+    #[inline]
+    fn new(slot: Slot) -> Self {
+        Self {
+            slot,
+            rooted: false,
+        }
+    }
+    #[inline]
+    fn root(mut self) -> Self {
+        self.rooted = true;
+        self
+    }
+
+    /// Whether the slot has been rooted, as opposed to pruned
+    #[inline]
+    pub fn rooted(&self) -> bool {
+        self.rooted
+    }
+}
+
+impl From<RootedOrPrunedSlot> for Slot {
+    fn from(rops: RootedOrPrunedSlot) -> Self {
+        rops.slot
+    }
+}
+
+impl SlotTree {
+    /// Create empty tree, which will bootstrap itself from
+    /// the first rooted slot it observes
+    pub fn new() -> Self {
+        Self {
+            root: 0,
+            nodes: HashMap::default(),
+            bootstrapping: true,
+        }
+    }
+
+    fn bootstrap<T: RawSlot>(&mut self, raw: T) -> Option<Vec<RootedOrPrunedSlot>> {
+        let slot = raw.slot();
+        let node = SlotNode::new(raw.parent(), raw.status());
+        let rooted = node.rooted();
+        // there's no root to attach slots to yet, so they are kept
+        // detached, until it's known, which of them descend from it
+        self.nodes.insert(slot, node);
+        if !rooted {
+            return None;
+        }
+        let mut detached = std::mem::take(&mut self.nodes);
+        let links: Vec<_> = detached.iter().map(|(&s, n)| (s, n.parent)).collect();
+        for (child, parent) in links {
+            if let Some(node) = detached.get_mut(&parent) {
+                node.children.insert(child);
+            }
+        }
+        // descendants of the root stay in tree, attached to their parents
+        let mut descendants = vec![slot];
+        while let Some(slot) = descendants.pop() {
+            if let Some(node) = detached.remove(&slot) {
+                descendants.extend(&node.children);
+                self.nodes.insert(slot, node);
+            }
+        }
+        let mut rooted_or_pruned = vec![RootedOrPrunedSlot::new(slot).root()];
+        // ancestors of the root are rooted along with it
+        let mut ancestor = self.nodes[&slot].parent;
+        while let Some(node) = detached.remove(&ancestor) {
+            rooted_or_pruned.push(RootedOrPrunedSlot::new(ancestor).root());
+            ancestor = node.parent;
+        }
+        // and the rest of slots belong to the rival forks
+        let pruned = detached.into_keys().map(RootedOrPrunedSlot::new);
+        rooted_or_pruned.extend(pruned);
+        self.root = slot;
+        self.bootstrapping = false;
+        Some(rooted_or_pruned)
+    }
+
+    /// Add slot update to tree, if slot became rooted as a result,
+    /// return it along with all the slots, which got rooted or pruned
+    pub fn push<T: RawSlot>(&mut self, raw: T) -> Option<Vec<RootedOrPrunedSlot>> {
+        if self.bootstrapping {
+            return self.bootstrap(raw);
+        }
+        let (slot, parent) = (raw.slot(), raw.parent());
+        if slot <= self.root {
+            // shouldn't be able to modify already rooted nodes
+            return None;
+        }
+        // slots can only be attached to the tracked ones
+        self.nodes.get_mut(&parent)?.children.insert(slot);
+
+        let old_parent = match self.nodes.get_mut(&slot) {
+            Some(node) => {
+                // slot is already being tracked
+                node.status = raw.status();
+                std::mem::replace(&mut node.parent, parent)
+            }
+            None => {
+                // slot has never been seen before
+                self.nodes.insert(slot, SlotNode::new(parent, raw.status()));
+                parent
+            }
+        };
+        // check whether slot has changed parents
+        if old_parent != parent {
+            if let Some(node) = self.nodes.get_mut(&old_parent) {
+                node.children.remove(&slot);
+            }
+        }
+
+        if !self.nodes[&slot].rooted() {
+            return None;
+        }
+
+        Some(self.root(slot))
+    }
+
+    fn root(&mut self, slot: Slot) -> Vec<RootedOrPrunedSlot> {
+        let mut rooted_or_pruned = vec![RootedOrPrunedSlot::new(slot).root()];
+        let mut child = slot;
+        let mut parent = self.nodes[&slot].parent;
+        loop {
+            // all the ancestors of new root leave the tree, including the old root
+            let node = self
+                .nodes
+                .remove(&parent)
+                .expect("active nodes should always have parents");
+            let rooted = node.rooted();
+            // get all the child slots which were in rival branches and prune them
+            for orphan in node.children.into_iter().filter(|&s| s != child) {
+                let pruned = self.prune(orphan).into_iter().map(RootedOrPrunedSlot::new);
+                rooted_or_pruned.extend(pruned);
+            }
+            if rooted {
+                break;
+            }
+            rooted_or_pruned.push(RootedOrPrunedSlot::new(parent).root());
+            child = parent;
+            parent = node.parent;
+        }
+        self.root = slot;
+        rooted_or_pruned
+    }
+
+    /// Remove slot from tree along with all of its descendants
+    fn prune(&mut self, slot: Slot) -> Vec<Slot> {
+        let mut pruned = Vec::new();
+        let mut orphans = vec![slot];
+        while let Some(slot) = orphans.pop() {
+            if let Some(node) = self.nodes.remove(&slot) {
+                orphans.extend(node.children);
+                pruned.push(slot);
+            }
+        }
+        pruned
+    }
+
+    /// Slot number of the most recently rooted slot
+    pub fn current_root(&self) -> Slot {
+        self.root
+    }
+}
+
+impl Default for SlotTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<Commitment> for SlotStatus {
+    fn from(commitment: Commitment) -> Self {
+        match commitment {
+            Commitment::Processed => Self::Processed,
+            Commitment::Confirmed => Self::Confirmed,
+            Commitment::Finalized => Self::Rooted,
+        }
+    }
+}
+
+impl RawSlot for SlotData {
+    #[inline]
+    fn slot(&self) -> Slot {
+        self.slot
+    }
+
+    #[inline]
+    fn parent(&self) -> Slot {
+        self.parent
+    }
+
+    #[inline]
+    fn status(&self) -> SlotStatus {
+        self.status.into()
+    }
+}
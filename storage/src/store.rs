@@ -0,0 +1,210 @@
+use std::path::Path;
+
+use pubsub_wire::AccountData;
+use sled::transaction::{ConflictableTransactionError, TransactionError, Transactional};
+
+use crate::{filter::Filter, history::History, Commitment, Error, Pubkey, Slot, KEY_LEN};
+
+/// Names of trees, which hold account states for each commitment level
+const LEVELS: [&str; 3] = ["processed", "confirmed", "finalized"];
+/// Name of tree, which holds latest observed slots
+const SLOTS_TREE: &str = "slots";
+
+/// Latest known state of every account at each commitment level,
+/// persisted in embedded key value store. Accounts are keyed by
+/// public key, and stored in the same format they're sent over wire.
+/// Closed accounts (with zero lamports) are kept as tombstones, so
+/// that stale updates can't bring them back. Store is cheap to
+/// clone, and all the clones share the same underlying database
+#[derive(Clone)]
+pub struct Store {
+    db: sled::Db,
+    levels: [Level; 3],
+    slots: sled::Tree,
+    history: History,
+}
+
+/// Trees of single commitment level
+#[derive(Clone)]
+struct Level {
+    /// Account states by public key
+    accounts: sled::Tree,
+    /// Secondary index of accounts by owner: owner and
+    /// account public keys concatenated, with empty values
+    owners: sled::Tree,
+}
+
+impl Store {
+    /// Open store at given path, creating it if necessary
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::new(sled::open(path)?)
+    }
+
+    /// Open store, which is removed once the last of its clones is dropped
+    pub fn temporary() -> Result<Self, Error> {
+        Self::new(sled::Config::new().temporary(true).open()?)
+    }
+
+    pub(crate) fn new(db: sled::Db) -> Result<Self, Error> {
+        let [processed, confirmed, finalized] = LEVELS;
+        let levels = [
+            Level::open(&db, processed)?,
+            Level::open(&db, confirmed)?,
+            Level::open(&db, finalized)?,
+        ];
+        let slots = db.open_tree(SLOTS_TREE)?;
+        let history = History::new(&db, slots.clone())?;
+        Ok(Self {
+            db,
+            levels,
+            slots,
+            history,
+        })
+    }
+
+    /// Limit history of account states to given number of slots
+    /// before the latest root, by default all the history is kept
+    pub fn retention(mut self, slots: Slot) -> Self {
+        self.history.set_retention(slots);
+        self
+    }
+
+    /// History of finalized account states
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Get latest state of account at given commitment level,
+    /// closed accounts are reported as missing
+    pub fn get(
+        &self,
+        pubkey: &Pubkey,
+        commitment: Commitment,
+    ) -> Result<Option<AccountData>, Error> {
+        let account = self.level(commitment).get(pubkey)?;
+        Ok(account.filter(|a| a.lamports > 0))
+    }
+
+    /// Save account state at given commitment level, unless the
+    /// state, which is already stored, belongs to a newer slot.
+    /// Returns whether the state has been saved
+    pub fn put(&self, account: &AccountData, commitment: Commitment) -> Result<bool, Error> {
+        let level = self.level(commitment);
+        let value = pubsub_wire::encode(account)?;
+        let new = (account.lamports > 0).then(|| index_key(&account.owner, &account.pubkey));
+
+        let result = (&level.accounts, &level.owners).transaction(|(accounts, owners)| {
+            let old = accounts
+                .get(account.pubkey)?
+                .map(|v| pubsub_wire::decode::<AccountData>(&v))
+                .transpose()
+                .map_err(|e| ConflictableTransactionError::Abort(Error::from(e)))?;
+            if matches!(old, Some(ref old) if old.slot > account.slot) {
+                return Ok(false);
+            }
+            accounts.insert(&account.pubkey, value.as_slice())?;
+            let old = old
+                .filter(|old| old.lamports > 0)
+                .map(|old| index_key(&old.owner, &old.pubkey));
+            // keep index up to date, if account has changed owner, or has been closed
+            if old != new {
+                if let Some(ref key) = old {
+                    owners.remove(&key[..])?;
+                }
+                if let Some(ref key) = new {
+                    owners.insert(&key[..], &[])?;
+                }
+            }
+            Ok(true)
+        });
+        result.map_err(|e| match e {
+            TransactionError::Abort(e) => e,
+            TransactionError::Storage(e) => Error::Db(e),
+        })
+    }
+
+    /// Get all the accounts, owned by given program at given commitment
+    /// level, whose data passes all the filters, the same way they're
+    /// applied by `getProgramAccounts`
+    pub fn program_accounts(
+        &self,
+        owner: &Pubkey,
+        commitment: Commitment,
+        filters: &[Filter],
+    ) -> Result<Vec<AccountData>, Error> {
+        let level = self.level(commitment);
+        let mut accounts = Vec::new();
+        for key in level.owners.scan_prefix(owner).keys() {
+            let key = key?;
+            let pubkey = &key[KEY_LEN..];
+            let account = match level.get(pubkey)? {
+                Some(account) => account,
+                // account has been removed, after index was scanned
+                None => continue,
+            };
+            // account might have changed its owner, after index was scanned
+            if &account.owner != owner || account.lamports == 0 {
+                continue;
+            }
+            if filters.iter().all(|f| f.matches(&account.data)) {
+                accounts.push(account);
+            }
+        }
+        Ok(accounts)
+    }
+
+    /// Latest slot observed at given commitment level
+    pub fn slot(&self, commitment: Commitment) -> Result<Option<Slot>, Error> {
+        let slot = self.slots.get([commitment as u8])?.map(|v| {
+            let mut buf = [0; 8];
+            buf.copy_from_slice(&v);
+            Slot::from_be_bytes(buf)
+        });
+        Ok(slot)
+    }
+
+    /// Record slot as observed at given commitment level, if it's newer than known one
+    pub fn set_slot(&self, slot: Slot, commitment: Commitment) -> Result<(), Error> {
+        if self.slot(commitment)? < Some(slot) {
+            self.slots.insert([commitment as u8], &slot.to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Make sure that all the changes are persisted on disk
+    pub fn flush(&self) -> Result<(), Error> {
+        self.db.flush()?;
+        Ok(())
+    }
+
+    #[inline]
+    fn level(&self, commitment: Commitment) -> &Level {
+        &self.levels[commitment as usize - 1]
+    }
+}
+
+impl Level {
+    fn open(db: &sled::Db, name: &str) -> Result<Self, Error> {
+        Ok(Self {
+            accounts: db.open_tree(format!("accounts/{}", name))?,
+            owners: db.open_tree(format!("owners/{}", name))?,
+        })
+    }
+
+    /// Get stored account state, including tombstones of closed accounts
+    fn get(&self, pubkey: &[u8]) -> Result<Option<AccountData>, Error> {
+        self.accounts
+            .get(pubkey)?
+            .map(|v| pubsub_wire::decode(&v).map_err(Into::into))
+            .transpose()
+    }
+}
+
+/// Key of owner index entry
+#[inline]
+fn index_key(owner: &Pubkey, pubkey: &Pubkey) -> [u8; 2 * KEY_LEN] {
+    let mut key = [0; 2 * KEY_LEN];
+    key[..KEY_LEN].copy_from_slice(owner);
+    key[KEY_LEN..].copy_from_slice(pubkey);
+    key
+}
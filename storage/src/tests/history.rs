@@ -0,0 +1,97 @@
+#![cfg(test)]
+use pubsub_wire::AccountData;
+
+use crate::{store::Store, Error, Pubkey, Slot};
+
+const KEY: Pubkey = [1; 32];
+const OTHER_KEY: Pubkey = [2; 32];
+
+fn store(retention: Slot) -> Store {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    Store::new(db).unwrap().retention(retention)
+}
+
+fn account(pubkey: Pubkey, lamports: u64, slot: Slot) -> AccountData {
+    AccountData {
+        pubkey,
+        owner: [0; 32],
+        lamports,
+        data: vec![].into(),
+        rent_epoch: 0,
+        executable: false,
+        slot,
+    }
+}
+
+fn as_of(store: &Store, pubkey: &Pubkey, slot: Slot) -> Option<u64> {
+    let account = store.history().as_of(pubkey, slot).unwrap();
+    account.map(|a| a.lamports)
+}
+
+fn between(store: &Store, slots: std::ops::RangeInclusive<Slot>) -> Vec<(Slot, u64)> {
+    let versions = store.history().between(&KEY, slots).unwrap();
+    versions.into_iter().map(|a| (a.slot, a.lamports)).collect()
+}
+
+#[test]
+fn point_in_time() {
+    let store = store(1000);
+    let history = store.history();
+    for (slot, lamports) in [(10, 1), (20, 2), (30, 0), (40, 4)] {
+        history.record(&account(KEY, lamports, slot)).unwrap();
+    }
+    assert_eq!(as_of(&store, &KEY, 9), None);
+    assert_eq!(as_of(&store, &KEY, 10), Some(1));
+    assert_eq!(as_of(&store, &KEY, 25), Some(2));
+    // account has been closed at that time
+    assert_eq!(as_of(&store, &KEY, 35), None);
+    assert_eq!(as_of(&store, &KEY, 100), Some(4));
+    assert_eq!(as_of(&store, &OTHER_KEY, 100), None);
+
+    assert_eq!(between(&store, 15..=40), [(20, 2), (30, 0), (40, 4)]);
+    assert_eq!(between(&store, 11..=19), []);
+}
+
+#[test]
+fn out_of_order_versions() {
+    let store = store(10);
+    let history = store.history();
+    for (slot, lamports) in [(30, 3), (10, 1), (20, 2)] {
+        history.record(&account(KEY, lamports, slot)).unwrap();
+    }
+    assert_eq!(between(&store, 0..=30), [(10, 1), (20, 2), (30, 3)]);
+    // versions at 10 and 20 are both superseded by root - retention
+    history.prune(40).unwrap();
+    assert_eq!(history.versions_count(), 1);
+    assert_eq!(as_of(&store, &KEY, 30), Some(3));
+}
+
+#[test]
+fn retention() {
+    let store = store(20);
+    let history = store.history();
+    for (slot, lamports) in [(10, 1), (20, 2), (30, 3)] {
+        history.record(&account(KEY, lamports, slot)).unwrap();
+    }
+    // account, which hasn't changed for longer than retention window
+    history.record(&account(OTHER_KEY, 5, 5)).unwrap();
+
+    history.prune(45).unwrap();
+    assert_eq!(history.retained().unwrap(), 25);
+    // only version at 10 is no longer needed
+    assert_eq!(history.versions_count(), 3);
+    assert_eq!(as_of(&store, &KEY, 25), Some(2));
+    assert_eq!(as_of(&store, &KEY, 30), Some(3));
+    assert_eq!(as_of(&store, &OTHER_KEY, 45), Some(5));
+    assert!(matches!(history.as_of(&KEY, 24), Err(Error::Retention(25))));
+    assert!(matches!(
+        history.between(&KEY, 10..=30),
+        Err(Error::Retention(25))
+    ));
+
+    // once the old version is superseded, it's removed as well
+    history.record(&account(OTHER_KEY, 6, 40)).unwrap();
+    history.prune(60).unwrap();
+    assert_eq!(as_of(&store, &OTHER_KEY, 40), Some(6));
+    assert_eq!(history.versions_count(), 2);
+}
@@ -0,0 +1,4 @@
+mod history;
+mod slotree;
+mod store;
+mod writer;
@@ -0,0 +1,105 @@
+#![cfg(test)]
+use pubsub_wire::SlotData;
+
+use crate::{slotree::SlotTree, Commitment, Slot};
+
+fn slot(slot: Slot, parent: Slot, status: Commitment) -> SlotData {
+    SlotData {
+        slot,
+        parent,
+        status,
+    }
+}
+
+/// Push slot update, and split the result into rooted and pruned slots
+fn push(tree: &mut SlotTree, update: SlotData) -> Option<(Vec<Slot>, Vec<Slot>)> {
+    let (rooted, pruned): (Vec<_>, Vec<_>) =
+        tree.push(update)?.into_iter().partition(|s| s.rooted());
+    let mut rooted: Vec<Slot> = rooted.into_iter().map(Slot::from).collect();
+    let mut pruned: Vec<Slot> = pruned.into_iter().map(Slot::from).collect();
+    rooted.sort_unstable();
+    pruned.sort_unstable();
+    Some((rooted, pruned))
+}
+
+fn bootstrapped(root: Slot) -> SlotTree {
+    let mut tree = SlotTree::new();
+    push(&mut tree, slot(root, root - 1, Commitment::Finalized)).unwrap();
+    tree
+}
+
+#[test]
+fn bootstrap() {
+    let mut tree = SlotTree::new();
+    let updates = [
+        (9, 8, Commitment::Processed),
+        (11, 10, Commitment::Processed),
+        (12, 11, Commitment::Confirmed),
+        (13, 9, Commitment::Processed),
+        (15, 14, Commitment::Processed),
+    ];
+    for (s, p, status) in updates {
+        assert_eq!(push(&mut tree, slot(s, p, status)), None);
+    }
+    // slots seen before the first root are attached to it, if they descend
+    // from it, while its ancestors are rooted, and the rival forks are pruned
+    assert_eq!(
+        push(&mut tree, slot(10, 9, Commitment::Finalized)),
+        Some((vec![9, 10], vec![13, 15]))
+    );
+    assert_eq!(tree.current_root(), 10);
+    assert_eq!(push(&mut tree, slot(14, 12, Commitment::Processed)), None);
+    assert_eq!(
+        push(&mut tree, slot(14, 12, Commitment::Finalized)),
+        Some((vec![11, 12, 14], vec![]))
+    );
+}
+
+#[test]
+fn rooting_prunes_rival_forks() {
+    let mut tree = bootstrapped(10);
+    for (s, p) in [(11, 10), (12, 11), (13, 12), (14, 11), (15, 14), (16, 10)] {
+        assert_eq!(push(&mut tree, slot(s, p, Commitment::Processed)), None);
+    }
+    assert_eq!(push(&mut tree, slot(12, 11, Commitment::Confirmed)), None);
+    assert_eq!(
+        push(&mut tree, slot(12, 11, Commitment::Finalized)),
+        Some((vec![11, 12], vec![14, 15, 16]))
+    );
+    assert_eq!(tree.current_root(), 12);
+    // rooted slots can no longer change
+    assert_eq!(push(&mut tree, slot(11, 10, Commitment::Finalized)), None);
+    assert_eq!(push(&mut tree, slot(12, 11, Commitment::Finalized)), None);
+    // while descendants of the new root are still tracked
+    assert_eq!(
+        push(&mut tree, slot(13, 12, Commitment::Finalized)),
+        Some((vec![13], vec![]))
+    );
+}
+
+#[test]
+fn slot_changes_parent() {
+    let mut tree = bootstrapped(10);
+    push(&mut tree, slot(11, 10, Commitment::Processed));
+    push(&mut tree, slot(12, 10, Commitment::Processed));
+    push(&mut tree, slot(13, 11, Commitment::Processed));
+    // slot is moved to another fork, along with its descendants
+    push(&mut tree, slot(13, 12, Commitment::Processed));
+    push(&mut tree, slot(14, 13, Commitment::Processed));
+    assert_eq!(
+        push(&mut tree, slot(14, 13, Commitment::Finalized)),
+        Some((vec![12, 13, 14], vec![11]))
+    );
+}
+
+#[test]
+fn unknown_parent() {
+    let mut tree = bootstrapped(10);
+    assert_eq!(push(&mut tree, slot(12, 11, Commitment::Processed)), None);
+    push(&mut tree, slot(11, 10, Commitment::Processed));
+    // slot, which couldn't be attached, is neither rooted nor pruned
+    assert_eq!(
+        push(&mut tree, slot(11, 10, Commitment::Finalized)),
+        Some((vec![11], vec![]))
+    );
+}
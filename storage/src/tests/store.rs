@@ -0,0 +1,128 @@
+#![cfg(test)]
+use pubsub_wire::AccountData;
+
+use crate::{filter::Filter, store::Store, Commitment, Pubkey, Slot};
+
+const PROGRAM: Pubkey = [7; 32];
+const OTHER_PROGRAM: Pubkey = [8; 32];
+
+fn store() -> Store {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    Store::new(db).unwrap()
+}
+
+fn account(pubkey: u8, owner: Pubkey, lamports: u64, data: &[u8], slot: Slot) -> AccountData {
+    AccountData {
+        pubkey: [pubkey; 32],
+        owner,
+        lamports,
+        data: data.to_vec().into(),
+        rent_epoch: 0,
+        executable: false,
+        slot,
+    }
+}
+
+fn program_accounts(store: &Store, owner: &Pubkey, filters: &[Filter]) -> Vec<u8> {
+    let accounts = store
+        .program_accounts(owner, Commitment::Finalized, filters)
+        .unwrap();
+    accounts.into_iter().map(|a| a.pubkey[0]).collect()
+}
+
+#[test]
+fn owner_index() {
+    let store = store();
+    for (pubkey, owner) in [(1, PROGRAM), (2, PROGRAM), (3, OTHER_PROGRAM)] {
+        let account = account(pubkey, owner, 1, &[pubkey], 10);
+        store.put(&account, Commitment::Finalized).unwrap();
+    }
+    assert_eq!(program_accounts(&store, &PROGRAM, &[]), [1, 2]);
+    assert_eq!(program_accounts(&store, &OTHER_PROGRAM, &[]), [3]);
+    // index is kept separately for every commitment level
+    let accounts = store
+        .program_accounts(&PROGRAM, Commitment::Confirmed, &[])
+        .unwrap();
+    assert!(accounts.is_empty());
+}
+
+#[test]
+fn owner_change() {
+    let store = store();
+    store
+        .put(&account(1, PROGRAM, 1, &[], 10), Commitment::Finalized)
+        .unwrap();
+    store
+        .put(
+            &account(1, OTHER_PROGRAM, 1, &[], 11),
+            Commitment::Finalized,
+        )
+        .unwrap();
+    assert!(program_accounts(&store, &PROGRAM, &[]).is_empty());
+    assert_eq!(program_accounts(&store, &OTHER_PROGRAM, &[]), [1]);
+
+    // stale update doesn't move account back to previous owner
+    store
+        .put(&account(1, PROGRAM, 1, &[], 9), Commitment::Finalized)
+        .unwrap();
+    assert!(program_accounts(&store, &PROGRAM, &[]).is_empty());
+    assert_eq!(program_accounts(&store, &OTHER_PROGRAM, &[]), [1]);
+}
+
+#[test]
+fn closed_account() {
+    let store = store();
+    store
+        .put(&account(1, PROGRAM, 1, &[], 10), Commitment::Finalized)
+        .unwrap();
+    store
+        .put(&account(1, PROGRAM, 0, &[], 11), Commitment::Finalized)
+        .unwrap();
+    assert!(program_accounts(&store, &PROGRAM, &[]).is_empty());
+    assert!(store
+        .get(&[1; 32], Commitment::Finalized)
+        .unwrap()
+        .is_none());
+
+    // older state can't resurrect closed account
+    store
+        .put(&account(1, PROGRAM, 1, &[], 10), Commitment::Finalized)
+        .unwrap();
+    assert!(program_accounts(&store, &PROGRAM, &[]).is_empty());
+
+    // but account can be reopened later
+    store
+        .put(&account(1, PROGRAM, 5, &[], 12), Commitment::Finalized)
+        .unwrap();
+    assert_eq!(program_accounts(&store, &PROGRAM, &[]), [1]);
+}
+
+#[test]
+fn program_accounts_filters() {
+    let store = store();
+    let accounts = [
+        account(1, PROGRAM, 1, &[1, 2, 3], 10),
+        account(2, PROGRAM, 1, &[1, 2, 4], 10),
+        account(3, PROGRAM, 1, &[1, 2], 10),
+    ];
+    for account in &accounts {
+        store.put(account, Commitment::Finalized).unwrap();
+    }
+    let memcmp = |offset, bytes: &[u8]| Filter::Memcmp {
+        offset,
+        bytes: bytes.to_vec(),
+    };
+    assert_eq!(
+        program_accounts(&store, &PROGRAM, &[Filter::DataSize(3)]),
+        [1, 2]
+    );
+    assert_eq!(
+        program_accounts(&store, &PROGRAM, &[memcmp(1, &[2, 4])]),
+        [2]
+    );
+    assert_eq!(
+        program_accounts(&store, &PROGRAM, &[Filter::DataSize(3), memcmp(0, &[1, 2])]),
+        [1, 2]
+    );
+    assert!(program_accounts(&store, &PROGRAM, &[memcmp(2, &[5])]).is_empty());
+}
@@ -0,0 +1,99 @@
+#![cfg(test)]
+use pubsub_wire::{AccountData, SlotData};
+
+use crate::{store::Store, writer::Writer, Commitment, Pubkey, Slot};
+
+const KEY: Pubkey = [1; 32];
+
+fn writer() -> Writer {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    Writer::new(Store::new(db).unwrap())
+}
+
+fn account(slot: Slot, lamports: u64) -> AccountData {
+    AccountData {
+        pubkey: KEY,
+        owner: [2; 32],
+        lamports,
+        data: vec![1, 2, 3].into(),
+        rent_epoch: 0,
+        executable: false,
+        slot,
+    }
+}
+
+fn slot(slot: Slot, parent: Slot, status: Commitment) -> SlotData {
+    SlotData {
+        slot,
+        parent,
+        status,
+    }
+}
+
+fn lamports(writer: &Writer, commitment: Commitment) -> Option<u64> {
+    let account = writer.store().get(&KEY, commitment).unwrap();
+    account.map(|a| a.lamports)
+}
+
+#[test]
+fn account_lifecycle() {
+    let mut writer = writer();
+    writer.account(account(10, 1)).unwrap();
+    writer.slot(slot(10, 9, Commitment::Processed)).unwrap();
+    assert_eq!(lamports(&writer, Commitment::Processed), Some(1));
+    assert_eq!(lamports(&writer, Commitment::Confirmed), None);
+
+    writer.slot(slot(10, 9, Commitment::Confirmed)).unwrap();
+    assert_eq!(lamports(&writer, Commitment::Confirmed), Some(1));
+    assert_eq!(lamports(&writer, Commitment::Finalized), None);
+
+    writer.slot(slot(10, 9, Commitment::Finalized)).unwrap();
+    assert_eq!(lamports(&writer, Commitment::Finalized), Some(1));
+
+    let store = writer.store();
+    assert_eq!(store.slot(Commitment::Processed).unwrap(), Some(10));
+    assert_eq!(store.slot(Commitment::Confirmed).unwrap(), Some(10));
+    assert_eq!(store.slot(Commitment::Finalized).unwrap(), Some(10));
+    let version = store.history().as_of(&KEY, 10).unwrap();
+    assert_eq!(version.map(|a| a.lamports), Some(1));
+    assert!(store.history().as_of(&KEY, 9).unwrap().is_none());
+}
+
+#[test]
+fn pruned_fork() {
+    let mut writer = writer();
+    writer.slot(slot(10, 9, Commitment::Finalized)).unwrap();
+    // two rival forks, both descending from rooted slot
+    writer.slot(slot(11, 10, Commitment::Processed)).unwrap();
+    writer.slot(slot(12, 10, Commitment::Processed)).unwrap();
+    writer.account(account(11, 1)).unwrap();
+    writer.account(account(12, 2)).unwrap();
+    assert_eq!(lamports(&writer, Commitment::Processed), Some(2));
+
+    writer.slot(slot(11, 10, Commitment::Finalized)).unwrap();
+    // update from pruned fork never makes it to higher commitment levels
+    assert_eq!(lamports(&writer, Commitment::Confirmed), Some(1));
+    assert_eq!(lamports(&writer, Commitment::Finalized), Some(1));
+    let versions = writer.store().history().between(&KEY, 0..=12).unwrap();
+    assert_eq!(versions.len(), 1);
+}
+
+#[test]
+fn latest_update_in_slot() {
+    let mut writer = writer();
+    writer.slot(slot(10, 9, Commitment::Finalized)).unwrap();
+    writer.account(account(11, 1)).unwrap();
+    writer.account(account(11, 2)).unwrap();
+    writer.slot(slot(11, 10, Commitment::Finalized)).unwrap();
+    assert_eq!(lamports(&writer, Commitment::Finalized), Some(2));
+}
+
+#[test]
+fn stale_write() {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    let store = Store::new(db).unwrap();
+    assert!(store.put(&account(11, 1), Commitment::Finalized).unwrap());
+    assert!(!store.put(&account(10, 2), Commitment::Finalized).unwrap());
+    let stored = store.get(&KEY, Commitment::Finalized).unwrap().unwrap();
+    assert_eq!(stored.lamports, 1);
+}
@@ -0,0 +1,81 @@
+use std::collections::{BTreeMap, HashMap};
+
+use pubsub_wire::{AccountData, SlotData};
+
+use crate::{slotree::SlotTree, store::Store, Commitment, Error, Pubkey, Slot};
+
+/// Applies account and slot updates from pubsub to store. Every
+/// account update is saved as processed right away, and is kept in
+/// memory until its slot is confirmed or rooted, to be saved at those
+/// commitment levels as well, or until its slot is pruned from forks
+pub struct Writer {
+    store: Store,
+    /// Accounts, whose slots haven't been rooted yet,
+    /// only the latest update in slot is kept per account
+    pending: BTreeMap<Slot, HashMap<Pubkey, AccountData>>,
+    /// Tracking of slot to parent relations, to find out,
+    /// which slots get rooted, and which get pruned
+    slots: SlotTree,
+}
+
+impl Writer {
+    /// Create writer, which saves updates to given store
+    pub fn new(store: Store) -> Self {
+        Self {
+            store,
+            pending: BTreeMap::default(),
+            slots: SlotTree::new(),
+        }
+    }
+
+    /// Store, to which updates are saved
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// Handle account update, which is always produced at processed slot
+    pub fn account(&mut self, account: AccountData) -> Result<(), Error> {
+        self.store.put(&account, Commitment::Processed)?;
+        if account.slot > self.slots.current_root() {
+            self.pending
+                .entry(account.slot)
+                .or_default()
+                .insert(account.pubkey, account);
+        }
+        Ok(())
+    }
+
+    /// Handle slot status update, and save pending accounts of
+    /// slot, when it reaches higher commitment level
+    pub fn slot(&mut self, update: SlotData) -> Result<(), Error> {
+        self.store.set_slot(update.slot, update.status)?;
+        if let Commitment::Confirmed = update.status {
+            let accounts = self.pending.get(&update.slot).into_iter().flatten();
+            for (_, account) in accounts {
+                self.store.put(account, Commitment::Confirmed)?;
+            }
+        }
+        let rooted_or_pruned = self.slots.push(update);
+
+        // if slot status wasn't rooted, none of the code below will be executed
+        let rooted = rooted_or_pruned.is_some();
+        for slot in rooted_or_pruned.into_iter().flatten() {
+            let accounts = self.pending.remove(&slot).into_iter().flatten();
+            if slot.rooted() {
+                // rooted slots are also confirmed, even if that update was missed
+                for (_, account) in accounts {
+                    self.store.put(&account, Commitment::Confirmed)?;
+                    self.store.put(&account, Commitment::Finalized)?;
+                    self.store.history().record(&account)?;
+                }
+            } // else slot has been pruned, so we just drop related accounts
+        }
+        // remove dead slots: which weren't rooted or pruned
+        let root = self.slots.current_root();
+        self.pending = self.pending.split_off(&root);
+        if rooted {
+            self.store.history().prune(root)?;
+        }
+        Ok(())
+    }
+}
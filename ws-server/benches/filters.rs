@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use ws_server::index::FilterIndex;
+use ws_server::subscription::Filter;
+
+const TOKEN_ACCOUNT_LEN: usize = 165;
+/// Offset of owner public key in SPL Token account
+const OWNER_OFFSET: usize = 32;
+
+/// Filters of typical token accounts subscription: by owner and by account size
+fn filters(owner: u64) -> Vec<Filter> {
+    let mut bytes = vec![0; 32];
+    bytes[..8].copy_from_slice(&owner.to_le_bytes());
+    vec![
+        Filter::DataSize(TOKEN_ACCOUNT_LEN as u64),
+        Filter::Memcmp {
+            offset: OWNER_OFFSET,
+            bytes,
+        },
+    ]
+}
+
+fn account(owner: u64) -> Vec<u8> {
+    let mut data = vec![0; TOKEN_ACCOUNT_LEN];
+    data[OWNER_OFFSET..OWNER_OFFSET + 8].copy_from_slice(&owner.to_le_bytes());
+    data
+}
+
+fn dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dispatch");
+    for subscribers in [100_u64, 1_000, 10_000] {
+        let subscriptions: HashMap<u64, Vec<Filter>> =
+            (0..subscribers).map(|id| (id, filters(id))).collect();
+        let mut index = FilterIndex::default();
+        for (id, filters) in &subscriptions {
+            index.insert(*id, filters);
+        }
+        let data = account(subscribers / 2);
+
+        group.bench_with_input(BenchmarkId::new("scan", subscribers), &data, |b, data| {
+            b.iter(|| {
+                subscriptions
+                    .iter()
+                    .filter(|(_, filters)| filters.iter().all(|f| f.matches(data)))
+                    .count()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("index", subscribers), &data, |b, data| {
+            b.iter(|| {
+                index
+                    .candidates(black_box(data))
+                    .filter(|id| subscriptions[id].iter().all(|f| f.matches(data)))
+                    .count()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
@@ -2,11 +2,15 @@ use std::collections::BTreeMap;
 
 use actix::{Actor, Addr, Arbiter, Context, Handler, Supervised, Supervisor};
 
+use storage::slotree::SlotTree;
+
 use crate::{
     manager::SubscriptionsRouter,
-    message::{PubSubAccount, SlotUpdatedMessage, TrackAccount},
-    slotree::SlotTree,
-    Slot,
+    message::{
+        PubSubAccount, RootUpdatedMessage, SlotUpdatedMessage, SlotsUpdateKind,
+        SlotsUpdatedMessage, TrackAccount,
+    },
+    Commitment, Slot,
 };
 
 /// Type for buffering the non-finalized accounts, for which
@@ -61,6 +65,16 @@ impl Handler<SlotUpdatedMessage> for Buffer {
     type Result = ();
 
     fn handle(&mut self, update: SlotUpdatedMessage, _: &mut Self::Context) -> Self::Result {
+        // roots are only reported, once the tree has accepted them
+        let kind = match update.status {
+            Commitment::Processed => Some(SlotsUpdateKind::Completed),
+            Commitment::Confirmed => Some(SlotsUpdateKind::OptimisticConfirmation),
+            Commitment::Finalized => None,
+        };
+        if let Some(kind) = kind {
+            self.router
+                .do_send(SlotsUpdatedMessage::new(update.slot, kind));
+        }
         if update.status.confirmed() {
             let accounts = self.accounts.get_mut(&update.slot).into_iter().flatten();
             for mut acc in accounts {
@@ -68,17 +82,27 @@ impl Handler<SlotUpdatedMessage> for Buffer {
                 self.router.do_send(acc.clone());
             }
         }
-        let rooted_or_pruned = self.slots.push(update);
+        let mut rooted_or_pruned = self.slots.push(update).unwrap_or_default();
+        // rooted slots come from the newest to the oldest one, while
+        // subscribers should observe roots in the order they were made
+        rooted_or_pruned.sort_by_key(|slot| (!slot.rooted(), **slot));
 
         // if slot status wasn't rooted, none of the code below will be executed
-        for slot in rooted_or_pruned.into_iter().flatten() {
+        for slot in rooted_or_pruned {
             let accounts = self.accounts.remove(&slot).into_iter().flatten();
             if slot.rooted() {
+                self.router.do_send(RootUpdatedMessage(*slot));
+                let root = SlotsUpdatedMessage::new(*slot, SlotsUpdateKind::Root);
+                self.router.do_send(root);
                 for mut acc in accounts {
                     acc.slot_status = 3; // finalized slot
                     self.router.do_send(acc);
                 }
-            } // else slot has been pruned, so we just drop related accounts
+            } else {
+                // slot has been pruned, so we just drop related accounts
+                let dead = SlotsUpdatedMessage::new(*slot, SlotsUpdateKind::Dead);
+                self.router.do_send(dead);
+            }
         }
         // remove dead slots: which weren't rooted or pruned
         let root = self.slots.current_root();
@@ -1,5 +1,7 @@
 use structopt::StructOpt;
 
+use crate::listener::StreamSource;
+
 /// Command line options, which can be supplied during application start
 #[derive(StructOpt)]
 #[structopt(about = "Solana websocket server")]
@@ -26,6 +28,66 @@ pub struct CliOptions {
         about = "list of addresses, where nsq lookup daemons can be queried, e.g. http://127.0.0.1:4161"
     )]
     pub nsqlookup: Vec<String>,
+    /// List of nsqd TCP addresses to consume from directly, bypassing lookup daemons, e.g. 127.0.0.1:4150
+    #[structopt(
+        long,
+        multiple = true,
+        about = "list of nsqd TCP addresses to consume from directly, bypassing lookup daemons, e.g. 127.0.0.1:4150"
+    )]
+    pub nsqd: Vec<String>,
+    /// NSQ topic, to which account updates are published
+    #[structopt(
+        long = "account-topic",
+        about = "NSQ topic, to which account updates are published",
+        default_value = "accounts"
+    )]
+    pub account_topic: String,
+    /// NSQ channel, to consume account updates from
+    #[structopt(
+        long = "account-channel",
+        about = "NSQ channel, to consume account updates from",
+        default_value = "accounts"
+    )]
+    pub account_channel: String,
+    /// NSQ topic, to which slot updates are published
+    #[structopt(
+        long = "slot-topic",
+        about = "NSQ topic, to which slot updates are published",
+        default_value = "slots"
+    )]
+    pub slot_topic: String,
+    /// NSQ channel, to consume slot updates from
+    #[structopt(
+        long = "slot-channel",
+        about = "NSQ channel, to consume slot updates from",
+        default_value = "slots"
+    )]
+    pub slot_channel: String,
+    /// Whether to consume from ephemeral channels, unique to this server instance
+    #[structopt(
+        long,
+        about = "consume from ephemeral channels, unique to this server instance, so that every replica receives full stream of updates"
+    )]
+    pub ephemeral: bool,
+    /// Stream server of validator plugin, to consume updates from instead of NSQ
+    #[structopt(
+        long,
+        about = "stream server of validator plugin, to consume updates from instead of NSQ, e.g. tcp://127.0.0.1:9100 or unix:///tmp/pubsub.sock"
+    )]
+    pub source: Option<StreamSource>,
+    /// Path to directory of storage database, to answer JSON-RPC requests from
+    #[structopt(
+        long,
+        about = "path to directory of storage database, to persist account states to and answer JSON-RPC requests from, requests are rejected if not set"
+    )]
+    pub db: Option<String>,
+    /// Number of slots before the latest root, to keep history of account states for
+    #[structopt(
+        long,
+        about = "number of slots before the latest root, to keep history of account states for",
+        default_value = "216000"
+    )]
+    pub retention: u64,
     /// Address, to which server should bind
     #[structopt(
         short = "l",
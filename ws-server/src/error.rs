@@ -16,8 +16,14 @@ pub struct SubError<'a> {
 pub enum SubErrorKind {
     /// Subscription request couldn't be deserialized properly
     ParseError = -32700,
+    /// Request isn't a valid JSON-RPC request object
+    InvalidRequest = -32600,
+    /// Requested method doesn't exist, or isn't available
+    MethodNotFound = -32601,
     /// Subscription request contained parameter, which wasn't expected
     InvalidParams = -32602,
+    /// Request couldn't be handled due to internal error of server
+    InternalError = -32603,
 }
 
 impl<'a, T: serde::de::Error> From<T> for SubError<'a> {
@@ -33,7 +39,7 @@ impl<'a> std::error::Error for SubError<'a> {}
 
 impl<'a> Display for SubError<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self)
+        write!(f, "{}", self.message)
     }
 }
 
@@ -0,0 +1,124 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::{subscription::Filter, Pubkey, KEY_LEN};
+
+/// Index of subscriptions by their filters, which allows to find
+/// subscriptions, interested in account update, without checking
+/// filters of every subscription one by one. Every key is placed
+/// into single bucket, chosen by the most selective of its filters:
+/// memcmp of public key at fixed offset, then data size, otherwise
+/// key goes to the list of keys, which are always checked
+pub struct FilterIndex<K> {
+    /// Keys with memcmp filter of public key length, by offset and compared bytes
+    memcmp: HashMap<usize, HashMap<Pubkey, HashSet<K>>>,
+    /// Keys with data size filter, by data size
+    data_size: HashMap<u64, HashSet<K>>,
+    /// Keys, which don't have any filters, suitable for indexing
+    unindexed: HashSet<K>,
+}
+
+/// Bucket of index, to which key belongs, based on its filters
+enum Bucket {
+    Memcmp(usize, Pubkey),
+    DataSize(u64),
+    Unindexed,
+}
+
+impl<K> Default for FilterIndex<K> {
+    fn default() -> Self {
+        Self {
+            memcmp: HashMap::default(),
+            data_size: HashMap::default(),
+            unindexed: HashSet::default(),
+        }
+    }
+}
+
+impl<K: Hash + Eq> FilterIndex<K> {
+    /// Add key with given filters to index
+    pub fn insert(&mut self, key: K, filters: &[Filter]) {
+        match Bucket::from(filters) {
+            Bucket::Memcmp(offset, bytes) => self
+                .memcmp
+                .entry(offset)
+                .or_default()
+                .entry(bytes)
+                .or_default()
+                .insert(key),
+            Bucket::DataSize(size) => self.data_size.entry(size).or_default().insert(key),
+            Bucket::Unindexed => self.unindexed.insert(key),
+        };
+    }
+
+    /// Remove key from index, filters should be the same, that
+    /// the key has been inserted with
+    pub fn remove(&mut self, key: &K, filters: &[Filter]) {
+        match Bucket::from(filters) {
+            Bucket::Memcmp(offset, bytes) => {
+                if let Some(values) = self.memcmp.get_mut(&offset) {
+                    if let Some(keys) = values.get_mut(&bytes) {
+                        keys.remove(key);
+                        if keys.is_empty() {
+                            values.remove(&bytes);
+                        }
+                    }
+                    if values.is_empty() {
+                        self.memcmp.remove(&offset);
+                    }
+                }
+            }
+            Bucket::DataSize(size) => {
+                if let Some(keys) = self.data_size.get_mut(&size) {
+                    keys.remove(key);
+                    if keys.is_empty() {
+                        self.data_size.remove(&size);
+                    }
+                }
+            }
+            Bucket::Unindexed => {
+                self.unindexed.remove(key);
+            }
+        }
+    }
+
+    /// Find all the keys, which might be interested in account with
+    /// given data. Only the filter, used for indexing, is guaranteed to
+    /// match, the rest of key's filters should still be checked by caller
+    pub fn candidates<'a>(&'a self, data: &'a [u8]) -> impl Iterator<Item = &'a K> + 'a {
+        let memcmp = self.memcmp.iter().filter_map(move |(&offset, values)| {
+            let bytes = data.get(offset..offset.checked_add(KEY_LEN)?)?;
+            values.get(bytes)
+        });
+        let data_size = self.data_size.get(&(data.len() as u64));
+        memcmp
+            .chain(data_size)
+            .flatten()
+            .chain(self.unindexed.iter())
+    }
+
+    /// Whether there are no keys in index
+    pub fn is_empty(&self) -> bool {
+        self.memcmp.is_empty() && self.data_size.is_empty() && self.unindexed.is_empty()
+    }
+}
+
+impl From<&[Filter]> for Bucket {
+    fn from(filters: &[Filter]) -> Self {
+        let memcmp = filters.iter().find_map(|f| match f {
+            Filter::Memcmp { offset, bytes } if bytes.len() == KEY_LEN => {
+                let mut key = [0; KEY_LEN];
+                key.copy_from_slice(bytes);
+                Some(Self::Memcmp(*offset, key))
+            }
+            _ => None,
+        });
+        let data_size = || {
+            filters.iter().find_map(|f| match f {
+                Filter::DataSize(size) => Some(Self::DataSize(*size)),
+                _ => None,
+            })
+        };
+        memcmp.or_else(data_size).unwrap_or(Self::Unindexed)
+    }
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value as JsonValue};
+
+use crate::{
+    error::{SubError, SubErrorKind},
+    subscription::{SubResponse, SubResponseError},
+    JSONRPC,
+};
+
+/// Identifier of request, which is echoed back in response to it
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+#[cfg_attr(test, derive(Debug))]
+pub enum Id {
+    /// Numeric identifier, the most common one
+    Number(Number),
+    /// String identifier
+    String(String),
+    /// Explicitly null identifier, which is also used in responses to
+    /// requests, which were so malformed, that their id couldn't be found
+    Null,
+}
+
+/// Method call, extracted from JSON-RPC request
+pub struct Call {
+    /// Name of requested method
+    pub method: String,
+    /// Parameters of request, null if they weren't supplied
+    pub params: JsonValue,
+}
+
+/// Response to single request, either successful or not
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Response<'a, T> {
+    Success(SubResponse<T>),
+    Failure(SubResponseError<'a>),
+}
+
+impl From<u64> for Id {
+    fn from(id: u64) -> Self {
+        Self::Number(id.into())
+    }
+}
+
+/// Handle JSON-RPC message, which is either a single request, or a batch of
+/// them, by calling `handler` for every well formed request. Returns rendered
+/// response, unless there's nothing to respond with, as message consisted of
+/// notifications only, i.e. requests without id. The same handling is used
+/// for messages, sent over websocket connections, and for HTTP requests
+pub fn handle<T, F>(message: &[u8], mut handler: F) -> Option<String>
+where
+    T: Serialize,
+    F: FnMut(Call) -> Result<T, SubError<'static>>,
+{
+    let message: JsonValue = match serde_json::from_slice(message) {
+        Ok(message) => message,
+        Err(e) => {
+            println!("Invalid JSON-RPC message, cannot deserialize: {}", e);
+            let error = SubError::new("Parse error".into(), SubErrorKind::ParseError);
+            let response = Response::<T>::Failure(SubResponseError::new(Id::Null, error));
+            return Some(serde_json::to_string(&response).unwrap());
+        }
+    };
+    match message {
+        JsonValue::Array(batch) if batch.is_empty() => {
+            let response = invalid::<T>(Id::Null, "empty batch");
+            Some(serde_json::to_string(&response).unwrap())
+        }
+        // requests in batch are handled in order, though
+        // the specification doesn't require it to be so
+        JsonValue::Array(batch) => {
+            let responses: Vec<_> = batch
+                .into_iter()
+                .filter_map(|request| respond(request, &mut handler))
+                .collect();
+            if responses.is_empty() {
+                return None;
+            }
+            Some(serde_json::to_string(&responses).unwrap())
+        }
+        request => {
+            let response = respond(request, &mut handler)?;
+            Some(serde_json::to_string(&response).unwrap())
+        }
+    }
+}
+
+/// Validate single request and pass it to handler, returns
+/// response to request, unless it was a notification
+fn respond<T, F>(request: JsonValue, handler: &mut F) -> Option<Response<'static, T>>
+where
+    F: FnMut(Call) -> Result<T, SubError<'static>>,
+{
+    let mut request = match request {
+        JsonValue::Object(request) => request,
+        _ => return Some(invalid(Id::Null, "request should be an object")),
+    };
+    // absent id means, that request is a notification, while null id is still an id
+    let id = match request.remove("id").map(Id::deserialize) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(_)) => return Some(invalid(Id::Null, "id should be a number or a string")),
+        None => None,
+    };
+    if request.get("jsonrpc").and_then(JsonValue::as_str) != Some(JSONRPC) {
+        let reason = "jsonrpc version should be 2.0";
+        return Some(invalid(id.unwrap_or(Id::Null), reason));
+    }
+    let method = match request.remove("method") {
+        Some(JsonValue::String(method)) => method,
+        _ => return Some(invalid(id.unwrap_or(Id::Null), "method should be a string")),
+    };
+    let params = request.remove("params").unwrap_or(JsonValue::Null);
+
+    let result = handler(Call { method, params });
+    let response = match result {
+        Ok(result) => Response::Success(SubResponse::new(id?, result)),
+        Err(error) => Response::Failure(SubResponseError::new(id?, error)),
+    };
+    Some(response)
+}
+
+/// Render response to invalid request, the reason is only logged, as
+/// clients expect the same message as from any other JSON-RPC server
+fn invalid<T>(id: Id, reason: &str) -> Response<'static, T> {
+    println!("Invalid JSON-RPC request: {}", reason);
+    let error = SubError::new("Invalid request".into(), SubErrorKind::InvalidRequest);
+    Response::Failure(SubResponseError::new(id, error))
+}
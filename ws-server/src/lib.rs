@@ -1,9 +1,11 @@
 #![deny(missing_docs)]
 //! Websocket server for solana network
 use std::hash::Hash;
+use std::sync::Arc;
 
 use message::PubSubAccountWithSubKind;
 use serde::Deserialize;
+use subscription::{DataSlice, Encoding, Filter};
 
 /// Handling of temporarily buffered, not yet finalized accounts
 pub mod buffer;
@@ -11,7 +13,11 @@ pub mod buffer;
 pub mod cli;
 /// Collection of application specific errors
 pub mod error;
-/// Handling of message consumption from NSQ pubsub
+/// Index of program subscriptions by their filters
+pub mod index;
+/// JSON-RPC 2.0 protocol layer: single and batch requests, ids and error responses
+pub mod jsonrpc;
+/// Handling of message consumption from NSQ pubsub or plugin stream server
 pub mod listener;
 /// Subscription manager and subscription router to distribute work
 /// among several subscription managers
@@ -22,13 +28,19 @@ pub mod message;
 mod metrics;
 /// Update notifications sent to subscribed clients
 pub mod notification;
+/// Parsers of account data into JSON, for known owner programs
+pub mod parser;
+/// JSON-RPC requests over HTTP, answered from storage
+pub mod rpc;
+/// Detection of lost, duplicated and stale updates, received from plugin
+pub mod sequence;
 /// Main entry point to run http server to accept websocket connections
 pub mod server;
 /// Handling of websocket session and keeping track of subscriptions
 /// for this particular session
 pub mod session;
-/// Data structure to keep track of slot updates
-mod slotree;
+/// Persistence of account and slot updates to storage
+pub mod store;
 /// Subscription requests sent from client to server via established
 /// websocket connection
 pub mod subscription;
@@ -42,7 +54,8 @@ const KEY_LEN: usize = 32;
 
 /// Type of subscription, can be either for single account or for all
 /// accounts which are owned by specified program
-#[derive(PartialEq, Eq, Hash, Clone)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(test, derive(Debug))]
 pub enum SubscriptionKind {
     /// Subscription is for account
     Account,
@@ -50,6 +63,18 @@ pub enum SubscriptionKind {
     Program,
 }
 
+/// Type of subscription, which is related to slots, rather than accounts
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(test, derive(Debug))]
+pub enum SlotSubscriptionKind {
+    /// Subscription is for slot updates
+    Slot,
+    /// Subscription is for rooted slots
+    Root,
+    /// Subscription is for all transitions of slots
+    SlotsUpdates,
+}
+
 /// Commitment level, indicates how storngly, a particular record,
 /// is accepted by solana cluster  
 #[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
@@ -80,6 +105,29 @@ pub struct SubKey {
     key: Pubkey,
     commitment: Commitment,
     kind: SubscriptionKind,
+    encoding: Encoding,
+    slice: Option<DataSlice>,
+    filters: Arc<[Filter]>,
+}
+
+/// A key to uniquely identify a given slot related subscription by client
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(test, derive(Debug))]
+pub struct SlotSubKey {
+    kind: SlotSubscriptionKind,
+    /// Commitment level, which slots should reach, in order for
+    /// notification to be sent to client, any level if not set
+    commitment: Option<Commitment>,
+}
+
+/// Part of subscription key, which is used to match account
+/// updates against subscriptions, and to route both of them
+/// to the same subscription manager
+#[derive(PartialEq, Eq, Hash, Clone)]
+pub struct RouteKey {
+    key: Pubkey,
+    commitment: Commitment,
+    kind: SubscriptionKind,
 }
 
 impl Default for Commitment {
@@ -98,6 +146,26 @@ impl From<u8> for Commitment {
     }
 }
 
+impl From<pubsub_wire::Commitment> for Commitment {
+    fn from(commitment: pubsub_wire::Commitment) -> Self {
+        match commitment {
+            pubsub_wire::Commitment::Processed => Commitment::Processed,
+            pubsub_wire::Commitment::Confirmed => Commitment::Confirmed,
+            pubsub_wire::Commitment::Finalized => Commitment::Finalized,
+        }
+    }
+}
+
+impl From<Commitment> for pubsub_wire::Commitment {
+    fn from(commitment: Commitment) -> Self {
+        match commitment {
+            Commitment::Processed => pubsub_wire::Commitment::Processed,
+            Commitment::Confirmed => pubsub_wire::Commitment::Confirmed,
+            Commitment::Finalized => pubsub_wire::Commitment::Finalized,
+        }
+    }
+}
+
 impl Commitment {
     /// Whether the given commitment has been confirmed by supermajority
     pub fn confirmed(&self) -> bool {
@@ -106,7 +174,40 @@ impl Commitment {
 }
 
 impl SubKey {
-    /// Create a default subscription key, with given public key
+    /// Check whether account data passes all the filters of subscription
+    #[inline]
+    pub fn matches(&self, data: &[u8]) -> bool {
+        self.filters.iter().all(|f| f.matches(data))
+    }
+
+    /// Get the part of subscription key, which is used for routing
+    #[inline]
+    pub fn route(&self) -> RouteKey {
+        RouteKey {
+            key: self.key,
+            commitment: self.commitment,
+            kind: self.kind,
+        }
+    }
+}
+
+impl SlotSubKey {
+    /// Create key for slot related subscription, with optional commitment filter
+    #[inline]
+    pub fn new(kind: SlotSubscriptionKind, commitment: Option<Commitment>) -> Self {
+        Self { kind, commitment }
+    }
+
+    /// Check whether slot, which has reached given commitment level
+    /// (if any), passes the commitment filter of subscription
+    #[inline]
+    pub fn matches(&self, commitment: Option<Commitment>) -> bool {
+        self.commitment.is_none() || self.commitment == commitment
+    }
+}
+
+impl RouteKey {
+    /// Create a default routing key, with given public key
     #[inline]
     pub fn new(key: Pubkey) -> Self {
         Self {
@@ -115,14 +216,14 @@ impl SubKey {
             kind: SubscriptionKind::Account,
         }
     }
-    /// Builder like method to change commitment level of subscription key
+    /// Builder like method to change commitment level of routing key
     #[inline]
     pub fn commitment(mut self, slot_status: u8) -> Self {
         self.commitment = slot_status.into();
         self
     }
 
-    /// Builder like method to change subscription type of subscription key
+    /// Builder like method to change subscription type of routing key
     #[inline]
     pub fn kind(mut self, kind: SubscriptionKind) -> Self {
         self.kind = kind;
@@ -130,15 +231,15 @@ impl SubKey {
     }
 }
 
-impl From<&PubSubAccountWithSubKind> for SubKey {
+impl From<&PubSubAccountWithSubKind> for RouteKey {
     fn from(acc: &PubSubAccountWithSubKind) -> Self {
         let pubkey = match acc.kind {
             SubscriptionKind::Account => acc.account.pubkey,
             SubscriptionKind::Program => acc.account.owner,
         };
-        SubKey::new(pubkey)
+        RouteKey::new(pubkey)
             .commitment(acc.account.slot_status)
-            .kind(acc.kind.clone())
+            .kind(acc.kind)
     }
 }
 
@@ -1,149 +1,440 @@
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
+use std::convert::TryFrom;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use actix::{Actor, Addr, Arbiter, AsyncContext, Context, StreamHandler, Supervised, Supervisor};
-use futures::stream;
-use rmp_serde as rmps;
+use futures::stream::{self, BoxStream, StreamExt};
+use pubsub_wire::{AccountData, Kind, SlotData, Stamp, Stamped, FRAME_HEADER_LEN, MAX_FRAME_LEN};
+use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
+use tokio::net::{TcpStream, UnixStream};
 use tokio_nsq::*;
 
-use crate::message::PubSubAccount;
+use crate::cli::CliOptions;
+use crate::message::{PubSubAccount, StoreAccount, StoreSlot};
+use crate::parser;
+use crate::sequence::{LatestWrites, Sequence, SequenceTracker};
+use crate::store::StoreWriter;
 use crate::{manager::SubscriptionsRouter, message::SlotUpdatedMessage};
 use crate::{Slot, METRICS};
 
-/// Actor, which is responsible for listening to the NSQ messages,
+/// Delay before reconnecting to stream source, after connection has been lost
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Actor, which is responsible for listening to the updates from source,
 /// and forward them to subscription managers, after deserialization
 pub struct PubSubListner {
     /// Router, that distributes messages between `SubscriptionManager`s
     router: Addr<SubscriptionsRouter>,
-    /// List of web addresses, which can be used to locate NSQ lookup deamons in network
-    nsqlookupd: HashSet<String>,
+    /// Writer, which persists updates to storage, if it's enabled
+    writer: Option<Addr<StoreWriter>>,
+    /// Where account and slot updates are consumed from
+    source: Box<dyn Source>,
     /// Largest slot number, observed from pubsub
     max_slot: Slot,
+    /// Sequence numbers of account updates, received from plugin
+    accounts: SequenceTracker,
+    /// Sequence numbers of slot updates, received from plugin
+    slots: SequenceTracker,
+    /// Latest writes of accounts, to discard the stale ones
+    writes: LatestWrites,
+}
+
+/// Update, produced by validator plugin
+pub enum Update {
+    /// Account has been updated
+    Account(Stamped<AccountData>),
+    /// Status of slot has been updated
+    Slot(Stamped<SlotData>),
+}
+
+/// Source of updates, published by validator plugin, which
+/// should match transport, that plugin is configured with
+pub trait Source: Send {
+    /// Stream of updates, which is created anew every time listener
+    /// (re)starts, and which is expected to reconnect on its own
+    fn updates(&self) -> BoxStream<'static, Update>;
+}
+
+/// Configuration of NSQ consumers, used by listener
+#[derive(Clone)]
+pub struct ListenerConfig {
+    /// Topic and channel to consume account updates from
+    pub accounts: (String, String),
+    /// Topic and channel to consume slot updates from
+    pub slots: (String, String),
+    /// Where to look for NSQ daemons, publishing to configured topics
+    pub sources: NSQSources,
+}
+
+/// Locations of NSQ daemons, to consume messages from
+#[derive(Clone)]
+pub enum NSQSources {
+    /// List of web addresses of NSQ lookup daemons, like http://127.0.0.1:4161
+    Lookup(HashSet<String>),
+    /// List of TCP addresses of NSQ daemons, like 127.0.0.1:4150
+    Daemons(Vec<String>),
+}
+
+/// Stream server of validator plugin, to read framed updates from
+/// directly, without any broker in between
+#[derive(Clone, Debug)]
+pub enum StreamSource {
+    /// TCP address of server, like tcp://127.0.0.1:9100
+    Tcp(String),
+    /// Path to Unix domain socket of server, like unix:///tmp/pubsub.sock
+    Unix(PathBuf),
 }
 
 impl Actor for PubSubListner {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
-        // every time this actor is restarted, resubscribe to
-        // account and slot topics all over again
-        let pubsub_account_state =
-            PubSubState::new("accounts", "accounts", self.nsqlookupd.clone());
-        let pubsub_slot_state = PubSubState::new("slots", "slots", self.nsqlookupd.clone());
-        let pubsub_accounts_stream = stream::unfold(pubsub_account_state, pubsub_accounts_listen);
-        let pubsub_slot_stream = stream::unfold(pubsub_slot_state, pubsub_slots_listen);
-
-        // re-register streams
-        ctx.add_stream(pubsub_accounts_stream);
-        ctx.add_stream(pubsub_slot_stream);
-        println!("Subscribed to NSQ pubsub topics");
+        // every time this actor is restarted, resubscribe to source all over again
+        ctx.add_stream(self.source.updates());
     }
 }
 
 impl PubSubListner {
-    /// Create a new listener
-    pub fn new(router: Addr<SubscriptionsRouter>, nsqlookupd: HashSet<String>) -> Addr<Self> {
+    /// Create a new listener, which also forwards all the updates
+    /// to storage writer, if one is provided
+    pub fn new(
+        router: Addr<SubscriptionsRouter>,
+        writer: Option<Addr<StoreWriter>>,
+        source: Box<dyn Source>,
+    ) -> Addr<Self> {
         let listener = Self {
             router,
-            nsqlookupd,
+            writer,
+            source,
             max_slot: 0,
+            accounts: SequenceTracker::default(),
+            slots: SequenceTracker::default(),
+            writes: LatestWrites::default(),
         };
         let arbiter = Arbiter::new().handle();
         Supervisor::start_in_arbiter(&arbiter, |_| listener)
     }
 }
 
+impl From<&CliOptions> for ListenerConfig {
+    fn from(opts: &CliOptions) -> Self {
+        let mut account_channel = opts.account_channel.clone();
+        let mut slot_channel = opts.slot_channel.clone();
+        if opts.ephemeral {
+            // channel names should be unique to this instance, otherwise
+            // nsq would distribute messages between all the instances
+            let instance = instance_id();
+            account_channel = format!("{}-{}#ephemeral", account_channel, instance);
+            slot_channel = format!("{}-{}#ephemeral", slot_channel, instance);
+        }
+        let sources = if opts.nsqd.is_empty() {
+            NSQSources::Lookup(opts.nsqlookup.iter().cloned().collect())
+        } else {
+            NSQSources::Daemons(opts.nsqd.clone())
+        };
+        Self {
+            accounts: (opts.account_topic.clone(), account_channel),
+            slots: (opts.slot_topic.clone(), slot_channel),
+            sources,
+        }
+    }
+}
+
+/// Identifier of current server instance, which is unlikely
+/// to be shared with other running instances
+fn instance_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    format!("{:x}", nanos ^ std::process::id() as u64)
+}
+
 impl Supervised for PubSubListner {
     fn restarting(&mut self, _: &mut Self::Context) {
         println!("restarting pubsub listener");
     }
 }
 
-impl StreamHandler<PubSubAccount> for PubSubListner {
-    fn handle(&mut self, item: PubSubAccount, _: &mut Self::Context) {
-        METRICS.account_updates_count.inc();
-        self.router.do_send(item);
+impl StreamHandler<Update> for PubSubListner {
+    fn handle(&mut self, item: Update, _: &mut Self::Context) {
+        match item {
+            Update::Account(account) => self.account(account),
+            Update::Slot(slot) => self.slot(slot),
+        }
     }
 }
 
-impl StreamHandler<SlotUpdatedMessage> for PubSubListner {
-    fn handle(&mut self, item: SlotUpdatedMessage, _: &mut Self::Context) {
-        println!("Got slot");
+impl PubSubListner {
+    fn account(&mut self, item: Stamped<AccountData>) {
         METRICS.account_updates_count.inc();
+        if let Some(stamp) = item.stamp {
+            if !track(&mut self.accounts, stamp, "account") {
+                return;
+            }
+        }
+        let Stamped {
+            stamp,
+            payload: item,
+        } = item;
+        // updates for the same account might be reordered on their way from plugin,
+        // and the older ones shouldn't override the state, that clients have seen
+        if !self.writes.record(&item.pubkey, item.slot, stamp) {
+            METRICS.stale_account_updates.inc();
+            return;
+        }
+        parser::observe(&item.pubkey, &item.owner, &item.data);
+        if let Some(ref writer) = self.writer {
+            writer.do_send(StoreAccount(item.clone()));
+        }
+        self.router.do_send(PubSubAccount::from(item));
+    }
+
+    fn slot(&mut self, item: Stamped<SlotData>) {
+        println!("Got slot");
+        METRICS.slot_updates_count.inc();
+        if let Some(stamp) = item.stamp {
+            if !track(&mut self.slots, stamp, "slot") {
+                return;
+            }
+        }
+        let item = item.payload;
 
         self.max_slot = self.max_slot.max(item.slot);
         METRICS.slot.set(self.max_slot as i64);
-        self.router.do_send(item);
+        self.writes.prune(self.max_slot);
+        if let Some(ref writer) = self.writer {
+            writer.do_send(StoreSlot(item));
+        }
+        self.router.do_send(SlotUpdatedMessage::from(item));
+    }
+}
+
+/// Check sequence number of update of given kind, counting the updates,
+/// which have been lost, returns false if update is a duplicate
+fn track(tracker: &mut SequenceTracker, stamp: Stamp, kind: &str) -> bool {
+    let (sequence, lost) = tracker.check(stamp);
+    if lost > 0 {
+        println!("lost {} {} updates from pubsub", lost, kind);
+        METRICS.lost_updates.with_label_values(&[kind]).inc_by(lost);
+    }
+    if sequence == Sequence::Duplicate {
+        METRICS.duplicate_updates.with_label_values(&[kind]).inc();
+        return false;
+    }
+    true
+}
+
+impl Source for ListenerConfig {
+    fn updates(&self) -> BoxStream<'static, Update> {
+        let (ref topic, ref channel) = self.accounts;
+        let pubsub_account_state = PubSubState::new(topic, channel, self.sources.clone());
+        let (ref topic, ref channel) = self.slots;
+        let pubsub_slot_state = PubSubState::new(topic, channel, self.sources.clone());
+        let pubsub_accounts_stream =
+            stream::unfold(pubsub_account_state, pubsub_accounts_listen).map(Update::Account);
+        let pubsub_slot_stream =
+            stream::unfold(pubsub_slot_state, pubsub_slots_listen).map(Update::Slot);
+        println!("Subscribed to NSQ pubsub topics");
+
+        stream::select(pubsub_accounts_stream, pubsub_slot_stream).boxed()
+    }
+}
+
+impl Source for StreamSource {
+    fn updates(&self) -> BoxStream<'static, Update> {
+        let state = StreamState {
+            source: self.clone(),
+            reader: None,
+            pending: VecDeque::new(),
+        };
+        stream::unfold(state, stream_listen).boxed()
+    }
+}
+
+impl FromStr for StreamSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(address) = s.strip_prefix("tcp://") {
+            Ok(Self::Tcp(address.into()))
+        } else if let Some(path) = s.strip_prefix("unix://") {
+            Ok(Self::Unix(path.into()))
+        } else {
+            Err(format!(
+                "unsupported stream source: {}, expected tcp://<address> or unix://<path>",
+                s
+            ))
+        }
     }
 }
 
 /// Async function, that should be used in stream generator,
 /// to produce new account updates
 pub async fn pubsub_accounts_listen(
-    mut state: PubSubState,
-) -> Option<(PubSubAccount, PubSubState)> {
+    mut state: PubSubState<Stamped<AccountData>>,
+) -> Option<(Stamped<AccountData>, PubSubState<Stamped<AccountData>>)> {
     loop {
+        // accounts, which were published in batch, are produced one by one
+        if let Some(account) = state.pending.pop_front() {
+            break Some((account, state));
+        }
         let message = state.consume().await?;
-        let account: PubSubAccount = match rmps::from_read(message.body.as_slice()) {
-            Ok(v) => v,
-            Err(e) => {
-                println!("failed to deserialize account data from pubsub: {}", e);
-                // notify nsq to remove message anyway, so we nsq doesn't requeue it
-                message.finish();
-                continue;
-            }
-        };
+        match pubsub_wire::unpack_stamped::<AccountData>(&message.body) {
+            Ok(accounts) => state.pending.extend(accounts),
+            Err(e) => println!("failed to deserialize account data from pubsub: {}", e),
+        }
+        // notify nsq to remove message anyway, so we nsq doesn't requeue it
         message.finish();
-
-        break Some((account, state));
     }
 }
 
 /// Async function, that should be used in stream generator,
 /// to produce new slot upadates
 pub async fn pubsub_slots_listen(
-    mut state: PubSubState,
-) -> Option<(SlotUpdatedMessage, PubSubState)> {
+    mut state: PubSubState<Stamped<SlotData>>,
+) -> Option<(Stamped<SlotData>, PubSubState<Stamped<SlotData>>)> {
     loop {
+        if let Some(slot) = state.pending.pop_front() {
+            break Some((slot, state));
+        }
         let message = state.consume().await?;
-        let slot: SlotUpdatedMessage = match rmps::from_read(message.body.as_slice()) {
-            Ok(v) => v,
-            Err(e) => {
-                println!("failed to deserialize slot data from pubsub: {}", e);
-                // notify nsq to remove message anyway, so we don't get it again
-                message.finish();
-                continue;
-            }
-        };
+        match pubsub_wire::unpack_stamped::<SlotData>(&message.body) {
+            Ok(slots) => state.pending.extend(slots),
+            Err(e) => println!("failed to deserialize slot data from pubsub: {}", e),
+        }
+        // notify nsq to remove message anyway, so we don't get it again
         message.finish();
-        break Some((slot, state));
     }
 }
 
-/// Wrapping type to hold consumer of NSQ messages
-pub struct PubSubState(NSQConsumer);
+/// Wrapping type to hold consumer of NSQ messages, along with
+/// the values, which have been unpacked from batch, but not produced yet
+pub struct PubSubState<T> {
+    consumer: NSQConsumer,
+    pending: VecDeque<T>,
+}
 
-impl PubSubState {
+impl<T> PubSubState<T> {
     /// Create new instance of NSQ Consumer.
     /// Arguments:
     /// * `topic`: NSQ topic to subscribe to
     /// * `channel`: NSQ channel to join, after topic subscription
-    /// * `lookup`: list of NSQ lookup daemon addresses, like http://127.0.0.1:4161
-    pub fn new<T: Into<String>>(topic: T, channel: T, lookup: HashSet<String>) -> Self {
-        let topic = NSQTopic::new(topic).unwrap();
-        let channel = NSQChannel::new(channel).unwrap();
+    /// * `sources`: NSQ lookup daemons or NSQ daemons to consume from
+    pub fn new<S: Into<String>>(topic: S, channel: S, sources: NSQSources) -> Self {
+        let topic = NSQTopic::new(topic).expect("invalid NSQ topic name");
+        let channel = NSQChannel::new(channel).expect("invalid NSQ channel name");
 
-        let consumer = NSQConsumerConfig::new(topic, channel)
-            .set_sources(NSQConsumerConfigSources::Lookup(
+        let sources = match sources {
+            NSQSources::Lookup(lookup) => NSQConsumerConfigSources::Lookup(
                 NSQConsumerLookupConfig::new().set_addresses(lookup),
-            ))
+            ),
+            NSQSources::Daemons(daemons) => NSQConsumerConfigSources::Daemons(daemons),
+        };
+        let consumer = NSQConsumerConfig::new(topic, channel)
+            .set_sources(sources)
             .build();
 
-        Self(consumer)
+        Self {
+            consumer,
+            pending: VecDeque::new(),
+        }
     }
 
     /// Wrapper method to consume next nsq message, ignoring other nsq events
     #[inline]
     async fn consume(&mut self) -> Option<NSQMessage> {
-        self.0.consume_filtered().await
+        self.consumer.consume_filtered().await
+    }
+}
+
+/// State of connection to stream source
+pub struct StreamState {
+    source: StreamSource,
+    reader: Option<BufReader<Box<dyn AsyncRead + Unpin + Send>>>,
+    /// Updates, which have been unpacked from batch, but not produced yet
+    pending: VecDeque<Update>,
+}
+
+/// Async function, that should be used in stream generator, to produce
+/// updates read from stream source, reconnecting to it if necessary
+pub async fn stream_listen(mut state: StreamState) -> Option<(Update, StreamState)> {
+    loop {
+        if let Some(update) = state.pending.pop_front() {
+            break Some((update, state));
+        }
+        let reader = match state.reader {
+            Some(ref mut reader) => reader,
+            None => match state.source.connect().await {
+                Ok(stream) => {
+                    println!("Connected to stream source {:?}", state.source);
+                    state.reader.insert(BufReader::new(stream))
+                }
+                Err(e) => {
+                    println!("failed to connect to stream source: {}", e);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                    continue;
+                }
+            },
+        };
+        let (kind, message) = match read_frame(reader).await {
+            Ok(frame) => frame,
+            Err(e) => {
+                println!("lost connection to stream source: {}", e);
+                state.reader = None;
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+        let pending = &mut state.pending;
+        let result = match kind {
+            Kind::Account => pubsub_wire::unpack_stamped(&message)
+                .map(|accounts| pending.extend(accounts.into_iter().map(Update::Account))),
+            Kind::Slot => pubsub_wire::unpack_stamped(&message)
+                .map(|slots| pending.extend(slots.into_iter().map(Update::Slot))),
+            // startup snapshots are not consumed by server
+            Kind::Snapshot => Ok(()),
+        };
+        if let Err(e) = result {
+            println!(
+                "failed to deserialize {:?} message from stream: {}",
+                kind, e
+            );
+        }
+    }
+}
+
+impl StreamSource {
+    async fn connect(&self) -> io::Result<Box<dyn AsyncRead + Unpin + Send>> {
+        match self {
+            Self::Tcp(address) => {
+                let stream = TcpStream::connect(address).await?;
+                stream.set_nodelay(true)?;
+                Ok(Box::new(stream))
+            }
+            Self::Unix(path) => Ok(Box::new(UnixStream::connect(path).await?)),
+        }
+    }
+}
+
+/// Read single frame from stream, returning kind of message and message itself
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<(Kind, Vec<u8>)> {
+    let mut header = [0; FRAME_HEADER_LEN];
+    reader.read_exact(&mut header).await?;
+    let len = u32::from_be_bytes(header) as usize;
+    if len == 0 || len > MAX_FRAME_LEN {
+        let msg = format!("invalid length of frame: {}", len);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
     }
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame).await?;
+    let message = frame.split_off(1);
+    // frames of unknown kinds can't be skipped reliably either,
+    // as the stream is probably produced by incompatible plugin
+    let kind = Kind::try_from(frame[0])
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    Ok((kind, message))
 }
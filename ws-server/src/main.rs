@@ -1,10 +1,14 @@
+use storage::store::Store;
+use storage::writer::Writer;
 use structopt::StructOpt;
 use ws_server::buffer::Buffer;
 use ws_server::cli::CliOptions;
-use ws_server::listener::PubSubListner;
+use ws_server::listener::{ListenerConfig, PubSubListner, Source};
 use ws_server::manager::SubscriptionsRouter;
 use ws_server::message::SetBufferManager;
+use ws_server::parser;
 use ws_server::server::{Server, ServerState};
+use ws_server::store::StoreWriter;
 
 #[actix::main]
 async fn main() -> std::io::Result<()> {
@@ -12,15 +16,34 @@ async fn main() -> std::io::Result<()> {
     let cores = num_cpus::get();
     let workers = opts.worker_count.unwrap_or(cores / 2);
     let managers = opts.manager_count.unwrap_or(cores / 2 - 2);
+    let source: Box<dyn Source> = match opts.source {
+        Some(ref source) => Box::new(source.clone()),
+        None => Box::new(ListenerConfig::from(&opts)),
+    };
+
+    let store = match opts.db {
+        Some(ref path) => {
+            let store =
+                Store::open(path).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            Some(store.retention(opts.retention))
+        }
+        None => None,
+    };
 
     let router = SubscriptionsRouter::new(managers);
 
-    let state = ServerState::new(router.clone());
+    let mut state = ServerState::new(router.clone());
+    let mut writer = None;
+    if let Some(store) = store {
+        parser::use_store(store.clone());
+        state = state.store(store.clone());
+        writer = Some(StoreWriter::new(Writer::new(store)));
+    }
     let server = Server::new(state, opts.bind_addr, workers);
     let buffer = Buffer::new(router.clone());
     router.do_send(SetBufferManager(buffer));
 
-    PubSubListner::new(router, opts.nsqlookup.into_iter().collect());
+    PubSubListner::new(router, writer, source);
 
     server.run().await?;
 
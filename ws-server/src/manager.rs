@@ -1,25 +1,45 @@
-use actix::{Actor, Addr, Arbiter, Context, Handler, Recipient, Supervised, Supervisor};
-use std::collections::{hash_map::DefaultHasher, HashMap, HashSet};
+use actix::{Actor, Addr, Arbiter, Context, Handler, Message, Recipient, Supervised, Supervisor};
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 
 use crate::buffer::Buffer;
-use crate::message::{PubSubAccountWithSubKind, SetBufferManager};
-use crate::SubscriptionKind;
+use crate::index::FilterIndex;
+use crate::message::{
+    AccountInfo, PubSubAccountWithSubKind, RootUpdatedMessage, SetBufferManager,
+    SlotsUpdatedMessage, TrackAccount,
+};
+use crate::notification::AccountNotification;
 use crate::{
     message::{AccountUpdatedMessage, PubSubAccount, SlotUpdatedMessage, SubscribeMessage},
     SubKey,
 };
+use crate::{Commitment, Pubkey, RouteKey, Slot, SubscriptionKind, METRICS};
+
+type Recipients = HashSet<Recipient<AccountUpdatedMessage>>;
 
 /// Main struct to track which websocket sessions are interested
 /// in which kinds of updates. Keeps to separate mappings to track
-/// account related and slot related subscriptions respectively
+/// account related and slot related subscriptions respectively.
+/// Account subscriptions are grouped by routing key, so that all
+/// the subscriptions matching account update can be found at once
 pub struct SubscriptionManager {
-    account_subscriptions: HashMap<SubKey, HashSet<Recipient<AccountUpdatedMessage>>>,
+    account_subscriptions: HashMap<RouteKey, Subscriptions>,
     slot_subscriptions: HashSet<Recipient<SlotUpdatedMessage>>,
-    buffer_manager: Option<Addr<Buffer>>,
+    root_subscriptions: HashSet<Recipient<RootUpdatedMessage>>,
+    slots_updates_subscriptions: HashSet<Recipient<SlotsUpdatedMessage>>,
     id: usize,
 }
 
+/// Account subscriptions, which share the same routing key, along
+/// with index of their filters, to quickly find the subscriptions,
+/// interested in particular account update
+#[derive(Default)]
+struct Subscriptions {
+    recipients: HashMap<SubKey, Recipients>,
+    index: FilterIndex<SubKey>,
+}
+
 /// Load balancer for subscriptions, evenly distributes work among
 /// several `SubscriptionManager`s based on hash value of unique
 /// subscription identifier: for account and program subscriptions
@@ -30,6 +50,19 @@ pub struct SubscriptionsRouter {
     managers: Vec<Addr<SubscriptionManager>>,
     // buffer manager, to track non-finalized accounts
     buffer_manager: Option<Addr<Buffer>>,
+    // public keys, which have active subscriptions
+    interest: Interest,
+    // latest rooted slot, reported to slot subscribers
+    root: Slot,
+}
+
+/// Number of active subscriptions for each public key, regardless of
+/// commitment level and other subscription options, used by router to
+/// discard account updates, nobody is interested in, as early as possible
+#[derive(Default)]
+struct Interest {
+    accounts: HashMap<Pubkey, usize>,
+    programs: HashMap<Pubkey, usize>,
 }
 
 impl SubscriptionManager {
@@ -40,22 +73,105 @@ impl SubscriptionManager {
             id,
             account_subscriptions,
             slot_subscriptions,
-            buffer_manager: None,
+            root_subscriptions: HashSet::default(),
+            slots_updates_subscriptions: HashSet::default(),
         }
     }
 }
 
+impl Subscriptions {
+    fn insert(&mut self, key: SubKey, recipient: Recipient<AccountUpdatedMessage>) {
+        let recipients = match self.recipients.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                self.index.insert(entry.key().clone(), &entry.key().filters);
+                entry.insert(HashSet::new())
+            }
+        };
+        recipients.insert(recipient);
+    }
+
+    fn remove(&mut self, key: &SubKey, recipient: &Recipient<AccountUpdatedMessage>) {
+        if let Some(recipients) = self.recipients.get_mut(key) {
+            recipients.remove(recipient);
+            if recipients.is_empty() {
+                self.recipients.remove(key);
+                self.index.remove(key, &key.filters);
+            }
+        }
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.recipients.is_empty()
+    }
+}
+
+impl Interest {
+    fn keys(&mut self, kind: &SubscriptionKind) -> &mut HashMap<Pubkey, usize> {
+        match kind {
+            SubscriptionKind::Account => &mut self.accounts,
+            SubscriptionKind::Program => &mut self.programs,
+        }
+    }
+
+    fn add(&mut self, key: &SubKey) {
+        *self.keys(&key.kind).entry(key.key).or_default() += 1;
+    }
+
+    fn remove(&mut self, key: &SubKey) {
+        let keys = self.keys(&key.kind);
+        if let Some(count) = keys.get_mut(&key.key) {
+            *count -= 1;
+            if *count == 0 {
+                keys.remove(&key.key);
+            }
+        }
+    }
+
+    #[inline]
+    fn account(&self, pubkey: &Pubkey) -> bool {
+        self.accounts.contains_key(pubkey)
+    }
+
+    #[inline]
+    fn program(&self, owner: &Pubkey) -> bool {
+        self.programs.contains_key(owner)
+    }
+}
+
 #[cfg(test)]
 impl SubscriptionManager {
+    /// Number of recipients, subscribed with given key
     pub fn account_sub_count(&self, key: &SubKey) -> usize {
         self.account_subscriptions
-            .get(&key)
+            .get(&key.route())
+            .and_then(|subs| subs.recipients.get(key))
             .map(|set| set.len())
             .unwrap_or_default()
     }
+    /// Number of slot subscriptions
     pub fn slot_sub_count(&self) -> usize {
         self.slot_subscriptions.len()
     }
+    /// Number of root and slots updates subscriptions
+    pub fn slot_transitions_sub_count(&self) -> (usize, usize) {
+        (
+            self.root_subscriptions.len(),
+            self.slots_updates_subscriptions.len(),
+        )
+    }
+}
+
+#[cfg(test)]
+impl SubscriptionsRouter {
+    /// Whether any updates of given key are routed to managers
+    pub fn is_interested(&self, key: &SubKey) -> bool {
+        match key.kind {
+            SubscriptionKind::Account => self.interest.account(&key.key),
+            SubscriptionKind::Program => self.interest.program(&key.key),
+        }
+    }
 }
 
 impl SubscriptionsRouter {
@@ -75,6 +191,8 @@ impl SubscriptionsRouter {
         let router = Self {
             managers,
             buffer_manager: None,
+            interest: Interest::default(),
+            root: 0,
         };
         let arbiter = Arbiter::new().handle();
         Supervisor::start_in_arbiter(&arbiter, |_| router)
@@ -118,26 +236,39 @@ impl Handler<SubscribeMessage> for SubscriptionManager {
         match msg {
             SubscribeMessage::AccountSubscribe(info) => {
                 self.account_subscriptions
-                    .entry(info.key)
-                    .or_insert_with(HashSet::new)
-                    .insert(info.recipient);
+                    .entry(info.key.route())
+                    .or_default()
+                    .insert(info.key, info.recipient);
             }
             SubscribeMessage::SlotSubscribe(recipient) => {
                 self.slot_subscriptions.insert(recipient);
             }
             SubscribeMessage::AccountUnsubscribe(info) => {
+                let route = info.key.route();
                 let mut empty = false;
-                if let Some(recipients) = self.account_subscriptions.get_mut(&info.key) {
-                    recipients.remove(&info.recipient);
-                    empty = recipients.is_empty();
+                if let Some(subscriptions) = self.account_subscriptions.get_mut(&route) {
+                    subscriptions.remove(&info.key, &info.recipient);
+                    empty = subscriptions.is_empty();
                 }
                 if empty {
-                    self.account_subscriptions.remove(&info.key);
+                    self.account_subscriptions.remove(&route);
                 }
             }
             SubscribeMessage::SlotUnsubscribe(recipient) => {
                 self.slot_subscriptions.remove(&recipient);
             }
+            SubscribeMessage::RootSubscribe(recipient) => {
+                self.root_subscriptions.insert(recipient);
+            }
+            SubscribeMessage::RootUnsubscribe(recipient) => {
+                self.root_subscriptions.remove(&recipient);
+            }
+            SubscribeMessage::SlotsUpdatesSubscribe(recipient) => {
+                self.slots_updates_subscriptions.insert(recipient);
+            }
+            SubscribeMessage::SlotsUpdatesUnsubscribe(recipient) => {
+                self.slots_updates_subscriptions.remove(&recipient);
+            }
         }
     }
 }
@@ -146,32 +277,44 @@ impl Handler<PubSubAccountWithSubKind> for SubscriptionManager {
     type Result = ();
 
     fn handle(&mut self, acc: PubSubAccountWithSubKind, _: &mut Self::Context) -> Self::Result {
-        let key = SubKey::from(&acc);
-
-        if let Some(recipients) = self.account_subscriptions.get_mut(&key) {
-            let pubsub_account = acc.account.clone();
-            if pubsub_account.slot_status == 1 {
-                // Account has been processed, start tracking it for slot
-                // status update, ignore other commitment levels
-                let bm = self
-                    .buffer_manager
-                    .as_ref()
-                    .expect("No buffer manager is set up for submanager");
-                bm.do_send(pubsub_account);
-            }
-            let update = AccountUpdatedMessage::from(acc);
-            let mut failed = Vec::new();
-            // Broadcast the account update to all websocket session managers,
-            // which have registered themselves for it
-            for r in recipients.iter() {
-                if let Err(e) = r.do_send(update.clone()) {
-                    println!("failed to send account data to ws session: {}", e);
-                    failed.push(r.clone());
+        let route = RouteKey::from(&acc);
+
+        if let Some(subscriptions) = self.account_subscriptions.get_mut(&route) {
+            let info = AccountInfo::from(acc.account);
+            let Subscriptions {
+                recipients: groups,
+                index,
+            } = subscriptions;
+            // notifications only differ in data encoding and slicing among subscriptions,
+            // which share routing key, so render each one once for all subscribers
+            let mut notifications = HashMap::new();
+            // only check subscriptions, whose indexed filter matches account
+            for key in index.candidates(&info.data) {
+                if !key.matches(&info.data) {
+                    continue;
+                }
+                let recipients = match groups.get_mut(key) {
+                    Some(recipients) => recipients,
+                    None => continue,
+                };
+                let notification = notifications
+                    .entry((key.encoding, key.slice))
+                    .or_insert_with(|| AccountNotification::new(key, &info))
+                    .clone();
+                let update = AccountUpdatedMessage::new(key.clone(), notification);
+                let mut failed = Vec::new();
+                // Broadcast the account update to all websocket session managers,
+                // which have registered themselves for it
+                for r in recipients.iter() {
+                    if let Err(e) = r.do_send(update.clone()) {
+                        println!("failed to send account data to ws session: {}", e);
+                        failed.push(r.clone());
+                    }
+                }
+                // Remove inactive subscriptions, for which there's no active websocket session
+                for f in failed {
+                    recipients.remove(&f);
                 }
-            }
-            // Remove inactive subscriptions, for which there's no active websocket session
-            for f in failed {
-                recipients.remove(&f);
             }
         }
     }
@@ -181,28 +324,62 @@ impl Handler<SlotUpdatedMessage> for SubscriptionManager {
     type Result = ();
 
     fn handle(&mut self, msg: SlotUpdatedMessage, _ctx: &mut Self::Context) -> Self::Result {
-        let mut failed = Vec::new();
-        for r in &self.slot_subscriptions {
-            if let Err(e) = r.do_send(msg.clone()) {
-                println!("failed to send slot data to ws session: {}", e);
-                failed.push(r.clone());
-            }
-        }
-        for f in failed {
-            self.slot_subscriptions.remove(&f);
+        broadcast(&mut self.slot_subscriptions, msg);
+    }
+}
+
+impl Handler<RootUpdatedMessage> for SubscriptionManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: RootUpdatedMessage, _ctx: &mut Self::Context) -> Self::Result {
+        broadcast(&mut self.root_subscriptions, msg);
+    }
+}
+
+impl Handler<SlotsUpdatedMessage> for SubscriptionManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: SlotsUpdatedMessage, _ctx: &mut Self::Context) -> Self::Result {
+        broadcast(&mut self.slots_updates_subscriptions, msg);
+    }
+}
+
+/// Send slot related message to all the subscribed websocket sessions,
+/// and remove subscriptions, for which there's no active session anymore
+fn broadcast<M>(recipients: &mut HashSet<Recipient<M>>, msg: M)
+where
+    M: Message<Result = ()> + Send + Clone,
+{
+    let mut failed = Vec::new();
+    for r in recipients.iter() {
+        if let Err(e) = r.do_send(msg.clone()) {
+            println!("failed to send slot data to ws session: {}", e);
+            failed.push(r.clone());
         }
     }
+    for f in failed {
+        recipients.remove(&f);
+    }
 }
 
 impl Handler<SubscribeMessage> for SubscriptionsRouter {
     type Result = ();
 
     fn handle(&mut self, msg: SubscribeMessage, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            SubscribeMessage::AccountSubscribe(ref info) => self.interest.add(&info.key),
+            SubscribeMessage::AccountUnsubscribe(ref info) => self.interest.remove(&info.key),
+            _ => (),
+        }
         let addr = match msg {
             SubscribeMessage::AccountSubscribe(ref info)
-            | SubscribeMessage::AccountUnsubscribe(ref info) => self.addr(&info.key),
+            | SubscribeMessage::AccountUnsubscribe(ref info) => self.addr(info.key.route()),
             SubscribeMessage::SlotUnsubscribe(ref recipient)
             | SubscribeMessage::SlotSubscribe(ref recipient) => self.addr(recipient),
+            SubscribeMessage::RootUnsubscribe(ref recipient)
+            | SubscribeMessage::RootSubscribe(ref recipient) => self.addr(recipient),
+            SubscribeMessage::SlotsUpdatesUnsubscribe(ref recipient)
+            | SubscribeMessage::SlotsUpdatesSubscribe(ref recipient) => self.addr(recipient),
         };
         addr.do_send(msg);
     }
@@ -212,27 +389,69 @@ impl Handler<PubSubAccount> for SubscriptionsRouter {
     type Result = ();
 
     fn handle(&mut self, acc: PubSubAccount, _ctx: &mut Self::Context) -> Self::Result {
+        let account = self.interest.account(&acc.pubkey);
+        let program = self.interest.program(&acc.owner);
+        if !account && !program {
+            // nobody is subscribed to either account or its owner
+            METRICS.dropped_account_updates.inc();
+            return;
+        }
+        if acc.slot_status == Commitment::Processed as u8 {
+            // Account has been processed, start tracking it for slot status
+            // updates, as there might be subscriptions at higher commitment
+            // levels. Confirmed and finalized updates come from buffer itself
+            let bm = self
+                .buffer_manager
+                .as_ref()
+                .expect("No buffer manager is set up for subrouter");
+            bm.do_send(TrackAccount(acc.clone()));
+        }
         // Get address of manager by account key
-        let mut key = SubKey::new(acc.pubkey).commitment(acc.slot_status);
-        let mut addr = self.addr(&key);
-        let mut update = PubSubAccountWithSubKind::new(acc.clone(), SubscriptionKind::Account);
-
-        addr.do_send(update);
-
+        let account = account.then(|| {
+            let key = RouteKey::new(acc.pubkey).commitment(acc.slot_status);
+            self.addr(&key)
+        });
         // Get address of manager by account owner key, to check for program subscriptions
-        key = SubKey::new(acc.owner)
-            .commitment(acc.slot_status)
-            .kind(SubscriptionKind::Program);
-        addr = self.addr(&key);
-        update = PubSubAccountWithSubKind::new(acc, SubscriptionKind::Program);
-        addr.do_send(update);
+        let program = program.then(|| {
+            let key = RouteKey::new(acc.owner)
+                .commitment(acc.slot_status)
+                .kind(SubscriptionKind::Program);
+            self.addr(&key)
+        });
+        // only clone the account, if it's going to be checked for both kinds of subscriptions
+        match (account, program) {
+            (Some(account), Some(program)) => {
+                account.do_send(PubSubAccountWithSubKind::new(
+                    acc.clone(),
+                    SubscriptionKind::Account,
+                ));
+                program.do_send(PubSubAccountWithSubKind::new(
+                    acc,
+                    SubscriptionKind::Program,
+                ));
+            }
+            (Some(account), None) => {
+                account.do_send(PubSubAccountWithSubKind::new(
+                    acc,
+                    SubscriptionKind::Account,
+                ));
+            }
+            (None, Some(program)) => {
+                program.do_send(PubSubAccountWithSubKind::new(
+                    acc,
+                    SubscriptionKind::Program,
+                ));
+            }
+            (None, None) => (),
+        }
     }
 }
 
 impl Handler<SlotUpdatedMessage> for SubscriptionsRouter {
     type Result = ();
 
-    fn handle(&mut self, msg: SlotUpdatedMessage, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, mut msg: SlotUpdatedMessage, _ctx: &mut Self::Context) -> Self::Result {
+        msg.root = self.root;
         // broadcast slot message to all subscription managers
         for addr in &self.managers {
             addr.do_send(msg.clone());
@@ -248,24 +467,31 @@ impl Handler<SlotUpdatedMessage> for SubscriptionsRouter {
     }
 }
 
-impl Handler<SetBufferManager> for SubscriptionsRouter {
+impl Handler<RootUpdatedMessage> for SubscriptionsRouter {
     type Result = ();
 
-    fn handle(&mut self, msg: SetBufferManager, _: &mut Self::Context) -> Self::Result {
-        // forward message to all subscription managers
-        for m in &self.managers {
-            m.do_send(msg.clone());
+    fn handle(&mut self, msg: RootUpdatedMessage, _ctx: &mut Self::Context) -> Self::Result {
+        self.root = self.root.max(msg.0);
+        for addr in &self.managers {
+            addr.do_send(msg);
         }
+    }
+}
 
-        self.buffer_manager.replace(msg.0);
+impl Handler<SlotsUpdatedMessage> for SubscriptionsRouter {
+    type Result = ();
+
+    fn handle(&mut self, msg: SlotsUpdatedMessage, _ctx: &mut Self::Context) -> Self::Result {
+        for addr in &self.managers {
+            addr.do_send(msg);
+        }
     }
 }
 
-impl Handler<SetBufferManager> for SubscriptionManager {
+impl Handler<SetBufferManager> for SubscriptionsRouter {
     type Result = ();
 
     fn handle(&mut self, msg: SetBufferManager, _: &mut Self::Context) -> Self::Result {
-        // set buffer manager's address
-        self.buffer_manager = Some(msg.0);
+        self.buffer_manager.replace(msg.0);
     }
 }
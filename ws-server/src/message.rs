@@ -1,11 +1,13 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use actix::{Addr, Message, Recipient};
 use bytes::Bytes;
-use serde::Deserialize;
+use pubsub_wire::{AccountData, SlotData};
+use storage::slotree::{RawSlot, SlotStatus};
 
 use crate::{
-    buffer::Buffer,
-    slotree::{RawSlot, SlotStatus},
-    Commitment, Pubkey, Slot, SubID, SubKey, SubscriptionKind,
+    buffer::Buffer, notification::AccountNotification, Commitment, Pubkey, Slot, SubKey,
+    SubscriptionKind,
 };
 
 /// Message that contains information about which account was
@@ -15,15 +17,13 @@ use crate::{
 pub struct AccountUpdatedMessage {
     /// Unique internal subscription identifier
     pub key: SubKey,
-    /// All information about account, which will be published to client
-    pub info: AccountInfo,
-    /// Subscription ID issued to client, to differentiate between
-    /// different notifications sent via websocket connection
-    pub sub: SubID,
+    /// Notification, rendered once for all subscribers of the same
+    /// key, which only lacks client issued subscription id
+    pub notification: AccountNotification,
 }
 
 /// Message containing information about slot updates
-#[derive(Message, Clone, Deserialize)]
+#[derive(Message, Clone)]
 #[rtype(result = "()")]
 pub struct SlotUpdatedMessage {
     /// Slot number
@@ -32,11 +32,46 @@ pub struct SlotUpdatedMessage {
     pub parent: Slot,
     /// Level of finalization of given slot
     pub status: Commitment,
+    /// Latest rooted slot, filled in by router, as plugin doesn't report it
+    pub root: Slot,
+}
+
+/// Message indicating that slot has been rooted
+#[derive(Message, Clone, Copy)]
+#[rtype(result = "()")]
+pub struct RootUpdatedMessage(pub Slot);
+
+/// Message about transition of slot in the tree of forks,
+/// which is tracked by buffer manager
+#[derive(Message, Clone, Copy)]
+#[rtype(result = "()")]
+pub struct SlotsUpdatedMessage {
+    /// Slot number
+    pub slot: Slot,
+    /// What has happened to slot
+    pub kind: SlotsUpdateKind,
+    /// Unix timestamp in milliseconds, at which transition has been observed
+    pub timestamp: u64,
+}
+
+/// Kinds of slot transitions, reported to slots updates subscribers
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SlotsUpdateKind {
+    /// Slot has been processed by validator
+    Completed,
+    /// Supermajority of the cluster has voted on slot
+    OptimisticConfirmation,
+    /// Slot has been rooted
+    Root,
+    /// Slot belonged to the fork, which has been pruned
+    Dead,
 }
 
 /// Representation of account state
 #[derive(Clone)]
 pub struct AccountInfo {
+    /// Public key of account itself
+    pub pubkey: Pubkey,
     /// Number of lamports assigned to this account
     pub lamports: u64,
     /// Pubkey of the program this account has been assigned to
@@ -64,6 +99,14 @@ pub enum SubscribeMessage {
     AccountUnsubscribe(SubscriptionInfo),
     /// Request to remove active slot subscription
     SlotUnsubscribe(Recipient<SlotUpdatedMessage>),
+    /// Request to subscribe to rooted slots
+    RootSubscribe(Recipient<RootUpdatedMessage>),
+    /// Request to remove active root subscription
+    RootUnsubscribe(Recipient<RootUpdatedMessage>),
+    /// Request to subscribe to slot transitions
+    SlotsUpdatesSubscribe(Recipient<SlotsUpdatedMessage>),
+    /// Request to remove active slots updates subscription
+    SlotsUpdatesUnsubscribe(Recipient<SlotsUpdatedMessage>),
 }
 
 /// Data describing all the pieces of information
@@ -77,7 +120,7 @@ pub struct SubscriptionInfo {
 }
 
 /// Account update received over NSQ channel
-#[derive(Deserialize, Message, Clone)]
+#[derive(Message, Clone)]
 #[rtype(result = "()")]
 pub struct PubSubAccount {
     /// Public key of given account
@@ -105,6 +148,16 @@ pub struct PubSubAccountWithSubKind {
     pub kind: SubscriptionKind,
 }
 
+/// Account update, which should be persisted to storage
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct StoreAccount(pub AccountData);
+
+/// Slot update, which should be persisted to storage
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct StoreSlot(pub SlotData);
+
 /// Message that is sent to Buffer manager, which starts
 /// keeping track of account's slot status updates
 #[derive(Message)]
@@ -112,13 +165,41 @@ pub struct PubSubAccountWithSubKind {
 pub struct TrackAccount(pub PubSubAccount);
 
 /// Message used to set buffer manager's address in subscription
-/// manager, as it's not possible to do it during initialization,
+/// router, as it's not possible to do it during initialization,
 /// due to circular dependency: subscription router ->
-/// subscription manager -> buffer manager -> subscription router
+/// buffer manager -> subscription router
 #[derive(Message, Clone)]
 #[rtype(result = "()")]
 pub struct SetBufferManager(pub Addr<Buffer>);
 
+impl SlotsUpdatedMessage {
+    /// Construct slot transition, which has just been observed
+    pub fn new(slot: Slot, kind: SlotsUpdateKind) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        Self {
+            slot,
+            kind,
+            timestamp,
+        }
+    }
+}
+
+impl SlotsUpdateKind {
+    /// Commitment level, which slot has reached with this
+    /// transition, dead slots never reach any of them
+    pub fn commitment(&self) -> Option<Commitment> {
+        match self {
+            Self::Completed => Some(Commitment::Processed),
+            Self::OptimisticConfirmation => Some(Commitment::Confirmed),
+            Self::Root => Some(Commitment::Finalized),
+            Self::Dead => None,
+        }
+    }
+}
+
 impl PubSubAccountWithSubKind {
     /// Helper method to crate new instance of `PubSubAccountWithSubKind` message
     pub fn new(account: PubSubAccount, kind: SubscriptionKind) -> Self {
@@ -126,19 +207,58 @@ impl PubSubAccountWithSubKind {
     }
 }
 
-impl From<PubSubAccountWithSubKind> for AccountUpdatedMessage {
-    fn from(acc: PubSubAccountWithSubKind) -> Self {
-        let key = SubKey::from(&acc);
-        let info = AccountInfo::from(acc.account);
-        let sub = SubID::default();
+impl AccountUpdatedMessage {
+    /// Construct account update for subscription with given key
+    pub fn new(key: SubKey, notification: AccountNotification) -> Self {
+        Self { key, notification }
+    }
+}
+
+impl From<AccountData> for PubSubAccount {
+    fn from(acc: AccountData) -> Self {
+        Self {
+            pubkey: acc.pubkey,
+            owner: acc.owner,
+            lamports: acc.lamports,
+            data: acc.data,
+            rent_epoch: acc.rent_epoch,
+            executable: acc.executable,
+            slot: acc.slot,
+            // all account updates from pubsub are generated for processed slots
+            slot_status: Commitment::Processed as u8,
+        }
+    }
+}
+
+impl From<SlotData> for SlotUpdatedMessage {
+    fn from(slot: SlotData) -> Self {
+        Self {
+            slot: slot.slot,
+            parent: slot.parent,
+            status: slot.status.into(),
+            root: 0,
+        }
+    }
+}
 
-        Self { key, info, sub }
+impl From<AccountData> for AccountInfo {
+    fn from(acc: AccountData) -> Self {
+        AccountInfo {
+            pubkey: acc.pubkey,
+            lamports: acc.lamports,
+            owner: acc.owner,
+            data: acc.data,
+            executable: acc.executable,
+            rent_epoch: acc.rent_epoch,
+            slot: acc.slot,
+        }
     }
 }
 
 impl From<PubSubAccount> for AccountInfo {
     fn from(acc: PubSubAccount) -> Self {
         AccountInfo {
+            pubkey: acc.pubkey,
             lamports: acc.lamports,
             owner: acc.owner,
             data: acc.data,
@@ -162,6 +282,10 @@ impl RawSlot for SlotUpdatedMessage {
 
     #[inline]
     fn status(&self) -> SlotStatus {
-        self.status.into()
+        match self.status {
+            Commitment::Processed => SlotStatus::Processed,
+            Commitment::Confirmed => SlotStatus::Confirmed,
+            Commitment::Finalized => SlotStatus::Rooted,
+        }
     }
 }
@@ -1,7 +1,7 @@
 use lazy_static::lazy_static;
 use prometheus::{
-    register_int_counter, register_int_gauge, register_int_gauge_vec, IntCounter, IntGauge,
-    IntGaugeVec,
+    register_int_counter, register_int_counter_vec, register_int_gauge, register_int_gauge_vec,
+    IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
 };
 
 /// Collection of different application metrics
@@ -16,6 +16,10 @@ pub struct Metrics {
     pub connection_timeouts: IntCounter,
     pub buffered_accounts: IntCounter,
     pub buffered_slots: IntCounter,
+    pub dropped_account_updates: IntCounter,
+    pub lost_updates: IntCounterVec,
+    pub duplicate_updates: IntCounterVec,
+    pub stale_account_updates: IntCounter,
 }
 
 lazy_static! {
@@ -78,7 +82,31 @@ lazy_static! {
         )
         .unwrap();
 
+        let dropped_account_updates = register_int_counter!(
+            "dropped_account_updates",
+            "Total number of account updates discarded by router, as nobody was subscribed to them"
+        )
+        .unwrap();
+
+        let lost_updates = register_int_counter_vec!(
+            "lost_updates",
+            "Total number of updates, which have been published by plugin, but never received",
+            &["kind"]
+        )
+        .unwrap();
 
+        let duplicate_updates = register_int_counter_vec!(
+            "duplicate_updates",
+            "Total number of updates discarded by listener, as they have been received already",
+            &["kind"]
+        )
+        .unwrap();
+
+        let stale_account_updates = register_int_counter!(
+            "stale_account_updates",
+            "Total number of account updates discarded by listener, as newer ones have been received already"
+        )
+        .unwrap();
 
         Metrics {
             subscriptions_count,
@@ -91,6 +119,10 @@ lazy_static! {
             connection_timeouts,
             buffered_accounts,
             buffered_slots,
+            dropped_account_updates,
+            lost_updates,
+            duplicate_updates,
+            stale_account_updates,
         }
     };
 }
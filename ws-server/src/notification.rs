@@ -1,27 +1,37 @@
+use std::sync::Arc;
+
 use serde::Serialize;
 
 use crate::{
-    message::{AccountInfo, AccountUpdatedMessage, SlotUpdatedMessage},
-    Slot, SubID, SubscriptionKind, JSONRPC,
+    message::{
+        AccountInfo, RootUpdatedMessage, SlotUpdatedMessage, SlotsUpdateKind, SlotsUpdatedMessage,
+    },
+    parser::{self, ParsedAccount},
+    subscription::{DataSlice, Encoding},
+    Pubkey, Slot, SubID, SubKey, SubscriptionKind, JSONRPC,
 };
 
-/// Notification sent over websocket connection,
-/// indicating that account has changed
-#[derive(Serialize)]
-pub struct AccountNotification {
-    jsonrpc: &'static str,
-    method: &'static str,
-    params: AccountNotificationParams,
-}
+/// Max size of account data, which can be encoded in base58,
+/// larger accounts are substituted with error message
+const MAX_BASE58_BYTES: usize = 128;
+/// Max length of decimal representation of subscription id
+const MAX_SUB_ID_LEN: usize = 20;
 
-/// Parameters of notification, contains account information
-/// and client issued subscription identifier
-#[derive(Serialize)]
-struct AccountNotificationParams {
-    result: AccountNotificationResult,
-    subscription: SubID,
+/// Notification sent over websocket connection, indicating that
+/// account has changed. The notification is rendered only once
+/// for all the subscribers, which share the same subscription key,
+/// and each subscriber only completes it with its own subscription id
+#[derive(Clone)]
+pub struct AccountNotification {
+    /// Rendered JSON of notification, up to the subscription id
+    prefix: Arc<str>,
+    /// Public key of account, which has been updated
+    pubkey: Pubkey,
+    /// Slot, at which account has been updated
+    slot: Slot,
 }
 
+/// Result of notification, contains account information
 #[derive(Serialize)]
 struct AccountNotificationResult {
     context: AccountNotificationContext,
@@ -47,14 +57,27 @@ pub enum AccountNotificationValue {
 
 /// Updated account state sent as payload of notification
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct AccountValue {
-    data: [String; 2],
+    data: AccountData,
     owner: String,
     rent_epoch: u64,
     lamports: u64,
     executable: bool,
 }
 
+/// Account data, encoded in format requested by client
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum AccountData {
+    /// Binary data, encoded as string, paired with encoding name
+    Binary([String; 2]),
+    /// Binary data, encoded as base58 string, with no encoding name
+    LegacyBinary(String),
+    /// Data, parsed into JSON by parser of owner program
+    Parsed(ParsedAccount),
+}
+
 /// Updated account state for program subscriptions, contains
 /// additional public key, to indicate which account has changed
 #[derive(Serialize)]
@@ -63,56 +86,83 @@ pub struct ProgramValue {
     account: AccountValue,
 }
 
-impl From<AccountUpdatedMessage> for AccountNotification {
-    fn from(msg: AccountUpdatedMessage) -> Self {
-        let method = match msg.key.kind {
+impl AccountNotification {
+    /// Render notification about account update for given subscription key
+    pub fn new(key: &SubKey, info: &AccountInfo) -> Self {
+        let method = match key.kind {
             SubscriptionKind::Program => "programNotification",
             SubscriptionKind::Account => "accountNotification",
         };
-        let subscription = msg.sub;
-        let result = AccountNotificationResult::from(msg);
-        let params = AccountNotificationParams {
-            result,
-            subscription,
-        };
+        let result = AccountNotificationResult::new(key, info);
+        let result = serde_json::to_string(&result).unwrap();
+        // subscription id is the last field of notification, so that
+        // the rest of it can be rendered without knowing the id
+        let prefix = format!(
+            r#"{{"jsonrpc":"{}","method":"{}","params":{{"result":{},"subscription":"#,
+            JSONRPC, method, result
+        );
 
         Self {
-            jsonrpc: JSONRPC,
-            method,
-            params,
+            prefix: prefix.into(),
+            pubkey: info.pubkey,
+            slot: info.slot,
         }
     }
+
+    /// Public key of account, which has been updated
+    #[inline]
+    pub fn pubkey(&self) -> &Pubkey {
+        &self.pubkey
+    }
+
+    /// Slot, at which account has been updated
+    #[inline]
+    pub fn slot(&self) -> Slot {
+        self.slot
+    }
+
+    /// Complete the notification with subscription id, issued to client
+    pub fn render(&self, sub: SubID) -> String {
+        let mut text = String::with_capacity(self.prefix.len() + MAX_SUB_ID_LEN + 2);
+        text.push_str(&self.prefix);
+        text.push_str(&sub.to_string());
+        text.push_str("}}");
+        text
+    }
 }
 
-impl From<AccountUpdatedMessage> for AccountNotificationResult {
-    fn from(msg: AccountUpdatedMessage) -> Self {
-        let context = AccountNotificationContext {
-            slot: msg.info.slot,
-        };
+impl AccountNotificationResult {
+    fn new(key: &SubKey, info: &AccountInfo) -> Self {
+        let context = AccountNotificationContext { slot: info.slot };
 
-        let value = AccountNotificationValue::from(msg);
+        let value = AccountNotificationValue::new(key, info);
 
         Self { context, value }
     }
 }
 
-impl From<AccountUpdatedMessage> for AccountNotificationValue {
-    fn from(msg: AccountUpdatedMessage) -> Self {
-        let account = AccountValue::from(msg.info);
+impl AccountNotificationValue {
+    fn new(key: &SubKey, info: &AccountInfo) -> Self {
+        let account = AccountValue::new(info, key.encoding, key.slice);
 
-        match msg.key.kind {
-            SubscriptionKind::Program => {
-                let pubkey = bs58::encode(msg.key.key).into_string();
-                let value = ProgramValue { pubkey, account };
-                Self::Program(value)
-            }
+        match key.kind {
+            SubscriptionKind::Program => Self::Program(ProgramValue::new(&info.pubkey, account)),
             SubscriptionKind::Account => Self::Account(account),
         }
     }
 }
 
-impl From<AccountInfo> for AccountValue {
-    fn from(info: AccountInfo) -> Self {
+impl ProgramValue {
+    /// Construct state of program account with given public key
+    pub fn new(pubkey: &Pubkey, account: AccountValue) -> Self {
+        let pubkey = bs58::encode(pubkey).into_string();
+        Self { pubkey, account }
+    }
+}
+
+impl AccountValue {
+    /// Construct account state, with data sliced and encoded as requested by client
+    pub fn new(info: &AccountInfo, encoding: Encoding, slice: Option<DataSlice>) -> Self {
         let AccountInfo {
             data,
             owner,
@@ -122,19 +172,42 @@ impl From<AccountInfo> for AccountValue {
             ..
         } = info;
 
-        let data = base64::encode(
-            zstd::encode_all(&data[..], 0).expect("Account data cannot be compressed"),
-        );
-        let data = [data, "base64+zstd".into()];
+        let data = match slice {
+            Some(slice) => encode_data(slice.apply(data), owner, encoding),
+            None => encode_data(data, owner, encoding),
+        };
         let owner = bs58::encode(owner).into_string();
 
         Self {
             data,
             owner,
-            rent_epoch,
-            lamports,
-            executable,
+            rent_epoch: *rent_epoch,
+            lamports: *lamports,
+            executable: *executable,
+        }
+    }
+}
+
+/// Encode account data in the same way as solana RPC does
+fn encode_data(data: &[u8], owner: &Pubkey, encoding: Encoding) -> AccountData {
+    let encoded = match encoding {
+        Encoding::Binary | Encoding::Base58 if data.len() > MAX_BASE58_BYTES => {
+            "error: data too large for bs58 encoding".into()
         }
+        Encoding::Binary | Encoding::Base58 => bs58::encode(data).into_string(),
+        Encoding::JsonParsed => match parser::parse(owner, data) {
+            Some(parsed) => return AccountData::Parsed(parsed),
+            // owner program is unknown, or data couldn't be parsed
+            None => return encode_data(data, owner, Encoding::Base64),
+        },
+        Encoding::Base64 => base64::encode(data),
+        Encoding::Base64Zstd => {
+            base64::encode(zstd::encode_all(data, 0).expect("Account data cannot be compressed"))
+        }
+    };
+    match encoding {
+        Encoding::Binary => AccountData::LegacyBinary(encoded),
+        _ => AccountData::Binary([encoded, encoding.as_str().into()]),
     }
 }
 
@@ -154,25 +227,26 @@ pub struct SlotNotificationParams {
     subscription: SubID,
 }
 
-/// Main payload of slot notification, contains slot
-/// number and its parent slot
+/// Main payload of slot notification, contains slot number,
+/// its parent slot and the latest rooted slot
 #[derive(Serialize)]
 pub struct SlotNotificationResult {
     slot: Slot,
     parent: Slot,
+    root: Slot,
 }
 
-impl From<SlotUpdatedMessage> for SlotNotification {
-    fn from(msg: SlotUpdatedMessage) -> Self {
+impl SlotNotification {
+    /// Construct notification about slot update for subscription with given id
+    pub fn new(msg: &SlotUpdatedMessage, subscription: SubID) -> Self {
         let result = SlotNotificationResult {
             slot: msg.slot,
             parent: msg.parent,
+            root: msg.root,
         };
         let params = SlotNotificationParams {
             result,
-            // 0, because it's not important, as only one slot subscription
-            // can exist per websocket session
-            subscription: 0,
+            subscription,
         };
         Self {
             jsonrpc: JSONRPC,
@@ -181,3 +255,116 @@ impl From<SlotUpdatedMessage> for SlotNotification {
         }
     }
 }
+
+/// Notification indicating that slot has been rooted
+#[derive(Serialize)]
+pub struct RootNotification {
+    jsonrpc: &'static str,
+    method: &'static str,
+    params: RootNotificationParams,
+}
+
+/// Parameters of root notification, result is just the rooted slot
+#[derive(Serialize)]
+pub struct RootNotificationParams {
+    result: Slot,
+    subscription: SubID,
+}
+
+/// Notification indicating that slot has transitioned to another state
+#[derive(Serialize)]
+pub struct SlotsUpdatesNotification {
+    jsonrpc: &'static str,
+    method: &'static str,
+    params: SlotsUpdatesNotificationParams,
+}
+
+/// Parameters of slots updates notification
+#[derive(Serialize)]
+pub struct SlotsUpdatesNotificationParams {
+    result: SlotsUpdatesNotificationResult,
+    subscription: SubID,
+}
+
+/// Slot transition, tagged with its type, the same way solana does it
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum SlotsUpdatesNotificationResult {
+    /// Slot has been processed by validator
+    Completed {
+        /// Slot number
+        slot: Slot,
+        /// Unix timestamp in milliseconds
+        timestamp: u64,
+    },
+    /// Supermajority of the cluster has voted on slot
+    OptimisticConfirmation {
+        /// Slot number
+        slot: Slot,
+        /// Unix timestamp in milliseconds
+        timestamp: u64,
+    },
+    /// Slot has been rooted
+    Root {
+        /// Slot number
+        slot: Slot,
+        /// Unix timestamp in milliseconds
+        timestamp: u64,
+    },
+    /// Slot will never be rooted, as its fork has been pruned
+    Dead {
+        /// Slot number
+        slot: Slot,
+        /// Unix timestamp in milliseconds
+        timestamp: u64,
+        /// Reason, why slot is dead
+        err: &'static str,
+    },
+}
+
+impl RootNotification {
+    /// Construct notification about rooted slot for subscription with given id
+    pub fn new(msg: &RootUpdatedMessage, subscription: SubID) -> Self {
+        let params = RootNotificationParams {
+            result: msg.0,
+            subscription,
+        };
+        Self {
+            jsonrpc: JSONRPC,
+            method: "rootNotification",
+            params,
+        }
+    }
+}
+
+impl SlotsUpdatesNotification {
+    /// Construct notification about slot transition for subscription with given id
+    pub fn new(msg: &SlotsUpdatedMessage, subscription: SubID) -> Self {
+        let SlotsUpdatedMessage {
+            slot, timestamp, ..
+        } = *msg;
+        let result = match msg.kind {
+            SlotsUpdateKind::Completed => {
+                SlotsUpdatesNotificationResult::Completed { slot, timestamp }
+            }
+            SlotsUpdateKind::OptimisticConfirmation => {
+                SlotsUpdatesNotificationResult::OptimisticConfirmation { slot, timestamp }
+            }
+            SlotsUpdateKind::Root => SlotsUpdatesNotificationResult::Root { slot, timestamp },
+            SlotsUpdateKind::Dead => SlotsUpdatesNotificationResult::Dead {
+                slot,
+                timestamp,
+                err: "fork has been pruned",
+            },
+        };
+        let params = SlotsUpdatesNotificationParams {
+            result,
+            subscription,
+        };
+        Self {
+            jsonrpc: JSONRPC,
+            method: "slotsUpdatesNotification",
+            params,
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto;
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use pubsub_wire::Commitment;
+use serde::Serialize;
+use serde_json::{json, Value};
+use storage::store::Store;
+
+use crate::{Pubkey, KEY_LEN};
+
+const SPL_TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+const SYSTEM_PROGRAM: &str = "11111111111111111111111111111111";
+
+/// Packed length of SPL Token account
+const TOKEN_ACCOUNT_LEN: usize = 165;
+/// Packed length of SPL Token mint
+const TOKEN_MINT_LEN: usize = 82;
+/// Length of nonce account state
+const NONCE_LEN: usize = 80;
+/// Max number of SPL Token mints, whose decimals are kept in memory
+const MAX_CACHED_MINTS: usize = 65536;
+
+/// Function, which tries to parse raw account data into JSON
+type Parser = fn(&[u8]) -> Option<Value>;
+
+lazy_static! {
+    static ref SPL_TOKEN: Pubkey = program_id(SPL_TOKEN_PROGRAM);
+    /// Registry of available parsers, keyed by owner program of account
+    static ref PARSERS: HashMap<Pubkey, (&'static str, Parser)> = {
+        let mut parsers: HashMap<Pubkey, (&'static str, Parser)> = HashMap::new();
+        parsers.insert(*SPL_TOKEN, ("spl-token", parse_token as Parser));
+        parsers.insert(program_id(SYSTEM_PROGRAM), ("nonce", parse_nonce as Parser));
+        parsers
+    };
+    /// Decimals of SPL Token mints, which were observed in account updates,
+    /// or read from storage, required to parse token amounts of token accounts.
+    /// Mints, which couldn't be found in storage, are cached as well
+    static ref MINT_DECIMALS: RwLock<MintDecimals> =
+        RwLock::new(MintDecimals::new(MAX_CACHED_MINTS));
+    /// Storage of account states, to read mints from, which aren't cached
+    static ref STORE: RwLock<Option<Store>> = RwLock::default();
+}
+
+/// Cache of decimals of SPL Token mints, bounded by number of mints, the
+/// least recently added of which are evicted first. Decimals of mint can't
+/// change once it has been initialized, so cached values never get stale,
+/// while missing mints are replaced, once they are observed in updates
+pub struct MintDecimals {
+    decimals: HashMap<Pubkey, Option<u8>>,
+    /// Mints in the order they were added to cache
+    order: VecDeque<Pubkey>,
+    capacity: usize,
+}
+
+/// Account data, parsed into JSON representation, the same as
+/// solana RPC returns for jsonParsed encoding
+#[derive(Serialize)]
+pub struct ParsedAccount {
+    program: &'static str,
+    parsed: Value,
+    space: u64,
+}
+
+/// Try to parse account data with parser registered for its owner
+/// program, returns `None` if there's no such parser, or if the data
+/// doesn't conform to the layouts known to parser
+pub fn parse(owner: &Pubkey, data: &[u8]) -> Option<ParsedAccount> {
+    let &(program, parser) = PARSERS.get(owner)?;
+    let parsed = parser(data)?;
+    Some(ParsedAccount {
+        program,
+        parsed,
+        space: data.len() as u64,
+    })
+}
+
+/// Inspect account update, and remember the decimals of SPL Token
+/// mint, if the account happens to be one
+pub fn observe(pubkey: &Pubkey, owner: &Pubkey, data: &[u8]) {
+    let decimals = match mint_decimals(owner, data) {
+        Some(decimals) => decimals,
+        None => return,
+    };
+    if MINT_DECIMALS.read().unwrap().get(pubkey) != Some(Some(decimals)) {
+        MINT_DECIMALS.write().unwrap().insert(*pubkey, decimals);
+    }
+}
+
+/// Read decimals of SPL Token mints from given storage, when
+/// mints haven't been observed in updates since startup
+pub fn use_store(store: Store) {
+    STORE.write().unwrap().replace(store);
+}
+
+impl MintDecimals {
+    /// Create cache, which holds decimals of up to `capacity` mints
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            decimals: HashMap::default(),
+            order: VecDeque::default(),
+            capacity,
+        }
+    }
+
+    /// Get cached decimals of mint, `Some(None)` if mint is known to be missing
+    pub fn get(&self, mint: &Pubkey) -> Option<Option<u8>> {
+        self.decimals.get(mint).copied()
+    }
+
+    /// Cache decimals of mint, evicting the oldest mint, if cache is full
+    pub fn insert(&mut self, mint: Pubkey, decimals: u8) {
+        self.put(mint, Some(decimals));
+    }
+
+    /// Remember, that mint couldn't be found, so that it isn't looked up again
+    pub fn insert_missing(&mut self, mint: Pubkey) {
+        self.put(mint, None);
+    }
+
+    fn put(&mut self, mint: Pubkey, decimals: Option<u8>) {
+        if self.decimals.insert(mint, decimals).is_some() {
+            return;
+        }
+        self.order.push_back(mint);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.decimals.remove(&oldest);
+            }
+        }
+    }
+
+    /// Number of cached mints
+    pub fn len(&self) -> usize {
+        self.decimals.len()
+    }
+
+    /// Whether any mints are cached at all
+    pub fn is_empty(&self) -> bool {
+        self.decimals.is_empty()
+    }
+}
+
+/// Decimals of initialized SPL Token mint, `None` if account is not a mint
+fn mint_decimals(owner: &Pubkey, data: &[u8]) -> Option<u8> {
+    let initialized = data.len() == TOKEN_MINT_LEN && data[45] != 0;
+    (initialized && owner == &*SPL_TOKEN).then(|| data[44])
+}
+
+/// Find decimals of mint in cache, or in storage, if it's configured.
+/// Storage is read at most once per mint, as long as it stays cached
+fn decimals(mint: &Pubkey) -> Option<u8> {
+    if let Some(decimals) = MINT_DECIMALS.read().unwrap().get(mint) {
+        return decimals;
+    }
+    let store = STORE.read().unwrap().clone()?;
+    // every update is stored as processed, and decimals never change
+    let decimals = match store.get(mint, Commitment::Processed) {
+        Ok(account) => account.and_then(|a| mint_decimals(&a.owner, &a.data)),
+        Err(e) => {
+            println!("Couldn't read token mint from storage: {}", e);
+            return None;
+        }
+    };
+    let mut cache = MINT_DECIMALS.write().unwrap();
+    match decimals {
+        Some(decimals) => cache.insert(*mint, decimals),
+        None => cache.insert_missing(*mint),
+    }
+    decimals
+}
+
+fn parse_token(data: &[u8]) -> Option<Value> {
+    match data.len() {
+        TOKEN_ACCOUNT_LEN => parse_token_account(data),
+        TOKEN_MINT_LEN => parse_token_mint(data),
+        _ => None,
+    }
+}
+
+fn parse_token_account(data: &[u8]) -> Option<Value> {
+    let mint: Pubkey = data[..32].try_into().ok()?;
+    let state = match data[108] {
+        1 => "initialized",
+        2 => "frozen",
+        _ => return None,
+    };
+    // amounts can't be represented without decimals of mint, so if
+    // it's unknown, account is left to be encoded as binary data
+    let decimals = decimals(&mint)?;
+    let amount = read_u64(data, 64);
+    let delegate = read_coption_key(data, 72);
+    let is_native = read_coption_u64(data, 109);
+    let delegated_amount = read_u64(data, 121);
+    let close_authority = read_coption_key(data, 129);
+
+    let mut info = json!({
+        "mint": encode_key(&data[..32]),
+        "owner": encode_key(&data[32..64]),
+        "tokenAmount": token_amount(amount, decimals),
+        "state": state,
+        "isNative": is_native.is_some(),
+    });
+    if let Some(delegate) = delegate {
+        info["delegate"] = delegate.into();
+        info["delegatedAmount"] = token_amount(delegated_amount, decimals);
+    }
+    if let Some(reserve) = is_native {
+        info["rentExemptReserve"] = token_amount(reserve, decimals);
+    }
+    if let Some(authority) = close_authority {
+        info["closeAuthority"] = authority.into();
+    }
+    Some(json!({ "type": "account", "info": info }))
+}
+
+fn parse_token_mint(data: &[u8]) -> Option<Value> {
+    if data[45] == 0 {
+        return None;
+    }
+    let info = json!({
+        "mintAuthority": read_coption_key(data, 0),
+        "supply": read_u64(data, 36).to_string(),
+        "decimals": data[44],
+        "isInitialized": true,
+        "freezeAuthority": read_coption_key(data, 46),
+    });
+    Some(json!({ "type": "mint", "info": info }))
+}
+
+fn parse_nonce(data: &[u8]) -> Option<Value> {
+    if data.len() != NONCE_LEN {
+        return None;
+    }
+    // both legacy and current versions of nonce state share the same layout
+    if read_u32(data, 0) > 1 {
+        return None;
+    }
+    let parsed = match read_u32(data, 4) {
+        0 => json!({ "type": "uninitialized" }),
+        1 => json!({
+            "type": "initialized",
+            "info": {
+                "authority": encode_key(&data[8..40]),
+                "blockhash": encode_key(&data[40..72]),
+                "feeCalculator": {
+                    "lamportsPerSignature": read_u64(data, 72).to_string(),
+                },
+            },
+        }),
+        _ => return None,
+    };
+    Some(parsed)
+}
+
+/// Token amount, in the format used by solana RPC
+fn token_amount(amount: u64, decimals: u8) -> Value {
+    json!({
+        "amount": amount.to_string(),
+        "decimals": decimals,
+        "uiAmount": amount as f64 / 10_f64.powi(decimals as i32),
+        "uiAmountString": ui_amount_string(amount, decimals),
+    })
+}
+
+/// Decimal representation of token amount, with trailing zeroes trimmed
+fn ui_amount_string(amount: u64, decimals: u8) -> String {
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return amount.to_string();
+    }
+    // left pad with zeroes, so that there's at least an integer zero
+    let mut s = format!("{:01$}", amount, decimals + 1);
+    s.insert(s.len() - decimals, '.');
+    s.trim_end_matches('0').trim_end_matches('.').to_owned()
+}
+
+#[inline]
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(buf)
+}
+
+#[inline]
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(buf)
+}
+
+/// Read optional value, prefixed with 4 byte tag, as it's packed by SPL Token
+#[inline]
+fn read_coption_key(data: &[u8], offset: usize) -> Option<String> {
+    let start = offset + 4;
+    (read_u32(data, offset) == 1).then(|| encode_key(&data[start..start + KEY_LEN]))
+}
+
+#[inline]
+fn read_coption_u64(data: &[u8], offset: usize) -> Option<u64> {
+    (read_u32(data, offset) == 1).then(|| read_u64(data, offset + 4))
+}
+
+#[inline]
+fn encode_key(key: &[u8]) -> String {
+    bs58::encode(key).into_string()
+}
+
+fn program_id(key: &str) -> Pubkey {
+    let mut buf = [0; KEY_LEN];
+    bs58::decode(key)
+        .into(&mut buf)
+        .expect("program id should be valid base58 public key");
+    buf
+}
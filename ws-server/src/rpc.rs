@@ -0,0 +1,263 @@
+use actix_web::web::{self, Bytes, Data, HttpResponse};
+use actix_web::{post, Error as HttpError};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use storage::store::Store;
+
+use crate::{
+    error::{SubError, SubErrorKind},
+    jsonrpc,
+    message::AccountInfo,
+    notification::{AccountValue, ProgramValue},
+    server::ServerState,
+    subscription::{DataSlice, Encoding, Filter, MAX_FILTERS},
+    Commitment, Pubkey, Slot,
+};
+
+/// Max number of accounts, which can be requested at once with `getMultipleAccounts`
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Value along with the slot, at which it has been observed
+#[derive(Serialize)]
+struct WithContext<T> {
+    context: RpcContext,
+    value: T,
+}
+
+#[derive(Serialize)]
+struct RpcContext {
+    slot: Slot,
+}
+
+/// Options of account related requests
+#[derive(Deserialize, Default)]
+struct AccountConfig {
+    #[serde(default)]
+    encoding: Option<Encoding>,
+    #[serde(default)]
+    commitment: Commitment,
+    #[serde(default, rename = "dataSlice")]
+    data_slice: Option<DataSlice>,
+}
+
+/// Options of `getProgramAccounts` request
+#[derive(Deserialize, Default)]
+struct ProgramAccountsConfig {
+    #[serde(flatten)]
+    account: AccountConfig,
+    #[serde(default)]
+    filters: Vec<Filter>,
+    #[serde(default, rename = "withContext")]
+    with_context: bool,
+}
+
+/// Options of `getSlot` request
+#[derive(Deserialize, Default)]
+struct SlotConfig {
+    #[serde(default)]
+    commitment: Commitment,
+}
+
+/// Parameters, which consist of single required value,
+/// followed by optional configuration object
+#[derive(Deserialize)]
+struct ParamsWithConfig<T, C>(T, #[serde(default = "Option::default")] Option<C>);
+
+/// Entry point of JSON-RPC requests, sent over HTTP
+#[post("/")]
+pub async fn rpc(body: Bytes, state: Data<ServerState>) -> Result<HttpResponse, HttpError> {
+    let store = state.store.clone();
+    // storage queries are blocking, so they are moved off the worker thread
+    let response = web::block(move || handle(&body, store.as_ref())).await?;
+    match response {
+        Some(body) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(body)),
+        // request consisted of notifications only
+        None => Ok(HttpResponse::NoContent().finish()),
+    }
+}
+
+/// Handle JSON-RPC request (or batch of them), and render response to it
+pub fn handle(body: &[u8], store: Option<&Store>) -> Option<String> {
+    jsonrpc::handle(body, |call| match store {
+        Some(store) => dispatch(store, call.method.as_str(), call.params),
+        None => Err(SubError::new(
+            "Storage is not configured on this server".into(),
+            SubErrorKind::MethodNotFound,
+        )),
+    })
+}
+
+fn dispatch(
+    store: &Store,
+    method: &str,
+    params: JsonValue,
+) -> Result<JsonValue, SubError<'static>> {
+    match method {
+        "getAccountInfo" => account_info(store, params).and_then(to_value),
+        "getMultipleAccounts" => multiple_accounts(store, params).and_then(to_value),
+        "getProgramAccounts" => program_accounts(store, params),
+        "getSlot" => slot(store, params).and_then(to_value),
+        method => Err(SubError::new(
+            format!("Method not found: {}", method).into(),
+            SubErrorKind::MethodNotFound,
+        )),
+    }
+}
+
+fn account_info(
+    store: &Store,
+    params: JsonValue,
+) -> Result<WithContext<Option<AccountValue>>, SubError<'static>> {
+    let ParamsWithConfig(pubkey, config) = parse::<String, AccountConfig>(params)?;
+    let pubkey = decode_pubkey(&pubkey)?;
+    let config = config.unwrap_or_default();
+    let encoding = validate(&config)?;
+    let commitment = config.commitment.into();
+
+    let account = store.get(&pubkey, commitment).map_err(internal)?;
+    let value =
+        account.map(|a| AccountValue::new(&AccountInfo::from(a), encoding, config.data_slice));
+    let slot = store
+        .slot(commitment)
+        .map_err(internal)?
+        .unwrap_or_default();
+    Ok(WithContext::new(slot, value))
+}
+
+fn multiple_accounts(
+    store: &Store,
+    params: JsonValue,
+) -> Result<WithContext<Vec<Option<AccountValue>>>, SubError<'static>> {
+    let ParamsWithConfig(pubkeys, config) = parse::<Vec<String>, AccountConfig>(params)?;
+    if pubkeys.len() > MAX_MULTIPLE_ACCOUNTS {
+        return Err(SubError::new(
+            format!("Too many inputs provided; max {}", MAX_MULTIPLE_ACCOUNTS).into(),
+            SubErrorKind::InvalidParams,
+        ));
+    }
+    let pubkeys = pubkeys
+        .iter()
+        .map(|p| decode_pubkey(p))
+        .collect::<Result<Vec<_>, _>>()?;
+    let config = config.unwrap_or_default();
+    let encoding = validate(&config)?;
+    let commitment = config.commitment.into();
+
+    let slot = store
+        .slot(commitment)
+        .map_err(internal)?
+        .unwrap_or_default();
+    let mut values = Vec::with_capacity(pubkeys.len());
+    for pubkey in &pubkeys {
+        let account = store.get(pubkey, commitment).map_err(internal)?;
+        let value = account.map(|a| AccountValue::new(&a.into(), encoding, config.data_slice));
+        values.push(value);
+    }
+    Ok(WithContext::new(slot, values))
+}
+
+fn program_accounts(store: &Store, params: JsonValue) -> Result<JsonValue, SubError<'static>> {
+    let ParamsWithConfig(owner, config) = parse::<String, ProgramAccountsConfig>(params)?;
+    let owner = decode_pubkey(&owner)?;
+    let config = config.unwrap_or_default();
+    let encoding = validate(&config.account)?;
+    let commitment = config.account.commitment.into();
+    if config.filters.len() > MAX_FILTERS {
+        return Err(SubError::new(
+            format!("Too many filters provided; max {}", MAX_FILTERS).into(),
+            SubErrorKind::InvalidParams,
+        ));
+    }
+
+    let slot = store
+        .slot(commitment)
+        .map_err(internal)?
+        .unwrap_or_default();
+    let accounts = store
+        .program_accounts(&owner, commitment, &config.filters)
+        .map_err(internal)?;
+    let values: Vec<ProgramValue> = accounts
+        .into_iter()
+        .map(|a| {
+            let pubkey = a.pubkey;
+            let account = AccountValue::new(&a.into(), encoding, config.account.data_slice);
+            ProgramValue::new(&pubkey, account)
+        })
+        .collect();
+    if config.with_context {
+        to_value(WithContext::new(slot, values))
+    } else {
+        to_value(values)
+    }
+}
+
+fn slot(store: &Store, params: JsonValue) -> Result<Slot, SubError<'static>> {
+    // the only parameter of request is optional configuration
+    let config = match params {
+        JsonValue::Null => None,
+        params => serde_json::from_value::<Vec<SlotConfig>>(params)
+            .map_err(invalid_params)?
+            .into_iter()
+            .next(),
+    };
+    let commitment = config.unwrap_or_default().commitment.into();
+    let slot = store.slot(commitment).map_err(internal)?;
+    Ok(slot.unwrap_or_default())
+}
+
+impl<T> WithContext<T> {
+    fn new(slot: Slot, value: T) -> Self {
+        let context = RpcContext { slot };
+        Self { context, value }
+    }
+}
+
+/// Parse request parameters, which consist of value and optional configuration
+fn parse<T: DeserializeOwned, C: DeserializeOwned>(
+    params: JsonValue,
+) -> Result<ParamsWithConfig<T, C>, SubError<'static>> {
+    serde_json::from_value(params).map_err(invalid_params)
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<JsonValue, SubError<'static>> {
+    serde_json::to_value(value).map_err(internal)
+}
+
+fn invalid_params(e: serde_json::Error) -> SubError<'static> {
+    SubError::new(
+        format!("Invalid params: {}", e).into(),
+        SubErrorKind::InvalidParams,
+    )
+}
+
+/// Check account related options, and get encoding to use
+fn validate(config: &AccountConfig) -> Result<Encoding, SubError<'static>> {
+    // the same default as solana RPC has, for compatibility with older clients
+    let encoding = config.encoding.unwrap_or(Encoding::Binary);
+    if encoding == Encoding::JsonParsed && config.data_slice.is_some() {
+        return Err(SubError::new(
+            "Sliced account data can only be encoded using binary (base 58) or base64 encoding."
+                .into(),
+            SubErrorKind::InvalidParams,
+        ));
+    }
+    Ok(encoding)
+}
+
+fn decode_pubkey(pubkey: &str) -> Result<Pubkey, SubError<'static>> {
+    let mut buf = Pubkey::default();
+    match bs58::decode(pubkey).into(&mut buf) {
+        Ok(len) if len == buf.len() => Ok(buf),
+        _ => Err(SubError::new(
+            "Invalid param: WrongSize".into(),
+            SubErrorKind::InvalidParams,
+        )),
+    }
+}
+
+fn internal<E: std::fmt::Display>(e: E) -> SubError<'static> {
+    println!("failed to answer JSON-RPC request from storage: {}", e);
+    SubError::new("Internal error".into(), SubErrorKind::InternalError)
+}
@@ -0,0 +1,126 @@
+use std::collections::{BTreeSet, HashMap};
+
+use pubsub_wire::Stamp;
+
+use crate::{Pubkey, Slot};
+
+/// Number of sequence numbers behind the latest one, which are still
+/// expected to arrive late, missing messages older than that are lost
+const REORDER_WINDOW: u64 = 4096;
+/// Number of slots behind the latest one, for which
+/// the latest writes of accounts are remembered
+const WRITES_WINDOW: Slot = 1024;
+
+/// Outcome of checking sequence number of received message
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Sequence {
+    /// Message hasn't been received before
+    New,
+    /// Message has been received already
+    Duplicate,
+}
+
+/// Tracker of sequence numbers of messages of single kind, which detects
+/// messages, that have been lost or duplicated on their way from plugin
+#[derive(Default)]
+pub struct SequenceTracker {
+    /// Session of plugin, which messages are being tracked
+    session: Option<u64>,
+    /// Sequence number, which is expected next
+    next: u64,
+    /// Sequence numbers, which have been skipped, but might still arrive
+    missing: BTreeSet<u64>,
+}
+
+/// Latest writes of accounts, which are used to detect stale writes,
+/// that have been delivered after the more recent ones
+#[derive(Default)]
+pub struct LatestWrites {
+    writes: HashMap<Pubkey, (Slot, Option<Stamp>)>,
+    /// Slot, at which old writes have been forgotten last time
+    pruned: Slot,
+}
+
+impl SequenceTracker {
+    /// Check stamp of received message, returns whether it's a duplicate,
+    /// along with number of messages, which are considered lost by now
+    pub fn check(&mut self, stamp: Stamp) -> (Sequence, u64) {
+        match self.session {
+            Some(session) if session == stamp.session => (),
+            // messages from previous sessions might be delivered late,
+            // there's nothing to compare them with anymore
+            Some(session) if session > stamp.session => return (Sequence::New, 0),
+            // plugin has (re)started, so sequence numbers start over
+            _ => {
+                self.session = Some(stamp.session);
+                self.next = stamp.seq + 1;
+                self.missing.clear();
+                return (Sequence::New, 0);
+            }
+        }
+        if stamp.seq < self.next {
+            // message is either late, or it has been received already
+            let sequence = match self.missing.remove(&stamp.seq) {
+                true => Sequence::New,
+                false => Sequence::Duplicate,
+            };
+            return (sequence, 0);
+        }
+        // messages, which have been skipped too far behind, are lost right away
+        let expected = stamp.seq.saturating_sub(REORDER_WINDOW).max(self.next);
+        let mut lost = expected - self.next;
+        self.missing.extend(expected..stamp.seq);
+        self.next = stamp.seq + 1;
+        while let Some(&seq) = self.missing.iter().next() {
+            if seq + REORDER_WINDOW >= self.next {
+                break;
+            }
+            self.missing.remove(&seq);
+            lost += 1;
+        }
+        (Sequence::New, lost)
+    }
+}
+
+impl LatestWrites {
+    /// Remember write of account, returns false if it's older
+    /// than the latest known write of the same account
+    pub fn record(&mut self, pubkey: &Pubkey, slot: Slot, stamp: Option<Stamp>) -> bool {
+        let latest = match self.writes.get_mut(pubkey) {
+            Some(latest) => latest,
+            None => {
+                self.writes.insert(*pubkey, (slot, stamp));
+                return true;
+            }
+        };
+        // writes within the same slot can only be ordered by their stamps
+        let stale = match (stamp, latest.1) {
+            (Some(stamp), Some(last)) => (slot, stamp) <= (latest.0, last),
+            _ => slot < latest.0,
+        };
+        if !stale {
+            *latest = (slot, stamp);
+        }
+        !stale
+    }
+
+    /// Forget writes, which are too old to be overwritten by stale ones
+    pub fn prune(&mut self, slot: Slot) {
+        if slot < self.pruned + WRITES_WINDOW {
+            return;
+        }
+        let oldest = slot - WRITES_WINDOW;
+        self.writes.retain(|_, (s, _)| *s >= oldest);
+        self.pruned = slot;
+    }
+
+    /// Number of accounts, whose latest writes are remembered
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    /// Whether any writes are remembered at all
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+}
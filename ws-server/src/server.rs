@@ -6,8 +6,10 @@ use std::sync::{
     atomic::{AtomicU64, Ordering},
     Arc,
 };
+use storage::store::Store;
 
 use crate::manager::SubscriptionsRouter;
+use crate::rpc::rpc;
 use crate::session::WsSession;
 
 #[get("/")]
@@ -16,7 +18,10 @@ pub async fn connect(
     stream: Payload,
     state: Data<ServerState>,
 ) -> Result<HttpResponse, HttpError> {
-    let session = WsSession::new(state.router.clone(), state.next.load(Ordering::Relaxed));
+    let mut session = WsSession::new(state.router.clone(), state.next.load(Ordering::Relaxed));
+    if let Some(ref store) = state.store {
+        session = session.store(store.clone());
+    }
     state.next.fetch_add(1, Ordering::Relaxed);
 
     let resp = ws::start(session, &req, stream)?;
@@ -36,6 +41,7 @@ impl Server {
         HttpServer::new(move || {
             App::new()
                 .service(connect)
+                .service(rpc)
                 .app_data(Data::new(self.state.clone()))
         })
         .workers(self.workers)
@@ -52,6 +58,7 @@ impl Server {
 pub struct ServerState {
     router: Addr<SubscriptionsRouter>,
     next: Arc<AtomicU64>,
+    pub(crate) store: Option<Store>,
 }
 
 impl ServerState {
@@ -60,8 +67,15 @@ impl ServerState {
         Self {
             router,
             next: Arc::new(AtomicU64::new(0)),
+            store: None,
         }
     }
+
+    /// Answer JSON-RPC requests, and send initial values of subscriptions from given storage
+    pub fn store(mut self, store: Store) -> Self {
+        self.store = Some(store);
+        self
+    }
 }
 
 impl Server {
@@ -1,19 +1,37 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet, VecDeque};
+use std::convert::TryFrom;
 use std::time::Duration;
 
 use crate::{
     error::{SubError, SubErrorKind},
+    jsonrpc,
     manager::SubscriptionsRouter,
-    message::{AccountUpdatedMessage, SlotUpdatedMessage, SubscribeMessage, SubscriptionInfo},
-    notification::{AccountNotification, SlotNotification},
-    subscription::{Method, PubkeyParams, SubRequest, SubResponse, SubResponseError, SubResult},
+    message::{
+        AccountInfo, AccountUpdatedMessage, RootUpdatedMessage, SlotUpdatedMessage,
+        SlotsUpdatedMessage, SubscribeMessage, SubscriptionInfo,
+    },
+    notification::{
+        AccountNotification, RootNotification, SlotNotification, SlotsUpdatesNotification,
+    },
+    subscription::{Encoding, Method, PubkeyParams, SubRequest, SubResult, MAX_FILTERS},
     types::SubscriptionsMap,
-    SubID, SubKey, SubscriptionKind,
+    Commitment, Slot, SlotSubKey, SlotSubscriptionKind, SubID, SubKey, SubscriptionKind,
 };
-use actix::{clock::Instant, Actor, ActorContext, Addr, AsyncContext, Handler, StreamHandler};
+use actix::{
+    clock::Instant, Actor, ActorContext, ActorFutureExt, Addr, AsyncContext, Handler,
+    StreamHandler, WrapFuture,
+};
+use actix_web::web;
 use actix_web_actors::ws::{self, WebsocketContext};
+use pubsub_wire::AccountData;
+use serde::Serialize;
+use storage::store::Store;
 
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(15);
+/// Max number of live updates of subscription, which are held back, while its
+/// initial value is being read, the oldest of them are dropped to make room
+const MAX_HELD_UPDATES: usize = 4096;
 
 /// Websocket session manager, which is responsible for
 /// keeping connection alive and for servicing all
@@ -26,16 +44,33 @@ pub struct WsSession {
     /// list of subscriptions which are tracked by this
     /// session
     subscriptions: SubscriptionsMap,
+    /// list of slot, root and slots updates subscriptions,
+    /// which are tracked by this session
+    slot_subscriptions: SubscriptionsMap<SlotSubKey>,
 
     /// next subscription id to issued to client, on next
     /// subscription
     next: SubID,
     /// id of session itself
     id: u64,
+    /// storage of account states, which initial values
+    /// of subscriptions are read from, if configured
+    store: Option<Store>,
+    /// the latest slot of initial values, which have been sent to client, by
+    /// subscription, live updates older than that are stale, and are dropped
+    snapshots: HashMap<SubID, Slot>,
+    /// subscriptions, whose initial values are being read from storage
+    loading: HashMap<SubID, Loading>,
+}
+
+/// Initial value reads of subscription, which are in progress, along with
+/// live updates, which are held back until initial value is sent
+#[derive(Default)]
+struct Loading {
+    reads: usize,
+    held: VecDeque<AccountNotification>,
 }
 
-type Success = (SubResult, u64);
-type Failure<'a> = (SubError<'a>, Option<u64>);
 impl WsSession {
     /// Constructs new instance websocket session manager
     pub fn new(router: Addr<SubscriptionsRouter>, id: u64) -> Self {
@@ -43,11 +78,21 @@ impl WsSession {
             hb: Instant::now(),
             router,
             subscriptions: SubscriptionsMap::default(),
+            slot_subscriptions: SubscriptionsMap::default(),
             next: 0,
             id,
+            store: None,
+            snapshots: HashMap::default(),
+            loading: HashMap::default(),
         }
     }
 
+    /// Read initial values of subscriptions from given storage, if requested by client
+    pub fn store(mut self, store: Store) -> Self {
+        self.store = Some(store);
+        self
+    }
+
     /// Helper method, to perform regular heartbeat health
     /// checks. Will abort connection if client fails to
     /// respond during allowed time window
@@ -67,18 +112,11 @@ impl WsSession {
     }
 
     // Process incomming requests from clients over websocket connection
-    fn process<T: AsRef<str>>(
+    fn process(
         &mut self,
-        msg: T,
+        request: SubRequest,
         ctx: &mut WebsocketContext<Self>,
-    ) -> Result<Success, Failure<'_>> {
-        let request: SubRequest = match serde_json::from_str(msg.as_ref()) {
-            Ok(val) => val,
-            Err(e) => {
-                println!("Invalid websocket message, cannot deserialize: {}", e);
-                return Err((e.into(), None));
-            }
-        };
+    ) -> Result<SubResult, SubError<'static>> {
         use Method::*;
         match request.method {
             method @ (AccountSubscribe | ProgramSubscribe) => {
@@ -92,20 +130,54 @@ impl WsSession {
                         "Invalid params: expected [<pubkey | string>, <options: map>]".into(),
                         SubErrorKind::InvalidParams,
                     );
-                    return Err((err, Some(request.id)));
+                    return Err(err);
+                }
+                let PubkeyParams {
+                    pubkey,
+                    mut options,
+                } = params.unwrap();
+                if options.filters.len() > MAX_FILTERS {
+                    let err = SubError::new(
+                        format!("Too many filters provided; max {}", MAX_FILTERS).into(),
+                        SubErrorKind::InvalidParams,
+                    );
+                    return Err(err);
+                }
+                if options.data_slice.is_some() && options.encoding == Encoding::JsonParsed {
+                    let err = SubError::new(
+                        "Sliced account data can only be encoded using binary (base 58) or base64 encoding.".into(),
+                        SubErrorKind::InvalidParams,
+                    );
+                    return Err(err);
+                }
+                if options.initial_value && self.store.is_none() {
+                    let err = SubError::new(
+                        "Initial values are not available, storage is not configured on this server".into(),
+                        SubErrorKind::InvalidParams,
+                    );
+                    return Err(err);
                 }
-                let PubkeyParams { pubkey, options } = params.unwrap();
                 let kind = match method {
-                    AccountSubscribe => SubscriptionKind::Account,
+                    AccountSubscribe => {
+                        // filters are only applicable to program accounts
+                        options.filters.clear();
+                        SubscriptionKind::Account
+                    }
                     _ => SubscriptionKind::Program, // guaranteed to be ProgramSubscribe
                 };
                 let key = SubKey {
                     key: pubkey,
                     commitment: options.commitment,
                     kind,
+                    encoding: options.encoding,
+                    slice: options.data_slice,
+                    filters: options.filters.into(),
                 };
                 if let Some(&id) = self.subscriptions.get_by_key(&key) {
-                    return Ok((SubResult::Id(id), request.id));
+                    if options.initial_value {
+                        self.initial_value(key, id, ctx);
+                    }
+                    return Ok(SubResult::Id(id));
                 };
                 let recipient = ctx.address().recipient();
 
@@ -116,8 +188,13 @@ impl WsSession {
                 self.router
                     .do_send(SubscribeMessage::AccountSubscribe(info));
                 let id = self.next();
+                if options.initial_value {
+                    // subscription has been requested already, so any live update, which
+                    // is older than initial value, can be recognized and dropped later
+                    self.initial_value(key.clone(), id, ctx);
+                }
                 self.subscriptions.insert(key, id);
-                Ok((SubResult::Id(id), request.id))
+                Ok(SubResult::Id(id))
             }
             method @ (AccountUnsubscribe | ProgramUnsubscribe) => {
                 let params = request.params.unsub();
@@ -130,43 +207,232 @@ impl WsSession {
                         "Invalid params: expected [<id | u64>]".into(),
                         SubErrorKind::InvalidParams,
                     );
-                    return Err((err, Some(request.id)));
+                    return Err(err);
                 }
                 let id = params.unwrap();
                 let key = self.subscriptions.remove_by_id(&id);
+                self.snapshots.remove(&id);
+                self.loading.remove(&id);
                 if let Some(key) = key {
                     let recipient = ctx.address().recipient();
 
                     let info = SubscriptionInfo { key, recipient };
                     self.router
                         .do_send(SubscribeMessage::AccountUnsubscribe(info));
-                    Ok((SubResult::Status(true), request.id))
+                    Ok(SubResult::Status(true))
                 } else {
                     let err = SubError::new(
-                        "Invalid subscription id".into(),
+                        "Invalid subscription id.".into(),
                         SubErrorKind::InvalidParams,
                     );
-                    Err((err, Some(request.id)))
+                    Err(err)
                 }
             }
-            method @ (SlotSubscribe | SlotUnsubscribe) => {
-                let recipient = ctx.address().recipient();
-                let (message, result) = match method {
-                    SlotSubscribe => (
-                        SubscribeMessage::SlotSubscribe(recipient),
-                        SubResult::Id(self.next()),
-                    ),
-                    // guaranteed to be SlotUnsubscribe
-                    _ => (
-                        SubscribeMessage::SlotUnsubscribe(recipient),
-                        SubResult::Status(true),
-                    ),
+            method @ (SlotSubscribe | RootSubscribe | SlotsUpdatesSubscribe) => {
+                let options = request.params.slot();
+                if options.is_none() {
+                    println!(
+                        "Subscription parameters are invalid for request: {:?}",
+                        method
+                    );
+                    let err = SubError::new(
+                        "Invalid params: expected [<options: map>]".into(),
+                        SubErrorKind::InvalidParams,
+                    );
+                    return Err(err);
+                }
+                let kind = match method {
+                    SlotSubscribe => SlotSubscriptionKind::Slot,
+                    RootSubscribe => SlotSubscriptionKind::Root,
+                    _ => SlotSubscriptionKind::SlotsUpdates, // guaranteed to be SlotsUpdatesSubscribe
+                };
+                let commitment = match kind {
+                    // roots are finalized by definition
+                    SlotSubscriptionKind::Root => None,
+                    _ => options.unwrap().commitment,
                 };
-                self.router.do_send(message);
-                Ok((result, request.id))
+                let key = SlotSubKey::new(kind, commitment);
+                if let Some(&id) = self.slot_subscriptions.get_by_key(&key) {
+                    return Ok(SubResult::Id(id));
+                }
+                // session is subscribed only once for each kind, and
+                // notifications are matched against its subscriptions
+                self.router
+                    .do_send(slot_subscription(kind, ctx.address(), true));
+                let id = self.next();
+                self.slot_subscriptions.insert(key, id);
+                Ok(SubResult::Id(id))
+            }
+            method @ (SlotUnsubscribe | RootUnsubscribe | SlotsUpdatesUnsubscribe) => {
+                let params = request.params.unsub();
+                if params.is_none() {
+                    println!(
+                        "Subscription parameters are invalid for request: {:?}",
+                        method
+                    );
+                    let err = SubError::new(
+                        "Invalid params: expected [<id | u64>]".into(),
+                        SubErrorKind::InvalidParams,
+                    );
+                    return Err(err);
+                }
+                let kind = match method {
+                    SlotUnsubscribe => SlotSubscriptionKind::Slot,
+                    RootUnsubscribe => SlotSubscriptionKind::Root,
+                    _ => SlotSubscriptionKind::SlotsUpdates, // guaranteed to be SlotsUpdatesUnsubscribe
+                };
+                let id = params.unwrap();
+                // subscription should have been created with matching method
+                match self.slot_subscriptions.get_by_id(&id) {
+                    Some(key) if key.kind == kind => {
+                        self.slot_subscriptions.remove_by_id(&id);
+                    }
+                    _ => {
+                        let err = SubError::new(
+                            "Invalid subscription id.".into(),
+                            SubErrorKind::InvalidParams,
+                        );
+                        return Err(err);
+                    }
+                }
+                if !self
+                    .slot_subscriptions
+                    .iter()
+                    .any(|(key, _)| key.kind == kind)
+                {
+                    self.router
+                        .do_send(slot_subscription(kind, ctx.address(), false));
+                }
+                Ok(SubResult::Status(true))
             }
         }
     }
+
+    /// Read the current state of subscribed account, or of all the accounts of
+    /// program, which pass subscription filters, from storage, and send it to
+    /// client. Storage is scanned on blocking thread, and its result is sent
+    /// once the session gets to it, which is always after the response to
+    /// subscription request. Live updates of subscription are held back until
+    /// then, so that client receives them after initial value
+    fn initial_value(&mut self, key: SubKey, id: SubID, ctx: &mut WebsocketContext<Self>) {
+        let store = match self.store {
+            Some(ref store) => store.clone(),
+            None => return,
+        };
+        self.loading.entry(id).or_default().reads += 1;
+        let read = {
+            let key = key.clone();
+            web::block(move || read_initial_value(&store, &key))
+        };
+        let send = read.into_actor(self).map(move |result, actor, ctx| {
+            // held updates are released, even if initial value couldn't be read
+            let accounts = result.unwrap_or_else(|e| {
+                println!("Couldn't read initial value of subscription: {}", e);
+                Vec::new()
+            });
+            for notification in actor.initial_value_read(&key, id, accounts) {
+                ctx.text(notification);
+            }
+        });
+        ctx.spawn(send);
+    }
+
+    /// Record and render initial value of subscription, which has been read from
+    /// storage, followed by live updates, which have been held back in the meantime
+    fn initial_value_read(
+        &mut self,
+        key: &SubKey,
+        id: SubID,
+        accounts: Vec<AccountData>,
+    ) -> Vec<String> {
+        let mut loading = match self.loading.entry(id) {
+            Entry::Occupied(loading) => loading,
+            // client has unsubscribed while initial value was being read
+            Entry::Vacant(_) => return Vec::new(),
+        };
+        loading.get_mut().reads -= 1;
+        let held = if loading.get().reads == 0 {
+            loading.remove().held
+        } else {
+            VecDeque::new()
+        };
+        let mut notifications = Vec::with_capacity(accounts.len() + held.len());
+        for account in accounts {
+            // storage reflects all the updates up to the latest slot read from it
+            let snapshot = self.snapshots.entry(id).or_default();
+            *snapshot = account.slot.max(*snapshot);
+            let info = AccountInfo::from(account);
+            notifications.push(AccountNotification::new(key, &info).render(id));
+        }
+        for notification in held {
+            if !self.is_stale(id, &notification) {
+                notifications.push(notification.render(id));
+            }
+        }
+        notifications
+    }
+
+    /// Hold account update back, if initial value of its subscription is being
+    /// read, otherwise give it back to be delivered to client right away
+    fn hold(
+        &mut self,
+        id: SubID,
+        notification: AccountNotification,
+    ) -> Option<AccountNotification> {
+        match self.loading.get_mut(&id) {
+            Some(loading) => {
+                if loading.held.len() == MAX_HELD_UPDATES {
+                    println!(
+                        "Too many updates while reading initial value, dropping the oldest for subscription: {}",
+                        id
+                    );
+                    loading.held.pop_front();
+                }
+                loading.held.push_back(notification);
+                None
+            }
+            None => Some(notification),
+        }
+    }
+
+    /// Check whether account update is older, than initial value
+    /// of subscription, which has been already sent to client
+    fn is_stale(&mut self, id: SubID, notification: &AccountNotification) -> bool {
+        let slot = match self.snapshots.get(&id) {
+            Some(&slot) => slot,
+            None => return false,
+        };
+        // updates from the same slot are delivered, as it's not known whether
+        // they precede the initial value, or follow it within the slot
+        if notification.slot() < slot {
+            return true;
+        }
+        // updates are delivered in order from now on
+        self.snapshots.remove(&id);
+        false
+    }
+
+    /// Send notification, rendered for each slot related subscription of given
+    /// kind, whose commitment filter is passed by slot with given commitment
+    fn notify_slot<T, F>(
+        &self,
+        kind: SlotSubscriptionKind,
+        commitment: Option<Commitment>,
+        render: F,
+        ctx: &mut WebsocketContext<Self>,
+    ) where
+        T: Serialize,
+        F: Fn(SubID) -> T,
+    {
+        for (key, &id) in self.slot_subscriptions.iter() {
+            if key.kind != kind || !key.matches(commitment) {
+                continue;
+            }
+            let notification = serde_json::to_string(&render(id)).unwrap();
+            ctx.text(notification);
+        }
+    }
+
     fn next(&mut self) -> u64 {
         let id = self.next;
         self.next += 1;
@@ -174,6 +440,40 @@ impl WsSession {
     }
 }
 
+#[cfg(test)]
+impl WsSession {
+    /// Start reading initial value of subscription, as if it was requested by client
+    pub fn begin_initial_value(&mut self, key: &SubKey, id: SubID) -> Vec<AccountData> {
+        self.loading.entry(id).or_default().reads += 1;
+        read_initial_value(self.store.as_ref().unwrap(), key)
+    }
+
+    /// Finish reading initial value of subscription, and take notifications
+    /// rendered from it, along with updates, which have been held back
+    pub fn finish_initial_value(
+        &mut self,
+        key: &SubKey,
+        id: SubID,
+        accounts: Vec<AccountData>,
+    ) -> Vec<String> {
+        self.initial_value_read(key, id, accounts)
+    }
+
+    /// Read initial value of subscription, and take notifications rendered from it
+    pub fn take_initial_value(&mut self, key: &SubKey, id: SubID) -> Vec<String> {
+        let accounts = self.begin_initial_value(key, id);
+        self.finish_initial_value(key, id, accounts)
+    }
+
+    /// Whether account update would be delivered to client right away
+    pub fn delivers(&mut self, id: SubID, notification: &AccountNotification) -> bool {
+        match self.hold(id, notification.clone()) {
+            Some(notification) => !self.is_stale(id, &notification),
+            None => false,
+        }
+    }
+}
+
 impl Actor for WsSession {
     type Context = WebsocketContext<Self>;
 
@@ -190,9 +490,17 @@ impl Actor for WsSession {
             let msg = SubscribeMessage::AccountUnsubscribe(info);
             self.router.do_send(msg);
         }
-        let recipient = ctx.address().recipient();
-        let msg = SubscribeMessage::SlotUnsubscribe(recipient);
-        self.router.do_send(msg);
+        // session is subscribed only once for each kind of slot subscriptions
+        let kinds: HashSet<_> = self
+            .slot_subscriptions
+            .drain()
+            .into_keys()
+            .map(|key| key.kind)
+            .collect();
+        for kind in kinds {
+            let msg = slot_subscription(kind, ctx.address(), false);
+            self.router.do_send(msg);
+        }
 
         actix::Running::Stop
     }
@@ -200,9 +508,9 @@ impl Actor for WsSession {
 
 impl Handler<AccountUpdatedMessage> for WsSession {
     type Result = ();
-    fn handle(&mut self, mut msg: AccountUpdatedMessage, ctx: &mut Self::Context) -> Self::Result {
-        match self.subscriptions.get_by_key(&msg.key) {
-            Some(id) => msg.sub = *id,
+    fn handle(&mut self, msg: AccountUpdatedMessage, ctx: &mut Self::Context) -> Self::Result {
+        let id = match self.subscriptions.get_by_key(&msg.key) {
+            Some(id) => *id,
             None => {
                 println!(
                     "Subscription: {:?} coudn't be found in session: {}",
@@ -211,19 +519,80 @@ impl Handler<AccountUpdatedMessage> for WsSession {
                 return;
             }
         };
+        let notification = match self.hold(id, msg.notification) {
+            Some(notification) => notification,
+            None => return,
+        };
+        if self.is_stale(id, &notification) {
+            return;
+        }
 
-        let msg = AccountNotification::from(msg);
-        let msg = serde_json::to_string(&msg).unwrap();
-        ctx.text(msg);
+        ctx.text(notification.render(id));
     }
 }
 
 impl Handler<SlotUpdatedMessage> for WsSession {
     type Result = ();
     fn handle(&mut self, msg: SlotUpdatedMessage, ctx: &mut Self::Context) -> Self::Result {
-        let msg = SlotNotification::from(msg);
-        let msg = serde_json::to_string(&msg).unwrap();
-        ctx.text(msg);
+        let commitment = Some(msg.status);
+        let render = |id| SlotNotification::new(&msg, id);
+        self.notify_slot(SlotSubscriptionKind::Slot, commitment, render, ctx);
+    }
+}
+
+impl Handler<RootUpdatedMessage> for WsSession {
+    type Result = ();
+    fn handle(&mut self, msg: RootUpdatedMessage, ctx: &mut Self::Context) -> Self::Result {
+        let render = |id| RootNotification::new(&msg, id);
+        self.notify_slot(SlotSubscriptionKind::Root, None, render, ctx);
+    }
+}
+
+impl Handler<SlotsUpdatedMessage> for WsSession {
+    type Result = ();
+    fn handle(&mut self, msg: SlotsUpdatedMessage, ctx: &mut Self::Context) -> Self::Result {
+        let commitment = msg.kind.commitment();
+        let render = |id| SlotsUpdatesNotification::new(&msg, id);
+        self.notify_slot(SlotSubscriptionKind::SlotsUpdates, commitment, render, ctx);
+    }
+}
+
+/// Read accounts, which make up initial value of subscription, storage errors
+/// are only logged, and subscription goes on without initial value
+fn read_initial_value(store: &Store, key: &SubKey) -> Vec<AccountData> {
+    let commitment = key.commitment.into();
+    let accounts = match key.kind {
+        SubscriptionKind::Account => store
+            .get(&key.key, commitment)
+            .map(|account| account.into_iter().collect()),
+        SubscriptionKind::Program => store.program_accounts(&key.key, commitment, &key.filters),
+    };
+    accounts.unwrap_or_else(|e| {
+        println!(
+            "Couldn't read initial value of subscription from storage: {}",
+            e
+        );
+        Vec::new()
+    })
+}
+
+/// Request to (un)subscribe session to slot related updates of given kind
+fn slot_subscription(
+    kind: SlotSubscriptionKind,
+    addr: Addr<WsSession>,
+    subscribe: bool,
+) -> SubscribeMessage {
+    match (kind, subscribe) {
+        (SlotSubscriptionKind::Slot, true) => SubscribeMessage::SlotSubscribe(addr.recipient()),
+        (SlotSubscriptionKind::Slot, false) => SubscribeMessage::SlotUnsubscribe(addr.recipient()),
+        (SlotSubscriptionKind::Root, true) => SubscribeMessage::RootSubscribe(addr.recipient()),
+        (SlotSubscriptionKind::Root, false) => SubscribeMessage::RootUnsubscribe(addr.recipient()),
+        (SlotSubscriptionKind::SlotsUpdates, true) => {
+            SubscribeMessage::SlotsUpdatesSubscribe(addr.recipient())
+        }
+        (SlotSubscriptionKind::SlotsUpdates, false) => {
+            SubscribeMessage::SlotsUpdatesUnsubscribe(addr.recipient())
+        }
     }
 }
 
@@ -251,18 +620,15 @@ impl StreamHandler<WsMessage> for WsSession {
             ws::Message::Binary(bin) => {
                 println!("Unexpected binary websocket message of len: {}", bin.len())
             }
-            ws::Message::Text(text) => match self.process(text, ctx) {
-                Ok((result, id)) => {
-                    let response = SubResponse::new(id, result);
-                    let response = serde_json::to_string(&response).unwrap();
+            ws::Message::Text(text) => {
+                let response = jsonrpc::handle(text.as_bytes(), |call| {
+                    let request = SubRequest::try_from(call)?;
+                    self.process(request, ctx)
+                });
+                if let Some(response) = response {
                     ctx.text(response);
                 }
-                Err((error, id)) => {
-                    let error = SubResponseError::new(id, error);
-                    let error = serde_json::to_string(&error).unwrap();
-                    ctx.text(error);
-                }
-            },
+            }
             // TODO, not sure if we even should handle those, as subscribe messages never
             // come even close to default 64KB size of websocket frames, used by awc
             ws::Message::Continuation(_) => {}
@@ -0,0 +1,50 @@
+use actix::{Actor, Addr, Arbiter, Context, Handler, Supervised, Supervisor};
+use storage::writer::Writer;
+
+use crate::message::{StoreAccount, StoreSlot};
+
+/// Actor, which applies account and slot updates from pubsub to
+/// storage, so that JSON-RPC requests can be answered from it
+pub struct StoreWriter {
+    writer: Writer,
+}
+
+impl StoreWriter {
+    /// Convenient constructor, to start up storage writer in
+    /// a separate thread as an Actor, and return its address
+    pub fn new(writer: Writer) -> Addr<Self> {
+        let arbiter = Arbiter::new().handle();
+        let writer = Self { writer };
+        Supervisor::start_in_arbiter(&arbiter, |_| writer)
+    }
+}
+
+impl Actor for StoreWriter {
+    type Context = Context<Self>;
+}
+
+impl Supervised for StoreWriter {
+    fn restarting(&mut self, _: &mut Self::Context) {
+        println!("restarting storage writer");
+    }
+}
+
+impl Handler<StoreAccount> for StoreWriter {
+    type Result = ();
+
+    fn handle(&mut self, msg: StoreAccount, _: &mut Self::Context) -> Self::Result {
+        if let Err(e) = self.writer.account(msg.0) {
+            println!("failed to persist account update: {}", e);
+        }
+    }
+}
+
+impl Handler<StoreSlot> for StoreWriter {
+    type Result = ();
+
+    fn handle(&mut self, msg: StoreSlot, _: &mut Self::Context) -> Self::Result {
+        if let Err(e) = self.writer.slot(msg.0) {
+            println!("failed to persist slot update: {}", e);
+        }
+    }
+}
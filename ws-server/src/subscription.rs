@@ -1,16 +1,26 @@
-use crate::{error::SubError, Commitment, Pubkey, SubID, JSONRPC};
+use std::convert::TryFrom;
+
+use crate::{
+    error::{SubError, SubErrorKind},
+    jsonrpc::{Call, Id},
+    Commitment, Pubkey, SubID, JSONRPC,
+};
 use serde::{
     de::{self, Visitor},
     Deserialize, Serialize,
 };
 use serde_json::Value as JsonValue;
 
+pub use storage::filter::Filter;
+
+/// Max number of filters, allowed per program subscription
+pub const MAX_FILTERS: usize = 4;
+
 /// Represent all kinds of supported requests that the client
-/// may send over websocket connection
+/// may send over websocket connection, request id is handled
+/// separately, by JSON-RPC layer
 #[derive(Deserialize)]
 pub struct SubRequest {
-    /// Identifier of request, used when sending response back
-    pub id: u64,
     /// Request method that should be performed on server,
     /// e.g. subscribe or unsubscribe for variouse updates
     pub method: Method,
@@ -36,17 +46,27 @@ pub enum Method {
     SlotSubscribe,
     /// Unsubscribe from slot updates
     SlotUnsubscribe,
+    /// Subscribe for rooted slots
+    RootSubscribe,
+    /// Unsubscribe from rooted slots
+    RootUnsubscribe,
+    /// Subscribe for transitions of slots, including the dead ones
+    SlotsUpdatesSubscribe,
+    /// Unsubscribe from transitions of slots
+    SlotsUpdatesUnsubscribe,
 }
 
 /// Various formats of request parameters, that different methods require
 #[cfg_attr(test, derive(Debug, PartialEq, Eq))]
 pub enum Params {
     /// Parameter for all subscriptions methods.
-    /// _Note:_ slot subscriptions do not require any parameters
+    /// _Note:_ slot subscriptions use `SlotParams` instead
     SubscribeParams(PubkeyParams),
     /// Parameter for all unsubscription methods.
     /// Only client issued id is required
     UnsubscribeParams(SubID),
+    /// Optional parameter for slot related subscription methods
+    SlotParams(SlotOptions),
     /// Parameters weren't supplied
     Absent,
 }
@@ -71,14 +91,49 @@ pub struct SubOptions {
     /// slot, before sending notification to client
     #[serde(default)]
     pub commitment: Commitment,
+    /// Part of account data, which should be sent to client,
+    /// instead of the whole data
+    #[serde(default, rename = "dataSlice")]
+    pub data_slice: Option<DataSlice>,
+    /// Filters, which account must pass, in order for notification
+    /// to be sent to client, only used for program subscriptions
+    #[serde(default)]
+    pub filters: Vec<Filter>,
+    /// Whether the current state of account (or accounts of program)
+    /// should be sent to client, right after subscription is created
+    #[serde(default, rename = "initialValue")]
+    pub initial_value: bool,
+}
+
+/// Options for configuring slot related subscriptions
+#[derive(Deserialize, Default)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct SlotOptions {
+    /// Commitment level, which slot should reach, before sending
+    /// notification to client, all the transitions are sent if not set
+    #[serde(default)]
+    pub commitment: Option<Commitment>,
+}
+
+/// Range of account data, requested by client
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(Debug))]
+pub struct DataSlice {
+    /// Offset from the start of account data
+    pub offset: usize,
+    /// Max number of bytes to take, starting from offset
+    pub length: usize,
 }
 
 /// Various encoding options, that the client might
 /// want to receive the notification in
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "camelCase")]
-#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+#[cfg_attr(test, derive(Debug))]
 pub enum Encoding {
+    /// base58 encoding, sent as plain string, without the name of encoding,
+    /// it's deprecated, but still used by solana RPC, if encoding isn't set
+    Binary,
     /// base58 encoding
     Base58,
     /// base64 encoding
@@ -86,15 +141,18 @@ pub enum Encoding {
     /// base64 encoding, with additional zstd compression
     #[serde(rename = "base64+zstd")]
     Base64Zstd,
+    /// JSON representation of account data, for programs with
+    /// known account layouts, falls back to base64 otherwise
+    JsonParsed,
 }
 
 /// Response that must be sent to client over websocket
 /// connection, after receiving any request
 #[derive(Serialize)]
-pub struct SubResponse {
+pub struct SubResponse<T = SubResult> {
     jsonrpc: &'static str,
-    id: u64,
-    result: SubResult,
+    id: Id,
+    result: T,
 }
 
 /// Error response that is sent to client, in case if
@@ -102,7 +160,7 @@ pub struct SubResponse {
 #[derive(Serialize)]
 pub struct SubResponseError<'a> {
     jsonrpc: &'static str,
-    id: Option<u64>,
+    id: Id,
     error: SubError<'a>,
 }
 
@@ -116,6 +174,28 @@ pub enum SubResult {
     Status(bool),
 }
 
+impl Encoding {
+    /// Name of encoding, as it's known to client
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Binary => "binary",
+            Self::Base58 => "base58",
+            Self::Base64 => "base64",
+            Self::Base64Zstd => "base64+zstd",
+            Self::JsonParsed => "jsonParsed",
+        }
+    }
+}
+
+impl DataSlice {
+    /// Get the slice of account data, limited to data bounds
+    pub fn apply<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        let start = self.offset.min(data.len());
+        let end = self.offset.saturating_add(self.length).min(data.len());
+        &data[start..end]
+    }
+}
+
 impl Params {
     /// try to get subscription parameters for account or program,
     /// if the current request was a to create a new subscription
@@ -125,6 +205,15 @@ impl Params {
         }
         None
     }
+    /// Try to get options of slot related subscription, which
+    /// are all optional, so that parameters might be absent
+    pub fn slot(self) -> Option<SlotOptions> {
+        match self {
+            Self::SlotParams(options) => Some(options),
+            Self::Absent => Some(SlotOptions::default()),
+            _ => None,
+        }
+    }
     /// Try to get id of subscription to remove,if the current
     /// request was a to remove existing subscription
     pub fn unsub(self) -> Option<SubID> {
@@ -141,9 +230,9 @@ impl Default for Params {
     }
 }
 
-impl SubResponse {
+impl<T> SubResponse<T> {
     /// Construct a new response
-    pub fn new(id: u64, result: SubResult) -> Self {
+    pub fn new(id: Id, result: T) -> Self {
         Self {
             jsonrpc: JSONRPC,
             id,
@@ -154,7 +243,7 @@ impl SubResponse {
 
 impl<'a> SubResponseError<'a> {
     /// Construct a new response indicating error
-    pub fn new(id: Option<u64>, error: SubError<'a>) -> Self {
+    pub fn new(id: Id, error: SubError<'a>) -> Self {
         Self {
             jsonrpc: JSONRPC,
             id,
@@ -163,6 +252,25 @@ impl<'a> SubResponseError<'a> {
     }
 }
 
+impl TryFrom<Call> for SubRequest {
+    type Error = SubError<'static>;
+
+    fn try_from(call: Call) -> Result<Self, Self::Error> {
+        let method = Method::deserialize(JsonValue::String(call.method))
+            .map_err(|_| SubError::new("Method not found".into(), SubErrorKind::MethodNotFound))?;
+        let params = match call.params {
+            JsonValue::Null => Params::Absent,
+            params => Params::deserialize(params).map_err(|e| {
+                SubError::new(
+                    format!("Invalid params: {}", e).into(),
+                    SubErrorKind::InvalidParams,
+                )
+            })?,
+        };
+        Ok(Self { method, params })
+    }
+}
+
 impl<'de> Deserialize<'de> for Params {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -191,6 +299,11 @@ impl<'de> Deserialize<'de> for Params {
                     let sub = first.as_u64().unwrap();
                     return Ok(Params::UnsubscribeParams(sub));
                 }
+                if first.is_object() {
+                    let options = SlotOptions::deserialize(first)
+                        .map_err(|_| de::Error::custom("incorrect format of parameter options"))?;
+                    return Ok(Params::SlotParams(options));
+                }
 
                 let pubkey = first
                     .as_str()
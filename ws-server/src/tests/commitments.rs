@@ -0,0 +1,197 @@
+#![cfg(test)]
+use std::time::Duration;
+
+use actix::{clock::timeout, Actor, Addr, Context, Handler};
+use bytes::Bytes;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::StreamExt;
+use pubsub_wire::AccountData;
+
+use crate::{
+    buffer::Buffer,
+    manager::SubscriptionsRouter,
+    message::{
+        AccountUpdatedMessage, PubSubAccount, SetBufferManager, SlotUpdatedMessage,
+        SubscribeMessage, SubscriptionInfo,
+    },
+    subscription::Encoding,
+    Commitment, SubKey, SubscriptionKind,
+};
+
+const ACCOUNT: [u8; 32] = [5; 32];
+const OWNER: [u8; 32] = [6; 32];
+const SLOT: u64 = 10;
+
+/// Session stub, which reports subscriptions it was notified for
+struct Recorder(UnboundedSender<(SubscriptionKind, Commitment)>);
+
+impl Actor for Recorder {
+    type Context = Context<Self>;
+}
+
+impl Handler<AccountUpdatedMessage> for Recorder {
+    type Result = ();
+
+    fn handle(&mut self, msg: AccountUpdatedMessage, _: &mut Self::Context) -> Self::Result {
+        let _ = self.0.unbounded_send((msg.key.kind, msg.key.commitment));
+    }
+}
+
+/// Start router along with buffer, the same way server does
+async fn pipeline() -> Addr<SubscriptionsRouter> {
+    let router = SubscriptionsRouter::new(2);
+    let buffer = Buffer::new(router.clone());
+    router.send(SetBufferManager(buffer)).await.unwrap();
+    router
+}
+
+async fn subscribe(
+    router: &Addr<SubscriptionsRouter>,
+    recorder: &Addr<Recorder>,
+    kind: SubscriptionKind,
+    commitment: Commitment,
+) {
+    let key = SubKey {
+        key: match kind {
+            SubscriptionKind::Account => ACCOUNT,
+            SubscriptionKind::Program => OWNER,
+        },
+        commitment,
+        kind,
+        encoding: Encoding::Base64,
+        slice: None,
+        filters: Vec::new().into(),
+    };
+    let info = SubscriptionInfo {
+        key,
+        recipient: recorder.clone().recipient(),
+    };
+    router
+        .send(SubscribeMessage::AccountSubscribe(info))
+        .await
+        .unwrap();
+}
+
+async fn update_account(router: &Addr<SubscriptionsRouter>) {
+    let account = AccountData {
+        pubkey: ACCOUNT,
+        owner: OWNER,
+        lamports: 1,
+        data: Bytes::from_static(&[1, 2, 3]),
+        rent_epoch: 0,
+        executable: false,
+        slot: SLOT,
+    };
+    router.send(PubSubAccount::from(account)).await.unwrap();
+}
+
+async fn update_slot(router: &Addr<SubscriptionsRouter>, status: Commitment) {
+    let slot = SlotUpdatedMessage {
+        slot: SLOT,
+        parent: SLOT - 1,
+        status,
+        root: 0,
+    };
+    router.send(slot).await.unwrap();
+}
+
+/// Collect all the notifications, delivered within short period of time
+async fn received(
+    rx: &mut UnboundedReceiver<(SubscriptionKind, Commitment)>,
+) -> Vec<(SubscriptionKind, Commitment)> {
+    let mut notifications = Vec::new();
+    while let Ok(Some(n)) = timeout(Duration::from_millis(200), rx.next()).await {
+        notifications.push(n);
+    }
+    notifications
+}
+
+/// Subscribe at given commitment level, and return notifications
+/// received after each stage of slot's life: processed account
+/// update, slot confirmation, and slot being rooted
+async fn lifecycle(
+    kinds: &[SubscriptionKind],
+    commitment: Commitment,
+) -> [Vec<(SubscriptionKind, Commitment)>; 3] {
+    let router = pipeline().await;
+    let (tx, mut rx) = unbounded();
+    let recorder = Recorder(tx).start();
+    for kind in kinds {
+        subscribe(&router, &recorder, *kind, commitment).await;
+    }
+
+    update_account(&router).await;
+    let processed = received(&mut rx).await;
+    update_slot(&router, Commitment::Processed).await;
+    update_slot(&router, Commitment::Confirmed).await;
+    let confirmed = received(&mut rx).await;
+    update_slot(&router, Commitment::Finalized).await;
+    let finalized = received(&mut rx).await;
+    [processed, confirmed, finalized]
+}
+
+#[actix::test]
+async fn processed_commitment() {
+    let [processed, confirmed, finalized] =
+        lifecycle(&[SubscriptionKind::Account], Commitment::Processed).await;
+    assert_eq!(
+        processed,
+        [(SubscriptionKind::Account, Commitment::Processed)]
+    );
+    assert!(confirmed.is_empty());
+    assert!(finalized.is_empty());
+}
+
+#[actix::test]
+async fn confirmed_commitment() {
+    let [processed, confirmed, finalized] =
+        lifecycle(&[SubscriptionKind::Account], Commitment::Confirmed).await;
+    assert!(processed.is_empty());
+    assert_eq!(
+        confirmed,
+        [(SubscriptionKind::Account, Commitment::Confirmed)]
+    );
+    assert!(finalized.is_empty());
+}
+
+#[actix::test]
+async fn finalized_commitment() {
+    let [processed, confirmed, finalized] =
+        lifecycle(&[SubscriptionKind::Account], Commitment::Finalized).await;
+    assert!(processed.is_empty());
+    assert!(confirmed.is_empty());
+    assert_eq!(
+        finalized,
+        [(SubscriptionKind::Account, Commitment::Finalized)]
+    );
+}
+
+#[actix::test]
+async fn program_commitment() {
+    let [processed, confirmed, finalized] =
+        lifecycle(&[SubscriptionKind::Program], Commitment::Confirmed).await;
+    assert!(processed.is_empty());
+    assert_eq!(
+        confirmed,
+        [(SubscriptionKind::Program, Commitment::Confirmed)]
+    );
+    assert!(finalized.is_empty());
+}
+
+#[actix::test]
+async fn account_and_program_buffered_once() {
+    let kinds = [SubscriptionKind::Account, SubscriptionKind::Program];
+    let [processed, confirmed, mut finalized] = lifecycle(&kinds, Commitment::Finalized).await;
+    assert!(processed.is_empty());
+    assert!(confirmed.is_empty());
+    // each subscription is notified exactly once, even though
+    // the account update is relevant to both of them
+    finalized.sort_by_key(|(kind, _)| matches!(kind, SubscriptionKind::Program));
+    assert_eq!(
+        finalized,
+        [
+            (SubscriptionKind::Account, Commitment::Finalized),
+            (SubscriptionKind::Program, Commitment::Finalized),
+        ]
+    );
+}
@@ -0,0 +1,292 @@
+#![cfg(test)]
+//! Conformance of websocket sessions to solana RPC pubsub: requests and
+//! updates, described by fixtures, are fed into session and subscription
+//! managers, and every outgoing frame is compared against the one, which
+//! a validator sends in the same situation, as documented by solana RPC.
+//! Cases, which rely on behavior, that validator leaves up to server
+//! (or has no counterpart of), are marked as implementation defined, and
+//! only the shape of their frames is compared
+use std::convert::TryInto;
+use std::pin::Pin;
+use std::time::Duration;
+
+use actix::{clock::timeout, Addr};
+use actix_web::{
+    body::{AnyBody, MessageBody},
+    error::PayloadError,
+    http::header,
+    test::TestRequest,
+};
+use actix_web_actors::ws;
+use bytes::{Bytes, BytesMut};
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::future::poll_fn;
+use pubsub_wire::AccountData;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    buffer::Buffer,
+    manager::SubscriptionsRouter,
+    message::{PubSubAccount, SetBufferManager, SlotUpdatedMessage},
+    session::WsSession,
+    Commitment, Pubkey, Slot,
+};
+
+/// Masking key of client frames, its value doesn't matter to server
+const MASK: [u8; 4] = [0x37, 0xfa, 0x21, 0x3d];
+const OPCODE_TEXT: u8 = 0x1;
+
+/// Sequence of steps, each of which is followed by frames,
+/// that client is expected to receive in response to it
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Case {
+    name: String,
+    /// What makes frames of case differ from validator ones, if anything,
+    /// values of such frames are left out of comparison
+    #[serde(default)]
+    implementation_defined: Option<String>,
+    steps: Vec<Step>,
+}
+
+#[derive(Deserialize)]
+struct Step {
+    #[serde(flatten)]
+    input: Input,
+    receive: Vec<Value>,
+}
+
+/// Event, which causes server to send something to client
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Input {
+    /// Message sent by client, strings are sent as is, without being encoded
+    Send(Value),
+    /// Account update, coming from validator
+    Account(Account),
+    /// Slot status update, coming from validator
+    Slot(SlotUpdate),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Account {
+    pubkey: String,
+    owner: String,
+    lamports: u64,
+    /// Data along with its encoding, as in notifications
+    data: [String; 2],
+    executable: bool,
+    rent_epoch: u64,
+    slot: Slot,
+}
+
+#[derive(Deserialize)]
+struct SlotUpdate {
+    slot: Slot,
+    parent: Slot,
+    status: Commitment,
+}
+
+/// Websocket client, which talks to session over in memory
+/// streams, instead of network, with frames encoded by hand
+struct Client {
+    input: UnboundedSender<Result<Bytes, PayloadError>>,
+    output: Pin<Box<AnyBody>>,
+    buffer: BytesMut,
+}
+
+impl Client {
+    /// Perform websocket handshake, and start session for new connection
+    fn connect(router: Addr<SubscriptionsRouter>) -> Self {
+        let request = TestRequest::default()
+            .insert_header((header::UPGRADE, "websocket"))
+            .insert_header((header::CONNECTION, "upgrade"))
+            .insert_header((header::SEC_WEBSOCKET_VERSION, "13"))
+            .insert_header((header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ=="))
+            .to_http_request();
+        let (input, stream) = unbounded();
+        let response = ws::start(WsSession::new(router, 0), &request, stream).unwrap();
+        Self {
+            input,
+            output: Box::pin(response.into_body()),
+            buffer: BytesMut::new(),
+        }
+    }
+
+    /// Send text frame, masked as all the client frames should be
+    fn send(&self, text: &str) {
+        let mut frame = vec![0x80 | OPCODE_TEXT];
+        match text.len() {
+            len @ 0..=125 => frame.push(0x80 | len as u8),
+            len @ 126..=0xffff => {
+                frame.push(0x80 | 126);
+                frame.extend((len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(0x80 | 127);
+                frame.extend((len as u64).to_be_bytes());
+            }
+        }
+        frame.extend(MASK);
+        let masked = text.bytes().zip(MASK.iter().cycle()).map(|(b, m)| b ^ m);
+        frame.extend(masked);
+        self.input.unbounded_send(Ok(frame.into())).unwrap();
+    }
+
+    /// Collect all the text frames, sent by session within short period of time
+    async fn receive(&mut self) -> Vec<Value> {
+        let mut frames = Vec::new();
+        loop {
+            while let Some((opcode, payload)) = self.frame() {
+                // pings are of no interest here
+                if opcode == OPCODE_TEXT {
+                    frames.push(serde_json::from_slice(&payload).unwrap());
+                }
+            }
+            let output = &mut self.output;
+            let chunk = poll_fn(|cx| output.as_mut().poll_next(cx));
+            match timeout(Duration::from_millis(200), chunk).await {
+                Ok(Some(chunk)) => self.buffer.extend_from_slice(&chunk.unwrap()),
+                _ => break frames,
+            }
+        }
+    }
+
+    /// Take the next complete frame out of received bytes, server frames are never masked
+    fn frame(&mut self) -> Option<(u8, Bytes)> {
+        let buf = &self.buffer;
+        if buf.len() < 2 {
+            return None;
+        }
+        let (len, offset) = match buf[1] & 0x7f {
+            126 if buf.len() >= 4 => (u16::from_be_bytes([buf[2], buf[3]]) as usize, 4),
+            127 if buf.len() >= 10 => (
+                u64::from_be_bytes(buf[2..10].try_into().unwrap()) as usize,
+                10,
+            ),
+            126 | 127 => return None,
+            len => (len as usize, 2),
+        };
+        if buf.len() < offset + len {
+            return None;
+        }
+        let opcode = buf[0] & 0x0f;
+        let mut frame = self.buffer.split_to(offset + len).freeze();
+        Some((opcode, frame.split_off(offset)))
+    }
+}
+
+impl From<Account> for PubSubAccount {
+    fn from(account: Account) -> Self {
+        let [data, encoding] = account.data;
+        let data = match encoding.as_str() {
+            "base58" => bs58::decode(data).into_vec().unwrap(),
+            "base64" => base64::decode(data).unwrap(),
+            encoding => panic!("unsupported encoding of fixture data: {}", encoding),
+        };
+        let account = AccountData {
+            pubkey: pubkey(&account.pubkey),
+            owner: pubkey(&account.owner),
+            lamports: account.lamports,
+            data: data.into(),
+            rent_epoch: account.rent_epoch,
+            executable: account.executable,
+            slot: account.slot,
+        };
+        account.into()
+    }
+}
+
+fn pubkey(key: &str) -> Pubkey {
+    let mut pubkey = Pubkey::default();
+    bs58::decode(key).into(&mut pubkey).unwrap();
+    pubkey
+}
+
+/// Timestamps of slot transitions are different on every run, so
+/// they are only checked to be present, and then left out of comparison
+fn normalize(mut frame: Value) -> Value {
+    if frame["method"] == "slotsUpdatesNotification" {
+        let result = frame["params"]["result"].as_object_mut().unwrap();
+        let timestamp = result.remove("timestamp");
+        assert!(timestamp.as_ref().and_then(Value::as_u64).is_some());
+    }
+    frame
+}
+
+/// Structure of frame, with all the values replaced by their types,
+/// for frames, whose values are up to server to decide
+fn shape(frame: Value) -> Value {
+    match frame {
+        Value::Object(fields) => fields.into_iter().map(|(k, v)| (k, shape(v))).collect(),
+        Value::Array(items) => items.into_iter().map(shape).collect(),
+        Value::String(_) => json!("string"),
+        Value::Number(_) => json!("number"),
+        Value::Bool(_) => json!("bool"),
+        Value::Null => Value::Null,
+    }
+}
+
+/// Run every case from fixture, each over new connection to new pipeline
+async fn conformance(fixture: &str) {
+    let cases: Vec<Case> = serde_json::from_str(fixture).unwrap();
+    for case in cases {
+        // single manager keeps frames in the same order on every run
+        let router = SubscriptionsRouter::new(1);
+        let buffer = Buffer::new(router.clone());
+        router.send(SetBufferManager(buffer)).await.unwrap();
+        let mut client = Client::connect(router.clone());
+
+        for (n, step) in case.steps.into_iter().enumerate() {
+            match step.input {
+                Input::Send(Value::String(text)) => client.send(&text),
+                Input::Send(request) => client.send(&request.to_string()),
+                Input::Account(account) => router.send(PubSubAccount::from(account)).await.unwrap(),
+                Input::Slot(update) => {
+                    let msg = SlotUpdatedMessage {
+                        slot: update.slot,
+                        parent: update.parent,
+                        status: update.status,
+                        root: 0,
+                    };
+                    router.send(msg).await.unwrap()
+                }
+            }
+            let received: Vec<_> = client.receive().await.into_iter().map(normalize).collect();
+            let expected: Vec<_> = step.receive.into_iter().map(normalize).collect();
+            match case.implementation_defined {
+                Some(ref reason) => assert_eq!(
+                    received.into_iter().map(shape).collect::<Vec<_>>(),
+                    expected.into_iter().map(shape).collect::<Vec<_>>(),
+                    "{}, step #{} (implementation defined: {})",
+                    case.name,
+                    n,
+                    reason
+                ),
+                None => assert_eq!(received, expected, "{}, step #{}", case.name, n),
+            }
+        }
+    }
+}
+
+#[actix::test]
+async fn account_methods() {
+    conformance(include_str!("fixtures/account.json")).await;
+}
+
+#[actix::test]
+async fn program_methods() {
+    conformance(include_str!("fixtures/program.json")).await;
+}
+
+#[actix::test]
+async fn slot_methods() {
+    conformance(include_str!("fixtures/slot.json")).await;
+}
+
+#[actix::test]
+async fn malformed_requests() {
+    conformance(include_str!("fixtures/errors.json")).await;
+}
@@ -0,0 +1,178 @@
+#![cfg(test)]
+use bytes::Bytes;
+use pubsub_wire::AccountData;
+use serde_json::{json, Value};
+use storage::store::Store;
+
+use crate::{
+    manager::SubscriptionsRouter,
+    message::AccountInfo,
+    notification::AccountNotification,
+    session::WsSession,
+    subscription::{Encoding, SubRequest},
+    Commitment, Pubkey, Slot, SubKey, SubscriptionKind,
+};
+
+const ACCOUNT: Pubkey = [5; 32];
+const OTHER: Pubkey = [7; 32];
+const OWNER: Pubkey = [6; 32];
+
+fn account(pubkey: Pubkey, data: &'static [u8], slot: Slot) -> AccountData {
+    AccountData {
+        pubkey,
+        owner: OWNER,
+        lamports: 1,
+        data: Bytes::from_static(data),
+        rent_epoch: 0,
+        executable: false,
+        slot,
+    }
+}
+
+fn key(kind: SubscriptionKind, filters: Value) -> SubKey {
+    SubKey {
+        key: match kind {
+            SubscriptionKind::Account => ACCOUNT,
+            SubscriptionKind::Program => OWNER,
+        },
+        commitment: Commitment::Processed,
+        kind,
+        encoding: Encoding::Base64,
+        slice: None,
+        filters: serde_json::from_value::<Vec<_>>(filters).unwrap().into(),
+    }
+}
+
+fn session() -> WsSession {
+    let store = Store::temporary().unwrap();
+    let processed = Commitment::Processed.into();
+    store
+        .put(&account(ACCOUNT, &[1, 2, 3], 10), processed)
+        .unwrap();
+    store.put(&account(OTHER, &[1, 2], 12), processed).unwrap();
+    WsSession::new(SubscriptionsRouter::new(1), 0).store(store)
+}
+
+fn update(key: &SubKey, pubkey: Pubkey, slot: Slot) -> AccountNotification {
+    let info = AccountInfo::from(account(pubkey, &[4, 5, 6], slot));
+    AccountNotification::new(key, &info)
+}
+
+fn parse(notification: &str) -> Value {
+    serde_json::from_str(notification).unwrap()
+}
+
+#[test]
+fn parse_initial_value_option() {
+    let request = r#"{"jsonrpc":"2.0","id":1,"method":"accountSubscribe","params":["CM78CPUeXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNH12",{"encoding":"base64","initialValue":true}]}"#;
+    let parsed: SubRequest = serde_json::from_str(request).unwrap();
+    assert!(parsed.params.sub().unwrap().options.initial_value);
+}
+
+#[actix::test]
+async fn account_initial_value() {
+    let mut session = session();
+    let key = key(SubscriptionKind::Account, json!([]));
+    let notifications = session.take_initial_value(&key, 3);
+    assert_eq!(notifications.len(), 1);
+    let notification = parse(&notifications[0]);
+    assert_eq!(notification["method"], "accountNotification");
+    assert_eq!(notification["params"]["subscription"], 3);
+    assert_eq!(notification["params"]["result"]["context"]["slot"], 10);
+    assert_eq!(
+        notification["params"]["result"]["value"]["data"],
+        json!(["AQID", "base64"])
+    );
+
+    // updates, which were in flight, before initial value was read
+    assert!(!session.delivers(3, &update(&key, ACCOUNT, 9)));
+    // updates, which follow initial value
+    assert!(session.delivers(3, &update(&key, ACCOUNT, 10)));
+    assert!(session.delivers(3, &update(&key, ACCOUNT, 11)));
+    // once newer update is delivered, the rest are passed through as they come
+    assert!(session.delivers(3, &update(&key, ACCOUNT, 9)));
+}
+
+#[actix::test]
+async fn updates_during_initial_value() {
+    let mut session = session();
+    let key = key(SubscriptionKind::Account, json!([]));
+    let accounts = session.begin_initial_value(&key, 2);
+    // updates are held back, while initial value is being read
+    assert!(!session.delivers(2, &update(&key, ACCOUNT, 9)));
+    assert!(!session.delivers(2, &update(&key, ACCOUNT, 11)));
+    // and follow it, except for those, which are older than initial value
+    let notifications = session.finish_initial_value(&key, 2, accounts);
+    let slots: Vec<_> = notifications
+        .iter()
+        .map(|n| parse(n)["params"]["result"]["context"]["slot"].clone())
+        .collect();
+    assert_eq!(slots, [10, 11]);
+    assert!(session.delivers(2, &update(&key, ACCOUNT, 12)));
+}
+
+#[actix::test]
+async fn too_many_updates_during_initial_value() {
+    let mut session = session();
+    let key = key(SubscriptionKind::Account, json!([]));
+    let accounts = session.begin_initial_value(&key, 2);
+    for slot in 0..5000 {
+        assert!(!session.delivers(2, &update(&key, ACCOUNT, 11 + slot)));
+    }
+    // the oldest updates are dropped
+    let notifications = session.finish_initial_value(&key, 2, accounts);
+    assert_eq!(notifications.len(), 4097);
+    let slot = parse(&notifications[1])["params"]["result"]["context"]["slot"].clone();
+    assert_eq!(slot, 11 + 5000 - 4096);
+}
+
+#[actix::test]
+async fn missing_initial_value() {
+    let mut session = session();
+    let mut key = key(SubscriptionKind::Account, json!([]));
+    key.key = OWNER;
+    assert!(session.take_initial_value(&key, 0).is_empty());
+    assert!(session.delivers(0, &update(&key, OWNER, 1)));
+}
+
+#[actix::test]
+async fn program_initial_value() {
+    let mut session = session();
+    let key = key(SubscriptionKind::Program, json!([]));
+    let notifications = session.take_initial_value(&key, 1);
+    let mut pubkeys: Vec<_> = notifications
+        .iter()
+        .map(|n| {
+            let notification = parse(n);
+            assert_eq!(notification["method"], "programNotification");
+            notification["params"]["result"]["value"]["pubkey"].clone()
+        })
+        .collect();
+    pubkeys.sort_by_key(|p| p.to_string());
+    let mut expected = vec![
+        json!(bs58::encode(ACCOUNT).into_string()),
+        json!(bs58::encode(OTHER).into_string()),
+    ];
+    expected.sort_by_key(|p| p.to_string());
+    assert_eq!(pubkeys, expected);
+
+    // snapshot is as recent as the latest of its accounts
+    assert!(!session.delivers(1, &update(&key, ACCOUNT, 11)));
+    assert!(!session.delivers(1, &update(&key, OTHER, 11)));
+    assert!(session.delivers(1, &update(&key, ACCOUNT, 12)));
+    assert!(session.delivers(1, &update(&key, OTHER, 11)));
+}
+
+#[actix::test]
+async fn filtered_program_initial_value() {
+    let mut session = session();
+    let key = key(SubscriptionKind::Program, json!([{ "dataSize": 2 }]));
+    let notifications = session.take_initial_value(&key, 1);
+    assert_eq!(notifications.len(), 1);
+    let notification = parse(&notifications[0]);
+    assert_eq!(
+        notification["params"]["result"]["value"]["pubkey"],
+        bs58::encode(OTHER).into_string()
+    );
+    assert_eq!(notification["params"]["result"]["context"]["slot"], 12);
+}
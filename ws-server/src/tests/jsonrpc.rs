@@ -0,0 +1,95 @@
+#![cfg(test)]
+use std::convert::TryFrom;
+
+use serde_json::{json, Value};
+
+use crate::{
+    jsonrpc::handle,
+    subscription::{Method, SubRequest, SubResult},
+};
+
+/// Handle message the same way session does, with stub in place of session
+fn respond(message: &str) -> Option<Value> {
+    let response = handle(message.as_bytes(), |call| {
+        let request = SubRequest::try_from(call)?;
+        Ok(match request.method {
+            Method::SlotSubscribe => SubResult::Id(7),
+            _ => SubResult::Status(true),
+        })
+    });
+    response.map(|r| serde_json::from_str(&r).unwrap())
+}
+
+#[test]
+fn request_ids() {
+    for id in [json!(1), json!("abc"), Value::Null] {
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": "slotSubscribe" });
+        assert_eq!(
+            respond(&request.to_string()),
+            Some(json!({ "jsonrpc": "2.0", "id": id, "result": 7 }))
+        );
+    }
+    // requests without id are notifications, which are never answered
+    let request = json!({ "jsonrpc": "2.0", "method": "slotSubscribe" });
+    assert_eq!(respond(&request.to_string()), None);
+}
+
+#[test]
+fn error_codes() {
+    let code = |message: &str| {
+        let response = respond(message).unwrap();
+        (
+            response["id"].clone(),
+            response["error"]["code"].as_i64().unwrap(),
+        )
+    };
+    assert_eq!(code("{"), (Value::Null, -32700));
+    assert_eq!(code("[]"), (Value::Null, -32600));
+    assert_eq!(code("1"), (Value::Null, -32600));
+    assert_eq!(
+        code(r#"{"jsonrpc":"2.0","id":{},"method":"slotSubscribe"}"#),
+        (Value::Null, -32600)
+    );
+    assert_eq!(
+        code(r#"{"jsonrpc":"1.0","id":1,"method":"slotSubscribe"}"#),
+        (json!(1), -32600)
+    );
+    assert_eq!(code(r#"{"jsonrpc":"2.0","id":1}"#), (json!(1), -32600));
+    assert_eq!(
+        code(r#"{"jsonrpc":"2.0","id":"x","method":"blockSubscribe"}"#),
+        (json!("x"), -32601)
+    );
+    assert_eq!(
+        code(r#"{"jsonrpc":"2.0","id":2,"method":"accountSubscribe","params":[true]}"#),
+        (json!(2), -32602)
+    );
+}
+
+#[test]
+fn batch_requests() {
+    let batch = json!([
+        { "jsonrpc": "2.0", "id": 1, "method": "slotSubscribe" },
+        { "jsonrpc": "2.0", "method": "slotSubscribe" },
+        { "jsonrpc": "2.0", "id": "a", "method": "slotUnsubscribe", "params": [7] },
+        { "jsonrpc": "2.0", "id": 3, "method": "unknown" },
+        42
+    ]);
+    let response = respond(&batch.to_string()).unwrap();
+    assert_eq!(
+        response[0],
+        json!({ "jsonrpc": "2.0", "id": 1, "result": 7 })
+    );
+    assert_eq!(
+        response[1],
+        json!({ "jsonrpc": "2.0", "id": "a", "result": true })
+    );
+    assert_eq!(response[2]["id"], 3);
+    assert_eq!(response[2]["error"]["code"], -32601);
+    assert_eq!(response[3]["id"], Value::Null);
+    assert_eq!(response[3]["error"]["code"], -32600);
+    assert_eq!(response.as_array().unwrap().len(), 4);
+
+    // batch of notifications only is not answered at all
+    let batch = json!([{ "jsonrpc": "2.0", "method": "slotSubscribe" }]);
+    assert_eq!(respond(&batch.to_string()), None);
+}
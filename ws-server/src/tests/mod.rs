@@ -1 +1,11 @@
+mod commitments;
+mod conformance;
+mod initial;
+mod jsonrpc;
+mod notifications;
+mod parser;
+mod rpc;
+mod sequence;
+mod slots;
+mod source;
 mod subscriptions;
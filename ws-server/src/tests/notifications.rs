@@ -0,0 +1,214 @@
+#![cfg(test)]
+use bytes::Bytes;
+use serde_json::{json, Value};
+
+use crate::{
+    message::{AccountInfo, RootUpdatedMessage, SlotsUpdateKind, SlotsUpdatedMessage},
+    notification::{AccountNotification, RootNotification, SlotsUpdatesNotification},
+    subscription::{DataSlice, Encoding},
+    Commitment, Pubkey, SubKey, SubscriptionKind,
+};
+
+fn notification(data: &[u8], encoding: Encoding) -> Value {
+    notification_with_owner(data, [0; 32], encoding)
+}
+
+fn notification_with_owner(data: &[u8], owner: Pubkey, encoding: Encoding) -> Value {
+    sliced_notification(data, owner, encoding, None)
+}
+
+fn sliced_notification(
+    data: &[u8],
+    owner: Pubkey,
+    encoding: Encoding,
+    slice: Option<DataSlice>,
+) -> Value {
+    let key = SubKey {
+        key: [1; 32],
+        commitment: Commitment::Processed,
+        kind: SubscriptionKind::Account,
+        encoding,
+        slice,
+        filters: Vec::new().into(),
+    };
+    let info = AccountInfo {
+        pubkey: [1; 32],
+        lamports: 1,
+        owner,
+        data: Bytes::copy_from_slice(data),
+        executable: false,
+        rent_epoch: 2,
+        slot: 3,
+    };
+    let msg = AccountNotification::new(&key, &info).render(42);
+    serde_json::from_str(&msg).unwrap()
+}
+
+#[test]
+fn rendered_notification() {
+    let value = notification(&[1, 2, 3, 4], Encoding::Base64);
+    assert_eq!(
+        value,
+        json!({
+            "jsonrpc": "2.0",
+            "method": "accountNotification",
+            "params": {
+                "result": {
+                    "context": { "slot": 3 },
+                    "value": {
+                        "data": ["AQIDBA==", "base64"],
+                        "owner": "11111111111111111111111111111111",
+                        "rentEpoch": 2,
+                        "lamports": 1,
+                        "executable": false,
+                    },
+                },
+                "subscription": 42,
+            },
+        })
+    );
+}
+
+#[test]
+fn account_data_encodings() {
+    let data = [1, 2, 3, 4];
+    let value = notification(&data, Encoding::Base58);
+    assert_eq!(
+        value["params"]["result"]["value"]["data"],
+        json!(["2VfUX", "base58"])
+    );
+    let value = notification(&data, Encoding::Base64);
+    assert_eq!(
+        value["params"]["result"]["value"]["data"],
+        json!(["AQIDBA==", "base64"])
+    );
+    let value = notification(&data, Encoding::Base64Zstd);
+    let encoded = &value["params"]["result"]["value"]["data"];
+    assert_eq!(encoded[1], "base64+zstd");
+    let compressed = base64::decode(encoded[0].as_str().unwrap()).unwrap();
+    assert_eq!(zstd::decode_all(&compressed[..]).unwrap(), data);
+}
+
+#[test]
+fn base58_size_limit() {
+    let value = notification(&[1; 129], Encoding::Base58);
+    assert_eq!(
+        value["params"]["result"]["value"]["data"],
+        json!(["error: data too large for bs58 encoding", "base58"])
+    );
+    let value = notification(&[1; 128], Encoding::Base58);
+    let data = value["params"]["result"]["value"]["data"][0]
+        .as_str()
+        .unwrap();
+    assert_eq!(bs58::decode(data).into_vec().unwrap(), vec![1; 128]);
+}
+
+#[test]
+fn json_parsed_token_mint() {
+    let mut owner = [0; 32];
+    bs58::decode("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")
+        .into(&mut owner)
+        .unwrap();
+    let mut mint = [0; 82];
+    mint[36..44].copy_from_slice(&1_000_000_u64.to_le_bytes());
+    mint[44] = 6;
+    mint[45] = 1;
+    let value = notification_with_owner(&mint, owner, Encoding::JsonParsed);
+    assert_eq!(
+        value["params"]["result"]["value"]["data"],
+        json!({
+            "program": "spl-token",
+            "parsed": {
+                "type": "mint",
+                "info": {
+                    "mintAuthority": null,
+                    "supply": "1000000",
+                    "decimals": 6,
+                    "isInitialized": true,
+                    "freezeAuthority": null,
+                },
+            },
+            "space": 82,
+        })
+    );
+}
+
+#[test]
+fn json_parsed_fallback() {
+    let value = notification_with_owner(&[1, 2, 3, 4], [7; 32], Encoding::JsonParsed);
+    assert_eq!(
+        value["params"]["result"]["value"]["data"],
+        json!(["AQIDBA==", "base64"])
+    );
+}
+
+#[test]
+fn data_slice() {
+    let data = [0, 1, 2, 3, 4, 5];
+    let slice = DataSlice {
+        offset: 2,
+        length: 3,
+    };
+    let value = sliced_notification(&data, [0; 32], Encoding::Base64, Some(slice));
+    assert_eq!(
+        value["params"]["result"]["value"]["data"],
+        json!([base64::encode([2, 3, 4]), "base64"])
+    );
+    // slices beyond data bounds are truncated
+    let slice = DataSlice {
+        offset: 4,
+        length: 10,
+    };
+    let value = sliced_notification(&data, [0; 32], Encoding::Base64, Some(slice));
+    assert_eq!(
+        value["params"]["result"]["value"]["data"],
+        json!([base64::encode([4, 5]), "base64"])
+    );
+    let slice = DataSlice {
+        offset: 10,
+        length: 10,
+    };
+    let value = sliced_notification(&data, [0; 32], Encoding::Base64, Some(slice));
+    assert_eq!(
+        value["params"]["result"]["value"]["data"],
+        json!(["", "base64"])
+    );
+}
+
+#[test]
+fn slot_transitions() {
+    let root = RootNotification::new(&RootUpdatedMessage(42), 7);
+    assert_eq!(
+        serde_json::to_value(root).unwrap(),
+        json!({
+            "jsonrpc": "2.0",
+            "method": "rootNotification",
+            "params": { "result": 42, "subscription": 7 }
+        })
+    );
+    let mut dead = SlotsUpdatedMessage::new(43, SlotsUpdateKind::Dead);
+    dead.timestamp = 1_625_081_266_243;
+    let dead = SlotsUpdatesNotification::new(&dead, 7);
+    assert_eq!(
+        serde_json::to_value(dead).unwrap(),
+        json!({
+            "jsonrpc": "2.0",
+            "method": "slotsUpdatesNotification",
+            "params": {
+                "result": {
+                    "type": "dead",
+                    "slot": 43,
+                    "timestamp": 1_625_081_266_243u64,
+                    "err": "fork has been pruned"
+                },
+                "subscription": 7
+            }
+        })
+    );
+    let confirmed = SlotsUpdatedMessage::new(44, SlotsUpdateKind::OptimisticConfirmation);
+    let confirmed = serde_json::to_value(SlotsUpdatesNotification::new(&confirmed, 7)).unwrap();
+    assert_eq!(
+        confirmed["params"]["result"]["type"],
+        "optimisticConfirmation"
+    );
+}
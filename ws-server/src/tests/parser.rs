@@ -0,0 +1,239 @@
+#![cfg(test)]
+use bytes::Bytes;
+use pubsub_wire::{AccountData, Commitment};
+use serde_json::{json, Value};
+use storage::store::Store;
+
+use crate::{
+    message::AccountInfo,
+    notification::AccountValue,
+    parser::{self, MintDecimals},
+    subscription::Encoding,
+    Pubkey,
+};
+
+const SPL_TOKEN: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+const OWNER: Pubkey = [2; 32];
+const DELEGATE: Pubkey = [3; 32];
+const AUTHORITY: Pubkey = [4; 32];
+
+fn key(s: &str) -> Pubkey {
+    let mut key = [0; 32];
+    bs58::decode(s).into(&mut key).unwrap();
+    key
+}
+
+fn encode(key: &[u8]) -> String {
+    bs58::encode(key).into_string()
+}
+
+fn parse(owner: &str, data: &[u8]) -> Option<Value> {
+    let parsed = parser::parse(&key(owner), data)?;
+    Some(serde_json::to_value(parsed).unwrap())
+}
+
+/// Packed SPL Token mint, without authorities
+fn mint(supply: u64, decimals: u8) -> Vec<u8> {
+    let mut data = vec![0; 82];
+    data[36..44].copy_from_slice(&supply.to_le_bytes());
+    data[44] = decimals;
+    data[45] = 1;
+    data
+}
+
+/// Packed SPL Token account of given mint, which is initialized
+fn token_account(mint: &Pubkey, amount: u64) -> Vec<u8> {
+    let mut data = vec![0; 165];
+    data[..32].copy_from_slice(mint);
+    data[32..64].copy_from_slice(&OWNER);
+    data[64..72].copy_from_slice(&amount.to_le_bytes());
+    data[108] = 1;
+    data
+}
+
+#[test]
+fn token_mint() {
+    let mut data = mint(1_000_000, 6);
+    data[..4].copy_from_slice(&1_u32.to_le_bytes());
+    data[4..36].copy_from_slice(&AUTHORITY);
+    assert_eq!(
+        parse(SPL_TOKEN, &data),
+        Some(json!({
+            "program": "spl-token",
+            "parsed": {
+                "type": "mint",
+                "info": {
+                    "mintAuthority": encode(&AUTHORITY),
+                    "supply": "1000000",
+                    "decimals": 6,
+                    "isInitialized": true,
+                    "freezeAuthority": null,
+                },
+            },
+            "space": 82,
+        }))
+    );
+    // uninitialized mints are not parsed
+    data[45] = 0;
+    assert_eq!(parse(SPL_TOKEN, &data), None);
+}
+
+#[test]
+fn token_account_of_observed_mint() {
+    let mint = [11; 32];
+    parser::observe(&mint, &key(SPL_TOKEN), &self::mint(0, 2));
+    let mut data = token_account(&mint, 1500);
+    // delegate of 250 tokens
+    data[72..76].copy_from_slice(&1_u32.to_le_bytes());
+    data[76..108].copy_from_slice(&DELEGATE);
+    data[121..129].copy_from_slice(&250_u64.to_le_bytes());
+    assert_eq!(
+        parse(SPL_TOKEN, &data),
+        Some(json!({
+            "program": "spl-token",
+            "parsed": {
+                "type": "account",
+                "info": {
+                    "mint": encode(&mint),
+                    "owner": encode(&OWNER),
+                    "tokenAmount": {
+                        "amount": "1500",
+                        "decimals": 2,
+                        "uiAmount": 15.0,
+                        "uiAmountString": "15",
+                    },
+                    "delegate": encode(&DELEGATE),
+                    "delegatedAmount": {
+                        "amount": "250",
+                        "decimals": 2,
+                        "uiAmount": 2.5,
+                        "uiAmountString": "2.5",
+                    },
+                    "state": "initialized",
+                    "isNative": false,
+                },
+            },
+            "space": 165,
+        }))
+    );
+}
+
+#[test]
+fn token_account_of_stored_mint() {
+    let mint = [12; 32];
+    let store = Store::temporary().unwrap();
+    let account = AccountData {
+        pubkey: mint,
+        owner: key(SPL_TOKEN),
+        lamports: 1,
+        data: Bytes::from(self::mint(0, 9)),
+        rent_epoch: 0,
+        executable: false,
+        slot: 1,
+    };
+    store.put(&account, Commitment::Processed).unwrap();
+    parser::use_store(store);
+
+    let mut data = token_account(&mint, 5);
+    // native account, with rent exempt reserve
+    data[109..113].copy_from_slice(&1_u32.to_le_bytes());
+    data[113..121].copy_from_slice(&2_039_280_u64.to_le_bytes());
+    data[108] = 2;
+    let parsed = parse(SPL_TOKEN, &data).unwrap();
+    let info = &parsed["parsed"]["info"];
+    assert_eq!(info["state"], "frozen");
+    assert_eq!(info["isNative"], true);
+    assert_eq!(info["tokenAmount"]["uiAmountString"], "0.000000005");
+    assert_eq!(info["rentExemptReserve"]["uiAmountString"], "0.00203928");
+}
+
+#[test]
+fn token_account_of_unknown_mint() {
+    let mint = [13; 32];
+    let data = token_account(&mint, 1500);
+    assert_eq!(parse(SPL_TOKEN, &data), None);
+    // amounts can't be parsed, so data falls back to binary encoding
+    let account = AccountData {
+        pubkey: [14; 32],
+        owner: key(SPL_TOKEN),
+        lamports: 1,
+        data: Bytes::from(data.clone()),
+        rent_epoch: 0,
+        executable: false,
+        slot: 1,
+    };
+    let info = AccountInfo::from(account);
+    let value = AccountValue::new(&info, Encoding::JsonParsed, None);
+    assert_eq!(
+        serde_json::to_value(value).unwrap()["data"],
+        json!([base64::encode(&data), "base64"])
+    );
+    // once mint is observed, account gets parsed, despite mint having been missing
+    parser::observe(&mint, &key(SPL_TOKEN), &self::mint(0, 0));
+    let parsed = parse(SPL_TOKEN, &data).unwrap();
+    assert_eq!(parsed["parsed"]["info"]["tokenAmount"]["amount"], "1500");
+}
+
+#[test]
+fn uninitialized_token_account() {
+    let mut data = token_account(&[11; 32], 1500);
+    data[108] = 0;
+    assert_eq!(parse(SPL_TOKEN, &data), None);
+}
+
+#[test]
+fn nonce() {
+    let system = "11111111111111111111111111111111";
+    let mut data = vec![0; 80];
+    data[..4].copy_from_slice(&1_u32.to_le_bytes());
+    assert_eq!(
+        parse(system, &data).unwrap()["parsed"],
+        json!({ "type": "uninitialized" })
+    );
+
+    data[4..8].copy_from_slice(&1_u32.to_le_bytes());
+    data[8..40].copy_from_slice(&AUTHORITY);
+    data[40..72].copy_from_slice(&[5; 32]);
+    data[72..80].copy_from_slice(&5000_u64.to_le_bytes());
+    assert_eq!(
+        parse(system, &data),
+        Some(json!({
+            "program": "nonce",
+            "parsed": {
+                "type": "initialized",
+                "info": {
+                    "authority": encode(&AUTHORITY),
+                    "blockhash": encode(&[5; 32]),
+                    "feeCalculator": { "lamportsPerSignature": "5000" },
+                },
+            },
+            "space": 80,
+        }))
+    );
+    // unknown versions and states, as well as other system accounts are not parsed
+    data[..4].copy_from_slice(&2_u32.to_le_bytes());
+    assert_eq!(parse(system, &data), None);
+    assert_eq!(parse(system, &[0; 8]), None);
+}
+
+#[test]
+fn mint_decimals_cache() {
+    let mut cache = MintDecimals::new(2);
+    cache.insert([1; 32], 1);
+    cache.insert([2; 32], 2);
+    // known mints don't take more space
+    cache.insert([1; 32], 1);
+    assert_eq!(cache.len(), 2);
+    cache.insert([3; 32], 3);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get(&[1; 32]), None);
+    assert_eq!(cache.get(&[2; 32]), Some(Some(2)));
+    assert_eq!(cache.get(&[3; 32]), Some(Some(3)));
+    // missing mints take space as well, until they are found
+    cache.insert_missing([4; 32]);
+    assert_eq!(cache.get(&[4; 32]), Some(None));
+    assert_eq!(cache.get(&[2; 32]), None);
+    cache.insert([4; 32], 4);
+    assert_eq!(cache.get(&[4; 32]), Some(Some(4)));
+    assert_eq!(cache.len(), 2);
+}
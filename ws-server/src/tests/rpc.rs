@@ -0,0 +1,195 @@
+#![cfg(test)]
+use pubsub_wire::AccountData;
+use serde_json::{json, Value};
+use storage::store::Store;
+
+use crate::{rpc::handle, Commitment, Pubkey, Slot};
+
+fn account(pubkey: u8, owner: Pubkey, data: &[u8], slot: Slot) -> AccountData {
+    AccountData {
+        pubkey: [pubkey; 32],
+        owner,
+        lamports: 1,
+        data: data.to_vec().into(),
+        rent_epoch: 2,
+        executable: false,
+        slot,
+    }
+}
+
+fn store() -> Store {
+    let store = Store::temporary().unwrap();
+    for commitment in [Commitment::Processed, Commitment::Finalized] {
+        let commitment = commitment.into();
+        store
+            .put(&account(1, [0; 32], &[1, 2, 3, 4], 5), commitment)
+            .unwrap();
+        store
+            .put(&account(2, [9; 32], &[1, 2, 3, 4], 5), commitment)
+            .unwrap();
+        store
+            .put(&account(3, [9; 32], &[5, 6], 5), commitment)
+            .unwrap();
+        store.set_slot(5, commitment).unwrap();
+    }
+    store
+}
+
+fn respond(body: &[u8], store: Option<&Store>) -> Value {
+    serde_json::from_str(&handle(body, store).unwrap()).unwrap()
+}
+
+fn request(method: &str, params: Value) -> Vec<u8> {
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    serde_json::to_vec(&request).unwrap()
+}
+
+fn call(method: &str, params: Value) -> Value {
+    respond(&request(method, params), Some(&store()))
+}
+
+fn key(pubkey: u8) -> String {
+    bs58::encode([pubkey; 32]).into_string()
+}
+
+#[test]
+fn account_info() {
+    let response = call("getAccountInfo", json!([key(1), { "encoding": "base64" }]));
+    assert_eq!(
+        response,
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "context": { "slot": 5 },
+                "value": {
+                    "data": ["AQIDBA==", "base64"],
+                    "owner": "11111111111111111111111111111111",
+                    "rentEpoch": 2,
+                    "lamports": 1,
+                    "executable": false,
+                }
+            }
+        })
+    );
+}
+
+#[test]
+fn missing_account_info() {
+    let response = call("getAccountInfo", json!([key(4)]));
+    assert_eq!(response["result"]["value"], Value::Null);
+
+    // nothing has been stored with confirmed commitment
+    let response = call(
+        "getAccountInfo",
+        json!([key(1), { "commitment": "confirmed" }]),
+    );
+    assert_eq!(response["result"]["context"]["slot"], 0);
+    assert_eq!(response["result"]["value"], Value::Null);
+}
+
+#[test]
+fn multiple_accounts() {
+    let response = call(
+        "getMultipleAccounts",
+        json!([[key(1), key(4), key(3)], { "encoding": "base64", "dataSlice": { "offset": 1, "length": 1 } }]),
+    );
+    let values = &response["result"]["value"];
+    assert_eq!(values[0]["data"], json!(["Ag==", "base64"]));
+    assert_eq!(values[1], Value::Null);
+    assert_eq!(values[2]["data"], json!(["Bg==", "base64"]));
+    assert_eq!(values.as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn program_accounts() {
+    let response = call(
+        "getProgramAccounts",
+        json!([key(9), { "filters": [{ "dataSize": 4 }] }]),
+    );
+    let accounts = response["result"].as_array().unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0]["pubkey"], key(2));
+    // data is encoded as plain base58 string by default, as solana RPC does
+    assert_eq!(accounts[0]["account"]["data"], "2VfUX");
+
+    let response = call(
+        "getProgramAccounts",
+        json!([key(9), { "withContext": true, "commitment": "finalized" }]),
+    );
+    assert_eq!(response["result"]["context"]["slot"], 5);
+    assert_eq!(response["result"]["value"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn slot() {
+    assert_eq!(call("getSlot", Value::Null)["result"], 5);
+    let response = call("getSlot", json!([{ "commitment": "confirmed" }]));
+    assert_eq!(response["result"], 0);
+}
+
+#[test]
+fn errors() {
+    let response = call("getBalance", json!([key(1)]));
+    assert_eq!(response["id"], 1);
+    assert_eq!(response["error"]["code"], -32601);
+
+    let response = call("getAccountInfo", json!(["invalid"]));
+    assert_eq!(response["error"]["code"], -32602);
+
+    let response = call("getAccountInfo", json!([]));
+    assert_eq!(response["error"]["code"], -32602);
+
+    let response = call(
+        "getAccountInfo",
+        json!([key(1), { "encoding": "jsonParsed", "dataSlice": { "offset": 0, "length": 1 } }]),
+    );
+    assert_eq!(response["error"]["code"], -32602);
+
+    let response = respond(b"{", Some(&store()));
+    assert_eq!(response["id"], Value::Null);
+    assert_eq!(response["error"]["code"], -32700);
+
+    // valid JSON, which isn't a request, is told apart from malformed one
+    for body in [&b"42"[..], b"null"] {
+        let response = respond(body, Some(&store()));
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["error"]["code"], -32600);
+    }
+    // as well as requests without version, or with malformed method
+    for body in [
+        &br#"{"id":7,"method":"getSlot"}"#[..],
+        br#"{"jsonrpc":"2.0","id":7,"method":5}"#,
+    ] {
+        let response = respond(body, Some(&store()));
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["error"]["code"], -32600);
+    }
+
+    // server without storage doesn't serve any requests
+    let response = respond(&request("getSlot", Value::Null), None);
+    assert_eq!(response["id"], 1);
+    assert_eq!(response["error"]["code"], -32601);
+}
+
+#[test]
+fn batches_and_notifications() {
+    let body = json!([
+        { "jsonrpc": "2.0", "id": 1, "method": "getSlot" },
+        { "jsonrpc": "2.0", "method": "getSlot" },
+        1,
+        { "jsonrpc": "2.0", "id": "2", "method": "getSlot", "params": [{ "commitment": "confirmed" }] },
+    ]);
+    let response = respond(body.to_string().as_bytes(), Some(&store()));
+    assert_eq!(
+        response,
+        json!([
+            { "jsonrpc": "2.0", "id": 1, "result": 5 },
+            { "jsonrpc": "2.0", "id": null, "error": { "code": -32600, "message": "Invalid request" } },
+            { "jsonrpc": "2.0", "id": "2", "result": 0 },
+        ])
+    );
+    // requests without id are notifications, the same as over websocket
+    let body = br#"{"jsonrpc":"2.0","method":"getSlot"}"#;
+    assert_eq!(handle(body, Some(&store())), None);
+}
@@ -0,0 +1,68 @@
+#![cfg(test)]
+use pubsub_wire::Stamp;
+
+use crate::sequence::{LatestWrites, Sequence, SequenceTracker};
+
+fn stamp(session: u64, seq: u64) -> Stamp {
+    Stamp { session, seq }
+}
+
+#[test]
+fn sequence_gaps() {
+    let mut tracker = SequenceTracker::default();
+    assert_eq!(tracker.check(stamp(1, 0)), (Sequence::New, 0));
+    assert_eq!(tracker.check(stamp(1, 1)), (Sequence::New, 0));
+    // skipped messages are not lost yet, as they might arrive late
+    assert_eq!(tracker.check(stamp(1, 5)), (Sequence::New, 0));
+    assert_eq!(tracker.check(stamp(1, 3)), (Sequence::New, 0));
+    assert_eq!(tracker.check(stamp(1, 3)), (Sequence::Duplicate, 0));
+    assert_eq!(tracker.check(stamp(1, 1)), (Sequence::Duplicate, 0));
+    // once they fall behind reordering window, they are counted as lost
+    assert_eq!(
+        tracker.check(stamp(1, 10_000)),
+        (Sequence::New, 3 + 10_000 - 6 - 4096)
+    );
+    assert_eq!(tracker.check(stamp(1, 4)), (Sequence::Duplicate, 0));
+}
+
+#[test]
+fn sequence_sessions() {
+    let mut tracker = SequenceTracker::default();
+    // tracking starts with whatever message is received first
+    assert_eq!(tracker.check(stamp(5, 100)), (Sequence::New, 0));
+    assert_eq!(tracker.check(stamp(5, 100)), (Sequence::Duplicate, 0));
+    // restarted plugin starts counting from scratch
+    assert_eq!(tracker.check(stamp(6, 0)), (Sequence::New, 0));
+    assert_eq!(tracker.check(stamp(6, 1)), (Sequence::New, 0));
+    // late messages of previous session can't be checked anymore
+    assert_eq!(tracker.check(stamp(5, 101)), (Sequence::New, 0));
+    assert_eq!(tracker.check(stamp(6, 1)), (Sequence::Duplicate, 0));
+}
+
+#[test]
+fn stale_writes() {
+    let mut writes = LatestWrites::default();
+    let key = [1; 32];
+    assert!(writes.record(&key, 10, Some(stamp(1, 5))));
+    // writes of older slots are stale, regardless of their stamps
+    assert!(!writes.record(&key, 9, Some(stamp(1, 6))));
+    // within the same slot, writes are ordered by stamps
+    assert!(!writes.record(&key, 10, Some(stamp(1, 4))));
+    assert!(writes.record(&key, 10, Some(stamp(1, 7))));
+    assert!(writes.record(&key, 10, Some(stamp(2, 0))));
+    // writes without stamps are only ordered by slots
+    assert!(writes.record(&key, 10, None));
+    assert!(!writes.record(&key, 9, None));
+    assert!(writes.record(&[2; 32], 1, None));
+
+    // writes are forgotten once they fall behind the window
+    writes.prune(1000);
+    assert_eq!(writes.len(), 2);
+    writes.prune(1034);
+    assert_eq!(writes.len(), 1);
+    // and pruning is only done once in a while
+    writes.prune(2000);
+    assert_eq!(writes.len(), 1);
+    writes.prune(2058);
+    assert!(writes.is_empty());
+}
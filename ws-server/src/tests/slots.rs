@@ -0,0 +1,113 @@
+#![cfg(test)]
+use std::time::Duration;
+
+use actix::{clock::timeout, Actor, Addr, Context, Handler};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::StreamExt;
+
+use crate::{
+    buffer::Buffer,
+    manager::SubscriptionsRouter,
+    message::{
+        RootUpdatedMessage, SetBufferManager, SlotUpdatedMessage, SlotsUpdateKind,
+        SlotsUpdatedMessage, SubscribeMessage,
+    },
+    Commitment, Slot,
+};
+
+/// Session stub, which reports rooted slots and slot transitions
+struct Recorder {
+    roots: UnboundedSender<Slot>,
+    updates: UnboundedSender<(Slot, SlotsUpdateKind)>,
+}
+
+impl Actor for Recorder {
+    type Context = Context<Self>;
+}
+
+impl Handler<RootUpdatedMessage> for Recorder {
+    type Result = ();
+
+    fn handle(&mut self, msg: RootUpdatedMessage, _: &mut Self::Context) -> Self::Result {
+        let _ = self.roots.unbounded_send(msg.0);
+    }
+}
+
+impl Handler<SlotsUpdatedMessage> for Recorder {
+    type Result = ();
+
+    fn handle(&mut self, msg: SlotsUpdatedMessage, _: &mut Self::Context) -> Self::Result {
+        let _ = self.updates.unbounded_send((msg.slot, msg.kind));
+    }
+}
+
+async fn update_slot(
+    router: &Addr<SubscriptionsRouter>,
+    slot: Slot,
+    parent: Slot,
+    status: Commitment,
+) {
+    let slot = SlotUpdatedMessage {
+        slot,
+        parent,
+        status,
+        root: 0,
+    };
+    router.send(slot).await.unwrap();
+}
+
+/// Collect all the messages, delivered within short period of time
+async fn received<T>(rx: &mut UnboundedReceiver<T>) -> Vec<T> {
+    let mut messages = Vec::new();
+    while let Ok(Some(m)) = timeout(Duration::from_millis(200), rx.next()).await {
+        messages.push(m);
+    }
+    messages
+}
+
+#[actix::test]
+async fn roots_and_dead_forks() {
+    let router = SubscriptionsRouter::new(2);
+    let buffer = Buffer::new(router.clone());
+    router.send(SetBufferManager(buffer)).await.unwrap();
+
+    let (roots, mut roots_rx) = unbounded();
+    let (updates, mut updates_rx) = unbounded();
+    let recorder = Recorder { roots, updates }.start();
+    router
+        .send(SubscribeMessage::RootSubscribe(
+            recorder.clone().recipient(),
+        ))
+        .await
+        .unwrap();
+    router
+        .send(SubscribeMessage::SlotsUpdatesSubscribe(
+            recorder.recipient(),
+        ))
+        .await
+        .unwrap();
+
+    update_slot(&router, 1, 0, Commitment::Finalized).await;
+    update_slot(&router, 2, 1, Commitment::Processed).await;
+    // rival fork of two slots
+    update_slot(&router, 3, 1, Commitment::Processed).await;
+    update_slot(&router, 4, 3, Commitment::Processed).await;
+    update_slot(&router, 2, 1, Commitment::Confirmed).await;
+    update_slot(&router, 2, 1, Commitment::Finalized).await;
+
+    use SlotsUpdateKind::*;
+    assert_eq!(received(&mut roots_rx).await, [1, 2]);
+    assert_eq!(
+        received(&mut updates_rx).await,
+        [
+            (1, Root),
+            (2, Completed),
+            (3, Completed),
+            (4, Completed),
+            (2, OptimisticConfirmation),
+            (2, Root),
+            (3, Dead),
+            (4, Dead),
+        ]
+    );
+}
@@ -0,0 +1,143 @@
+#![cfg(test)]
+use bytes::Bytes;
+use futures::StreamExt;
+use pubsub_wire::{AccountData, Commitment, Kind, SlotData, SnapshotData, SnapshotEnd, Stamp};
+use structopt::StructOpt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::UnixListener;
+
+use crate::cli::CliOptions;
+use crate::listener::{ListenerConfig, NSQSources, Source, StreamSource, Update};
+
+fn account() -> AccountData {
+    AccountData {
+        pubkey: [1; 32],
+        owner: [2; 32],
+        lamports: 3,
+        data: Bytes::from_static(&[4, 5, 6]),
+        rent_epoch: 0,
+        executable: false,
+        slot: 10,
+    }
+}
+
+fn frame<T: serde::Serialize>(kind: Kind, payload: &T) -> Vec<u8> {
+    pubsub_wire::frame(kind, &pubsub_wire::encode(payload).unwrap())
+}
+
+#[test]
+fn parse_source() {
+    assert!(matches!(
+        "tcp://127.0.0.1:9100".parse(),
+        Ok(StreamSource::Tcp(address)) if address == "127.0.0.1:9100"
+    ));
+    assert!(matches!(
+        "unix:///tmp/pubsub.sock".parse(),
+        Ok(StreamSource::Unix(path)) if path.to_str() == Some("/tmp/pubsub.sock")
+    ));
+    assert!("http://127.0.0.1".parse::<StreamSource>().is_err());
+}
+
+fn config(args: &[&str]) -> ListenerConfig {
+    let args = std::iter::once("ws-server").chain(args.iter().copied());
+    ListenerConfig::from(&CliOptions::from_iter(args))
+}
+
+#[test]
+fn listener_config() {
+    let default = config(&["--nsqlookup", "http://127.0.0.1:4161"]);
+    assert_eq!(default.accounts, ("accounts".into(), "accounts".into()));
+    assert_eq!(default.slots, ("slots".into(), "slots".into()));
+    assert!(matches!(
+        default.sources,
+        NSQSources::Lookup(lookup) if lookup.len() == 1 && lookup.contains("http://127.0.0.1:4161")
+    ));
+
+    let custom = config(&[
+        "--account-topic=acc",
+        "--account-channel=ws",
+        "--slot-topic=sl",
+        "--slot-channel=ws-slots",
+        "--nsqd",
+        "127.0.0.1:4150",
+        "127.0.0.1:4250",
+    ]);
+    assert_eq!(custom.accounts, ("acc".into(), "ws".into()));
+    assert_eq!(custom.slots, ("sl".into(), "ws-slots".into()));
+    assert!(matches!(
+        custom.sources,
+        NSQSources::Daemons(daemons) if daemons == ["127.0.0.1:4150", "127.0.0.1:4250"]
+    ));
+
+    // ephemeral channels keep configured name as prefix, and share instance id
+    let ephemeral = config(&["--ephemeral"]);
+    let (topic, account) = ephemeral.accounts;
+    let (_, slot) = ephemeral.slots;
+    assert_eq!(topic, "accounts");
+    let account = account.strip_prefix("accounts-").unwrap();
+    let slot = slot.strip_prefix("slots-").unwrap();
+    assert_eq!(account, slot);
+    let instance = account.strip_suffix("#ephemeral").unwrap();
+    assert!(!instance.is_empty());
+    assert!(instance.chars().all(|c| c.is_ascii_hexdigit()));
+}
+
+#[actix::test]
+async fn stream_source() {
+    let dir = std::env::temp_dir().join(format!("stream-source-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("pubsub.sock");
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let mut updates = StreamSource::Unix(path).updates();
+    let slot = SlotData {
+        slot: 11,
+        parent: 10,
+        status: Commitment::Confirmed,
+    };
+    let end = SnapshotData::End(SnapshotEnd {
+        accounts: 0,
+        slot: 9,
+    });
+    let next = SlotData {
+        slot: 12,
+        parent: 11,
+        status: Commitment::Processed,
+    };
+    let batch = [&slot, &next].map(|s| pubsub_wire::encode(s).unwrap());
+    let batch = pubsub_wire::pack(&batch, true).unwrap();
+    let mut frames = frame(Kind::Snapshot, &end);
+    let stamp = Stamp { session: 1, seq: 7 };
+    let stamped = pubsub_wire::stamp(stamp, &pubsub_wire::encode(&account()).unwrap());
+    frames.extend(pubsub_wire::frame(Kind::Account, &stamped));
+    frames.extend(pubsub_wire::frame(Kind::Slot, &batch));
+
+    let server = async {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        // frames might be split between reads arbitrarily
+        for chunk in frames.chunks(7) {
+            stream.write_all(chunk).await.unwrap();
+            stream.flush().await.unwrap();
+        }
+        stream
+    };
+    let client = async {
+        // snapshot messages are skipped, and batches are unpacked
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(updates.next().await);
+        }
+        received
+    };
+    let (_stream, received) = futures::join!(server, client);
+    assert!(matches!(
+        &received[0],
+        Some(Update::Account(a)) if a.payload == account() && a.stamp == Some(stamp)
+    ));
+    assert!(matches!(&received[1], Some(Update::Slot(s)) if s.payload == slot));
+    assert!(
+        matches!(&received[2], Some(Update::Slot(s)) if s.payload == next && s.stamp.is_none())
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -3,10 +3,15 @@ use std::hash::Hash;
 
 use crate::{
     buffer::Buffer,
+    index::FilterIndex,
     manager::{SubscriptionManager, SubscriptionsRouter},
-    message::{AccountUpdatedMessage, SlotUpdatedMessage, SubscribeMessage, SubscriptionInfo},
+    message::{
+        AccountUpdatedMessage, SlotUpdatedMessage, SlotsUpdateKind, SubscribeMessage,
+        SubscriptionInfo,
+    },
     subscription::*,
-    Commitment, SubKey, SubscriptionKind,
+    types::SubscriptionsMap,
+    Commitment, SlotSubKey, SlotSubscriptionKind, SubKey, SubscriptionKind,
 };
 use actix::{Actor, Addr, Context, Handler, Message};
 
@@ -21,6 +26,10 @@ enum CountRequestMessage {
 #[rtype(result = "Addr<SubscriptionManager>")]
 struct GetAddr<T: Hash>(T);
 
+#[derive(Message)]
+#[rtype(result = "bool")]
+struct IsInterested(SubKey);
+
 struct DummyActor;
 impl Actor for DummyActor {
     type Context = Context<Self>;
@@ -55,6 +64,14 @@ impl<T: Hash> Handler<GetAddr<T>> for SubscriptionsRouter {
     }
 }
 
+impl Handler<IsInterested> for SubscriptionsRouter {
+    type Result = bool;
+
+    fn handle(&mut self, msg: IsInterested, _ctx: &mut Self::Context) -> Self::Result {
+        self.is_interested(&msg.0)
+    }
+}
+
 #[actix::test]
 async fn test_routing() {
     let router = SubscriptionsRouter::new(4);
@@ -62,6 +79,9 @@ async fn test_routing() {
         key: [1; 32],
         commitment: Commitment::Processed,
         kind: SubscriptionKind::Account,
+        encoding: Encoding::Base64,
+        slice: None,
+        filters: Vec::new().into(),
     };
     let handler = DummyActor.start();
 
@@ -69,7 +89,7 @@ async fn test_routing() {
         key: subkey.clone(),
         recipient: handler.clone().recipient(),
     }));
-    let addr = router.send(GetAddr(subkey.clone())).await.unwrap();
+    let addr = router.send(GetAddr(subkey.route())).await.unwrap();
     let mut acc_sub_count = addr
         .send(CountRequestMessage::AccountSubscriptionsCount(
             subkey.clone(),
@@ -142,7 +162,10 @@ fn parse_account_subscribe() {
             pubkey,
             options: SubOptions {
                 encoding: Encoding::Base64,
-                commitment: Commitment::Processed
+                commitment: Commitment::Processed,
+                data_slice: None,
+                filters: Vec::new(),
+                initial_value: false,
             }
         })
     );
@@ -174,7 +197,10 @@ fn parse_programs_subscribe_without_commitment() {
             pubkey,
             options: SubOptions {
                 encoding: Encoding::Base64Zstd,
-                commitment: Commitment::Finalized
+                commitment: Commitment::Finalized,
+                data_slice: None,
+                filters: Vec::new(),
+                initial_value: false,
             }
         })
     );
@@ -187,9 +213,203 @@ fn parse_slot_subscribe() {
     assert_eq!(parsed.method, Method::SlotSubscribe);
 }
 #[test]
+fn parse_slot_transitions_subscribe() {
+    let methods = [
+        ("rootSubscribe", Method::RootSubscribe),
+        ("rootUnsubscribe", Method::RootUnsubscribe),
+        ("slotsUpdatesSubscribe", Method::SlotsUpdatesSubscribe),
+        ("slotsUpdatesUnsubscribe", Method::SlotsUpdatesUnsubscribe),
+    ];
+    for (name, method) in methods {
+        let request = format!(r#"{{"jsonrpc":"2.0", "id":1, "method":"{}"}}"#, name);
+        let parsed: SubRequest = serde_json::from_str(&request).unwrap();
+        assert_eq!(parsed.method, method);
+    }
+}
+#[test]
+fn parse_slot_commitment() {
+    let request = r#"{"jsonrpc":"2.0", "id":1, "method":"slotSubscribe", "params":[{"commitment":"confirmed"}]}"#;
+    let parsed: SubRequest = serde_json::from_str(request).unwrap();
+    let options = parsed.params.slot().unwrap();
+    assert_eq!(options.commitment, Some(Commitment::Confirmed));
+
+    let request = r#"{"jsonrpc":"2.0", "id":1, "method":"slotsUpdatesSubscribe", "params":[{}]}"#;
+    let parsed: SubRequest = serde_json::from_str(request).unwrap();
+    assert_eq!(parsed.params.slot(), Some(SlotOptions::default()));
+
+    let request = r#"{"jsonrpc":"2.0", "id":1, "method":"slotSubscribe", "params":[0]}"#;
+    let parsed: SubRequest = serde_json::from_str(request).unwrap();
+    assert!(parsed.params.slot().is_none());
+}
+#[test]
+fn slot_commitment_filter() {
+    let any = SlotSubKey::new(SlotSubscriptionKind::SlotsUpdates, None);
+    let rooted = SlotSubKey::new(
+        SlotSubscriptionKind::SlotsUpdates,
+        Some(Commitment::Finalized),
+    );
+    assert!(any.matches(SlotsUpdateKind::Dead.commitment()));
+    assert!(!rooted.matches(SlotsUpdateKind::Dead.commitment()));
+    assert!(!rooted.matches(SlotsUpdateKind::OptimisticConfirmation.commitment()));
+    assert!(rooted.matches(SlotsUpdateKind::Root.commitment()));
+}
+#[test]
+fn slot_subscriptions_map() {
+    let mut map = SubscriptionsMap::default();
+    let slots = SlotSubKey::new(SlotSubscriptionKind::Slot, None);
+    let confirmed = SlotSubKey::new(SlotSubscriptionKind::Slot, Some(Commitment::Confirmed));
+    map.insert(slots, 1);
+    map.insert(confirmed, 2);
+    assert_eq!(map.get_by_key(&confirmed), Some(&2));
+    assert_eq!(map.remove_by_id(&1), Some(slots));
+    assert_eq!(map.iter().collect::<Vec<_>>(), [(&confirmed, &2)]);
+}
+#[test]
 fn parse_unsubscribe() {
     let request = r#"{"jsonrpc":"2.0", "id":1, "method":"accountUnsubscribe", "params":[0]}"#;
     let parsed: SubRequest = serde_json::from_str(request).unwrap();
     assert_eq!(parsed.method, Method::AccountUnsubscribe);
     assert_eq!(parsed.params, Params::UnsubscribeParams(0));
 }
+#[test]
+fn parse_data_slice() {
+    let request = r#"
+        {
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "accountSubscribe",
+            "params": [
+                "CM78CPUeXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNH12",
+                {
+                    "encoding": "base64",
+                    "dataSlice": { "offset": 8, "length": 32 }
+                }
+            ]
+        }
+        "#;
+    let parsed: SubRequest = serde_json::from_str(request).unwrap();
+    let options = parsed.params.sub().unwrap().options;
+    assert_eq!(
+        options.data_slice,
+        Some(DataSlice {
+            offset: 8,
+            length: 32
+        })
+    );
+}
+#[test]
+fn parse_program_filters() {
+    let request = r#"
+        {
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "programSubscribe",
+            "params": [
+                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+                {
+                    "encoding": "base64",
+                    "filters": [
+                        { "dataSize": 165 },
+                        { "memcmp": { "offset": 32, "bytes": "2VfUX" } },
+                        { "memcmp": { "offset": 0, "bytes": "AQIDBA==", "encoding": "base64" } }
+                    ]
+                }
+            ]
+        }
+        "#;
+    let parsed: SubRequest = serde_json::from_str(request).unwrap();
+    let options = parsed.params.sub().unwrap().options;
+    assert_eq!(
+        options.filters,
+        vec![
+            Filter::DataSize(165),
+            Filter::Memcmp {
+                offset: 32,
+                bytes: vec![1, 2, 3, 4]
+            },
+            Filter::Memcmp {
+                offset: 0,
+                bytes: vec![1, 2, 3, 4]
+            },
+        ]
+    );
+}
+#[test]
+fn match_program_filters() {
+    let data = [0, 1, 2, 3, 4, 5];
+    assert!(Filter::DataSize(6).matches(&data));
+    assert!(!Filter::DataSize(5).matches(&data));
+    let memcmp = |offset, bytes: &[u8]| Filter::Memcmp {
+        offset,
+        bytes: bytes.to_vec(),
+    };
+    assert!(memcmp(0, &[0, 1]).matches(&data));
+    assert!(memcmp(4, &[4, 5]).matches(&data));
+    assert!(!memcmp(4, &[4, 5, 6]).matches(&data));
+    assert!(!memcmp(7, &[]).matches(&data));
+    assert!(!memcmp(1, &[2]).matches(&data));
+}
+
+#[test]
+fn filter_index_candidates() {
+    let owner = |byte| Filter::Memcmp {
+        offset: 32,
+        bytes: vec![byte; 32],
+    };
+    let mut index = FilterIndex::default();
+    index.insert(1, &[Filter::DataSize(165), owner(1)]);
+    index.insert(2, &[owner(2)]);
+    index.insert(3, &[Filter::DataSize(165)]);
+    index.insert(4, &[]);
+
+    let mut data = vec![0; 165];
+    data[32..64].copy_from_slice(&[1; 32]);
+    let mut candidates: Vec<i32> = index.candidates(&data).copied().collect();
+    candidates.sort_unstable();
+    assert_eq!(candidates, vec![1, 3, 4]);
+
+    index.remove(&1, &[Filter::DataSize(165), owner(1)]);
+    index.remove(&3, &[Filter::DataSize(165)]);
+    index.remove(&4, &[]);
+    assert_eq!(index.candidates(&data).count(), 0);
+    index.remove(&2, &[owner(2)]);
+    assert!(index.is_empty());
+}
+
+#[actix::test]
+async fn router_interest() {
+    let router = SubscriptionsRouter::new(2);
+    let handler = DummyActor.start();
+    let key = |commitment, kind| SubKey {
+        key: [3; 32],
+        commitment,
+        kind,
+        encoding: Encoding::Base64,
+        slice: None,
+        filters: Vec::new().into(),
+    };
+    let confirmed = key(Commitment::Confirmed, SubscriptionKind::Account);
+    let finalized = key(Commitment::Finalized, SubscriptionKind::Account);
+    let program = key(Commitment::Confirmed, SubscriptionKind::Program);
+    let info = |key: &SubKey| SubscriptionInfo {
+        key: key.clone(),
+        recipient: handler.clone().recipient(),
+    };
+
+    assert!(!router.send(IsInterested(confirmed.clone())).await.unwrap());
+    router.do_send(SubscribeMessage::AccountSubscribe(info(&confirmed)));
+    router.do_send(SubscribeMessage::AccountSubscribe(info(&finalized)));
+    assert!(router.send(IsInterested(confirmed.clone())).await.unwrap());
+    // interest in account doesn't imply interest in program with the same key
+    assert!(!router.send(IsInterested(program.clone())).await.unwrap());
+
+    // interest is kept, while there's at least one subscription at any commitment
+    router.do_send(SubscribeMessage::AccountUnsubscribe(info(&confirmed)));
+    assert!(router.send(IsInterested(confirmed.clone())).await.unwrap());
+    router.do_send(SubscribeMessage::AccountUnsubscribe(info(&finalized)));
+    assert!(!router.send(IsInterested(confirmed.clone())).await.unwrap());
+
+    router.do_send(SubscribeMessage::AccountSubscribe(info(&program)));
+    assert!(router.send(IsInterested(program.clone())).await.unwrap());
+    assert!(!router.send(IsInterested(confirmed)).await.unwrap());
+}